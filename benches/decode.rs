@@ -0,0 +1,68 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::borrow::Cow;
+use tl_steam::parser::parse_tl;
+use tl_steam::runtime::de::deserialize;
+use tl_steam::runtime::ser::serialize;
+use tl_steam::runtime::{Object, Value};
+use tl_steam::schema::Schema;
+
+const SOURCE: &str = "int ? = Int;
+string ? = String;
+bytes ? = Bytes;
+vector#1cb5c415 {t:Type} # [ t ] = Vector t;
+message#5c8f1a0e id:int text:string = Message;
+file#0b7e9d21 id:int data:bytes = File;
+messages#1d3b8f44 items:(Vector Message) = Messages;
+files#2a6e4c17 items:(Vector File) = Files;
+";
+
+fn schema() -> Schema {
+  Schema::from_program(&parse_tl(SOURCE).unwrap()).unwrap()
+}
+
+/// A `messages` object of 1000 strings of up to 300 characters.
+fn strings(schema: &Schema) -> Vec<u8> {
+  let items = (0..1000)
+    .map(|i| {
+      let text = "lorem ipsum ".repeat(i % 25 + 1);
+      Value::Object(
+        Object::new("message")
+          .with("id", Value::Int(i as i32))
+          .with("text", Value::String(Cow::Owned(text))),
+      )
+    })
+    .collect();
+  let messages = Object::new("messages").with("items", Value::Vector(items));
+  serialize(schema, &Value::Object(messages)).unwrap()
+}
+
+/// A `files` object of 100 blobs of 4 KiB.
+fn bytes(schema: &Schema) -> Vec<u8> {
+  let items = (0..100u8)
+    .map(|i| {
+      let data = vec![i; 4096];
+      Value::Object(
+        Object::new("file")
+          .with("id", Value::Int(i32::from(i)))
+          .with("data", Value::Bytes(Cow::Owned(data))),
+      )
+    })
+    .collect();
+  let files = Object::new("files").with("items", Value::Vector(items));
+  serialize(schema, &Value::Object(files)).unwrap()
+}
+
+fn decode(c: &mut Criterion) {
+  let schema = schema();
+  for (name, data) in [("strings", strings(&schema)), ("bytes", bytes(&schema))].iter() {
+    c.bench_function(&format!("{}/borrowed", name), |b| {
+      b.iter(|| deserialize(&schema, black_box(data)).unwrap())
+    });
+    c.bench_function(&format!("{}/owned", name), |b| {
+      b.iter(|| deserialize(&schema, black_box(data)).unwrap().into_owned())
+    });
+  }
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -1,3 +1,5 @@
+use std::fmt;
+
 pub type Nat = u32;
 
 #[derive(Debug, Clone)]
@@ -12,6 +14,24 @@ pub enum TLUpperName {
   NameNs { ns: String, name: String },
 }
 
+impl fmt::Display for TLLowerName {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      TLLowerName::Name(name) => write!(f, "{}", name),
+      TLLowerName::NameNs { ns, name } => write!(f, "{}.{}", ns, name),
+    }
+  }
+}
+
+impl fmt::Display for TLUpperName {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      TLUpperName::Name(name) => write!(f, "{}", name),
+      TLUpperName::NameNs { ns, name } => write!(f, "{}.{}", ns, name),
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub enum TLTypeIdent {
   Lower(TLLowerName),
@@ -67,6 +87,37 @@ pub struct TLCombinator {
   pub result_type: TLExpression,
 }
 
+impl TLCName {
+  pub fn name(&self) -> Option<&TLLowerName> {
+    match self {
+      TLCName::Name(name) | TLCName::FullName(name, _) => Some(name),
+      TLCName::EmptyName => None,
+    }
+  }
+
+  /// Constructor ID written explicitly after `#`.
+  pub fn id(&self) -> Option<Nat> {
+    match self {
+      TLCName::FullName(_, id) => Some(*id),
+      _ => None,
+    }
+  }
+}
+
+impl TLCombinator {
+  /// Name of the boxed type this combinator belongs to, e.g. `User` for
+  /// `user id:int = User;`.
+  pub fn type_name(&self) -> Option<String> {
+    match &self.result_type {
+      TLExpression::Expression(exprs) => match exprs.first() {
+        Some(TLExpression::Ident(TLTypeIdent::Upper(name))) => Some(name.to_string()),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+}
+
 #[derive(Debug)]
 pub enum TLFinal {
   New(TLUpperName),
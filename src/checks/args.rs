@@ -0,0 +1,68 @@
+use super::*;
+
+/// Whether `args` has a `[ ... ]` block with no count and no `#` argument
+/// before it to take the count from. Blocks nested in another start
+/// without one, as `Schema` resolves them.
+fn uncounted_block(args: &[TLArg]) -> bool {
+  let mut counted = false;
+  for arg in args {
+    match arg {
+      TLArg::Arg(_, expr) | TLArg::OptArg(_, expr) => {
+        counted = counted || TLType::from_expression(expr) == TLType::NatType;
+      }
+      TLArg::ConditionalArg(_, _, _) => {}
+      TLArg::MultiplicityArg(_, count, args) => {
+        if (count.is_none() && !counted) || uncounted_block(args) {
+          return true;
+        }
+      }
+    }
+  }
+  false
+}
+
+/// The first `flags.N?` condition in `args` whose bit is past the 32 bits
+/// of a `#`.
+fn wide_flag_bit(args: &[TLArg]) -> Option<Nat> {
+  args.iter().find_map(|arg| match arg {
+    TLArg::ConditionalArg(_, TLCondition::Condition(_, bit), _) if *bit >= 32 => Some(*bit),
+    TLArg::MultiplicityArg(_, _, args) => wide_flag_bit(args),
+    _ => None,
+  })
+}
+
+/// The diagnostic for `combinator` having a `[ ... ]` block with nothing
+/// to count it.
+pub fn uncounted(combinator: &ResolvedCombinator) -> Diagnostic {
+  Diagnostic {
+    kind: DiagnosticKind::UncountedBlock,
+    message: format!(
+      "a `[ ... ]` block of `{}` has no count and no `#` argument before it",
+      combinator.name().unwrap_or_else(|| "_".to_string())
+    ),
+    locations: vec![combinator.location.clone()],
+  }
+}
+
+/// Checks the arguments of each combinator:
+///
+/// * a `[ ... ]` block without `n*` follows a `#` argument it repeats by;
+/// * a `flags.N?` condition tests one of bits 0 to 31.
+pub fn check_args(files: &[(&str, &TLProgram)], diagnostics: &mut Vec<Diagnostic>) {
+  for combinator in resolve_combinators(files) {
+    let name = combinator.name().unwrap_or_else(|| "_".to_string());
+    if uncounted_block(&combinator.combinator.args) {
+      diagnostics.push(uncounted(&combinator));
+    }
+    if let Some(bit) = wide_flag_bit(&combinator.combinator.args) {
+      diagnostics.push(Diagnostic {
+        kind: DiagnosticKind::FlagBitOutOfRange,
+        message: format!(
+          "`{}` has a condition on bit {}, but `#` has 32 bits",
+          name, bit
+        ),
+        locations: vec![combinator.location.clone()],
+      });
+    }
+  }
+}
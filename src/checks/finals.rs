@@ -0,0 +1,100 @@
+use super::*;
+use std::collections::HashMap;
+
+#[derive(Default)]
+struct TypeState {
+  constructor: Option<Location>,
+  new: Option<Location>,
+  final_: Option<Location>,
+  empty: Option<Location>,
+}
+
+fn final_name(fin: &TLFinal) -> String {
+  match fin {
+    TLFinal::New(name) | TLFinal::Final(name) | TLFinal::Empty(name) => name.to_string(),
+  }
+}
+
+/// Interprets `New`, `Final` and `Empty` declarations:
+///
+/// * `New T` declares a type that must not exist yet,
+/// * `Final T` forbids adding constructors to `T` afterwards,
+/// * `Empty T` declares a type without constructors, so it is `New T`
+///   and `Final T` at once.
+pub fn check_finals(files: &[(&str, &TLProgram)], diagnostics: &mut Vec<Diagnostic>) {
+  let mut types: HashMap<String, TypeState> = HashMap::new();
+  for_each_declaration(files, |location, kind, declaration| match declaration {
+    TLDeclaration::Final(fin) => {
+      let name = final_name(fin);
+      let state = types.entry(name.clone()).or_default();
+      match fin {
+        TLFinal::New(_) | TLFinal::Empty(_) => {
+          let conflict = match (fin, &state.constructor) {
+            (TLFinal::Empty(_), Some(constructor)) => Some((
+              DiagnosticKind::ConstructorOfEmptyType,
+              format!(
+                "type `{}` is declared Empty but already has constructors",
+                name
+              ),
+              constructor,
+            )),
+            _ => state
+              .new
+              .as_ref()
+              .or(state.empty.as_ref())
+              .or(state.constructor.as_ref())
+              .map(|previous| {
+                (
+                  DiagnosticKind::NewTypeRedefined,
+                  format!("type `{}` is declared as new but already exists", name),
+                  previous,
+                )
+              }),
+          };
+          if let Some((kind, message, previous)) = conflict {
+            diagnostics.push(Diagnostic {
+              kind,
+              message,
+              locations: vec![location.clone(), previous.clone()],
+            });
+          }
+          match fin {
+            TLFinal::Empty(_) => state.empty = Some(location),
+            _ => state.new = Some(location),
+          }
+        }
+        TLFinal::Final(_) => {
+          if state.final_.is_none() {
+            state.final_ = Some(location);
+          }
+        }
+      }
+    }
+    TLDeclaration::Combinator(combinator) | TLDeclaration::BuiltIn(combinator) => {
+      if kind == BlockKind::Functions {
+        return;
+      }
+      let name = match combinator.type_name() {
+        Some(name) => name,
+        None => return,
+      };
+      let state = types.entry(name.clone()).or_default();
+      if let Some(empty) = &state.empty {
+        diagnostics.push(Diagnostic {
+          kind: DiagnosticKind::ConstructorOfEmptyType,
+          message: format!("constructor added to type `{}` declared Empty", name),
+          locations: vec![location.clone(), empty.clone()],
+        });
+      } else if let Some(final_) = &state.final_ {
+        diagnostics.push(Diagnostic {
+          kind: DiagnosticKind::ConstructorOfFinalType,
+          message: format!("constructor added to type `{}` declared Final", name),
+          locations: vec![location.clone(), final_.clone()],
+        });
+      }
+      if state.constructor.is_none() {
+        state.constructor = Some(location);
+      }
+    }
+  });
+}
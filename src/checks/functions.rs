@@ -0,0 +1,115 @@
+use super::*;
+use std::collections::{HashMap, HashSet};
+
+fn contains_bang(ty: &TLType) -> bool {
+  let mut found = false;
+  ty.walk(&mut |ty| {
+    if let TLType::Bang(_) = ty {
+      found = true;
+    }
+  });
+  found
+}
+
+/// Checks that only apply to `---functions---` blocks:
+///
+/// * the result type is a boxed type declared in a types block or one of
+///   the function's `{X:Type}` parameters,
+/// * `!X` is only used in functions and only on `{X:Type}` parameters,
+/// * a function is not named like a constructor.
+pub fn check_functions(files: &[(&str, &TLProgram)], diagnostics: &mut Vec<Diagnostic>) {
+  let combinators = resolve_combinators(files);
+
+  let mut types: HashSet<String> = HashSet::new();
+  for_each_declaration(files, |_, _, declaration| {
+    if let TLDeclaration::Final(fin) = declaration {
+      match fin {
+        TLFinal::New(name) | TLFinal::Final(name) | TLFinal::Empty(name) => {
+          types.insert(name.to_string());
+        }
+      }
+    }
+  });
+  let mut constructors: HashMap<String, &Location> = HashMap::new();
+  for combinator in combinators.iter().filter(|c| c.kind == BlockKind::Types) {
+    if let Some(name) = combinator.result_type.name() {
+      types.insert(name.to_string());
+    }
+    if let Some(name) = combinator.name() {
+      constructors.entry(name).or_insert(&combinator.location);
+    }
+  }
+
+  for combinator in &combinators {
+    let name = combinator.name().unwrap_or_else(|| "_".to_string());
+    let mut args = vec![];
+    arg_types(&combinator.combinator.args, &mut args);
+
+    if combinator.kind == BlockKind::Types {
+      if args.iter().any(contains_bang) {
+        diagnostics.push(Diagnostic {
+          kind: DiagnosticKind::BangOutsideFunction,
+          message: format!(
+            "`!` used in constructor `{}`, it is only allowed in functions",
+            name
+          ),
+          locations: vec![combinator.location.clone()],
+        });
+      }
+      continue;
+    }
+
+    let params = combinator.type_params();
+    let result_name = combinator.result_type.name().unwrap_or("");
+    let is_param = params.iter().any(|param| param == result_name);
+    if is_param && !combinator.result_type.params().is_empty() {
+      diagnostics.push(Diagnostic {
+        kind: DiagnosticKind::UnknownResultType,
+        message: format!(
+          "function `{}` applies type variable `{}` to parameters",
+          name, result_name
+        ),
+        locations: vec![combinator.location.clone()],
+      });
+    } else if !is_param && !types.contains(result_name) {
+      diagnostics.push(Diagnostic {
+        kind: DiagnosticKind::UnknownResultType,
+        message: format!(
+          "function `{}` returns `{}` which is neither a declared type nor a type variable",
+          name, combinator.result_type
+        ),
+        locations: vec![combinator.location.clone()],
+      });
+    }
+
+    for arg in &args {
+      let valid = match arg {
+        TLType::Bang(inner) => match &**inner {
+          TLType::Named(var, inner_params) => {
+            inner_params.is_empty() && params.iter().any(|param| param == var)
+          }
+          _ => false,
+        },
+        arg => !contains_bang(arg),
+      };
+      if !valid {
+        diagnostics.push(Diagnostic {
+          kind: DiagnosticKind::BangOnNonTypeVariable,
+          message: format!(
+            "`{}` in function `{}`: `!` can only be applied to a {{X:Type}} parameter",
+            arg, name
+          ),
+          locations: vec![combinator.location.clone()],
+        });
+      }
+    }
+
+    if let Some(constructor) = constructors.get(&name) {
+      diagnostics.push(Diagnostic {
+        kind: DiagnosticKind::FunctionNameClash,
+        message: format!("function `{}` has the same name as a constructor", name),
+        locations: vec![combinator.location.clone(), (*constructor).clone()],
+      });
+    }
+  }
+}
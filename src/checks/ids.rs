@@ -0,0 +1,32 @@
+use super::*;
+use crate::id::computed_id;
+
+/// Recomputes the CRC32 of every combinator written with an explicit
+/// `#id` and reports the ones that do not match.
+///
+/// Not part of [`check`]: schemas in the wild carry IDs that were never
+/// derived from their text, so verification has to be asked for.
+pub fn verify_ids(files: &[(&str, &TLProgram)]) -> Vec<Diagnostic> {
+  let mut diagnostics = vec![];
+  for combinator in resolve_combinators(files) {
+    let (name, written) = match &combinator.combinator.identifier {
+      TLCName::FullName(name, id) => (name, *id),
+      _ => continue,
+    };
+    let computed = match computed_id(combinator.combinator, combinator.builtin) {
+      Some(id) => id,
+      None => continue,
+    };
+    if computed != written {
+      diagnostics.push(Diagnostic {
+        kind: DiagnosticKind::IdMismatch,
+        message: format!(
+          "`{}` is declared with ID #{:08x} but its declaration hashes to #{:08x}",
+          name, written, computed
+        ),
+        locations: vec![combinator.location],
+      });
+    }
+  }
+  diagnostics
+}
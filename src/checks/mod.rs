@@ -0,0 +1,250 @@
+use super::ast::*;
+use super::id::combinator_id;
+use super::types::TLType;
+use std::fmt;
+
+pub mod args;
+pub mod finals;
+pub mod functions;
+pub mod ids;
+pub mod names;
+pub mod params;
+
+/// Position of a declaration: the file it came from, the index of its
+/// `---types---`/`---functions---` block and its index inside that block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+  pub file: String,
+  pub block: usize,
+  pub declaration: usize,
+}
+
+impl fmt::Display for Location {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "{}: block {}, declaration {}",
+      self.file,
+      self.block + 1,
+      self.declaration + 1
+    )
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiagnosticKind {
+  ConstructorOfFinalType,
+  ConstructorOfEmptyType,
+  NewTypeRedefined,
+  DuplicateName,
+  DuplicateId,
+  IdCollision,
+  UnknownResultType,
+  BangOutsideFunction,
+  BangOnNonTypeVariable,
+  FunctionNameClash,
+  UnusedImplicitParam,
+  UnboundResultParam,
+  IdMismatch,
+  UncountedBlock,
+  FlagBitOutOfRange,
+}
+
+/// A problem found in a schema. The first location is the offending
+/// declaration, the rest point at the declarations it conflicts with.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+  pub kind: DiagnosticKind,
+  pub message: String,
+  pub locations: Vec<Location>,
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "error: {}", self.message)?;
+    for location in &self.locations {
+      write!(f, "\n  --> {}", location)?;
+    }
+    Ok(())
+  }
+}
+
+/// Runs every check over `files`, which are treated as one schema split
+/// across several sources and are walked in the given order.
+pub fn check(files: &[(&str, &TLProgram)]) -> Vec<Diagnostic> {
+  let mut diagnostics = vec![];
+  finals::check_finals(files, &mut diagnostics);
+  names::check_names(files, &mut diagnostics);
+  functions::check_functions(files, &mut diagnostics);
+  params::check_params(files, &mut diagnostics);
+  args::check_args(files, &mut diagnostics);
+  diagnostics
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockKind {
+  Types,
+  Functions,
+}
+
+/// Where the value of an implicit parameter comes from when a combinator
+/// is decoded or generated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recovery {
+  /// The parameter is the result type parameter at this position, e.g. `t`
+  /// in `vector {t:Type} # [ t ] = Vector t`.
+  ResultType(usize),
+  /// The parameter is the type of a `!X` argument with this name.
+  BangArg(String),
+  /// Nothing determines the parameter.
+  None,
+}
+
+/// An implicit `{X:Type}` or `{n:#}` parameter.
+#[derive(Debug, Clone)]
+pub struct ImplicitParam {
+  pub name: String,
+  pub ty: TLType,
+  pub recovery: Recovery,
+}
+
+/// A combinator together with where it was declared and its result type
+/// in normalized form.
+#[derive(Debug)]
+pub struct ResolvedCombinator<'a> {
+  pub location: Location,
+  pub kind: BlockKind,
+  pub builtin: bool,
+  pub combinator: &'a TLCombinator,
+  pub result_type: TLType,
+}
+
+impl<'a> ResolvedCombinator<'a> {
+  pub fn name(&self) -> Option<String> {
+    self
+      .combinator
+      .identifier
+      .name()
+      .map(|name| name.to_string())
+  }
+
+  /// Explicit `#id` or the CRC32 computed from the declaration.
+  pub fn id(&self) -> Option<Nat> {
+    combinator_id(self.combinator, self.builtin)
+  }
+
+  pub fn implicit_params(&self) -> Vec<ImplicitParam> {
+    let args = &self.combinator.args;
+    args
+      .iter()
+      .filter_map(|arg| match arg {
+        TLArg::OptArg(TLVarName::Name(name), expr) => Some((name, expr)),
+        _ => None,
+      })
+      .map(|(name, expr)| {
+        let by_result = self
+          .result_type
+          .params()
+          .iter()
+          .position(|param| match param {
+            TLType::Named(param, params) => param == name && params.is_empty(),
+            _ => false,
+          });
+        let by_bang = args.iter().find_map(|arg| match arg {
+          TLArg::Arg(arg_name, expr) => match TLType::from_expression(expr) {
+            TLType::Bang(inner) if inner.name() == Some(name.as_str()) => Some(match arg_name {
+              Some(TLVarName::Name(arg_name)) => arg_name.clone(),
+              None => String::new(),
+            }),
+            _ => None,
+          },
+          _ => None,
+        });
+        let recovery = match (by_result, by_bang) {
+          (Some(position), _) => Recovery::ResultType(position),
+          (None, Some(arg)) => Recovery::BangArg(arg),
+          (None, None) => Recovery::None,
+        };
+        ImplicitParam {
+          name: name.clone(),
+          ty: TLType::from_expression(expr),
+          recovery,
+        }
+      })
+      .collect()
+  }
+
+  /// Names of the `{X:Type}` parameters.
+  pub fn type_params(&self) -> Vec<String> {
+    self
+      .combinator
+      .args
+      .iter()
+      .filter_map(|arg| match arg {
+        TLArg::OptArg(TLVarName::Name(name), expr) => match TLType::from_expression(expr) {
+          TLType::Named(ref ty, ref params) if ty == "Type" && params.is_empty() => {
+            Some(name.clone())
+          }
+          _ => None,
+        },
+        _ => None,
+      })
+      .collect()
+  }
+}
+
+/// Types of all arguments, including the ones inside `[ ... ]` blocks.
+pub fn arg_types(args: &[TLArg], types: &mut Vec<TLType>) {
+  for arg in args {
+    match arg {
+      TLArg::Arg(_, expr) | TLArg::OptArg(_, expr) | TLArg::ConditionalArg(_, _, expr) => {
+        types.push(TLType::from_expression(expr))
+      }
+      TLArg::MultiplicityArg(_, _, args) => arg_types(args, types),
+    }
+  }
+}
+
+/// Collects every combinator of `files` in source order.
+pub fn resolve_combinators<'a>(files: &[(&str, &'a TLProgram)]) -> Vec<ResolvedCombinator<'a>> {
+  let mut combinators = vec![];
+  for_each_declaration(files, |location, kind, declaration| {
+    let (builtin, combinator) = match declaration {
+      TLDeclaration::Combinator(combinator) => (false, combinator),
+      TLDeclaration::BuiltIn(combinator) => (true, combinator),
+      TLDeclaration::Final(_) => return,
+    };
+    combinators.push(ResolvedCombinator {
+      location,
+      kind,
+      builtin,
+      combinator,
+      result_type: TLType::from_expression(&combinator.result_type),
+    });
+  });
+  combinators
+}
+
+/// Calls `f` for each declaration of `files` in source order, passing its
+/// location and the kind of block it belongs to.
+pub fn for_each_declaration<'a, F>(files: &[(&str, &'a TLProgram)], mut f: F)
+where
+  F: FnMut(Location, BlockKind, &'a TLDeclaration),
+{
+  for (file, program) in files {
+    for (block_index, block) in program.blocks.iter().enumerate() {
+      let (kind, declarations) = match block {
+        TLDeclarationBlock::Types(declarations) => (BlockKind::Types, declarations),
+        TLDeclarationBlock::Functions(declarations) => (BlockKind::Functions, declarations),
+      };
+      for (index, declaration) in declarations.iter().enumerate() {
+        let location = Location {
+          file: file.to_string(),
+          block: block_index,
+          declaration: index,
+        };
+        f(location, kind, declaration);
+      }
+    }
+  }
+}
@@ -0,0 +1,70 @@
+use super::*;
+use std::collections::HashMap;
+
+/// Reports combinators sharing a full name or a constructor ID.
+///
+/// Names only have to be unique among combinators of the same kind, clashes
+/// between functions and constructors are reported by the functions check.
+/// IDs share one space because both end up on the wire.
+pub fn check_names(files: &[(&str, &TLProgram)], diagnostics: &mut Vec<Diagnostic>) {
+  let mut names: HashMap<(BlockKind, String), (Location, Option<Nat>)> = HashMap::new();
+  let mut ids: HashMap<Nat, (Location, String)> = HashMap::new();
+  for_each_declaration(files, |location, kind, declaration| {
+    let (combinator, builtin) = match declaration {
+      TLDeclaration::Combinator(combinator) => (combinator, false),
+      TLDeclaration::BuiltIn(combinator) => (combinator, true),
+      TLDeclaration::Final(_) => return,
+    };
+    let name = match combinator.identifier.name() {
+      Some(name) => name.to_string(),
+      None => return,
+    };
+    let id = combinator_id(combinator, builtin);
+
+    if let Some(id) = id {
+      if let Some((previous, previous_name)) = ids.get(&id) {
+        let (kind, message) = if *previous_name == name {
+          (
+            DiagnosticKind::DuplicateId,
+            format!("`{}#{:08x}` is declared twice", name, id),
+          )
+        } else {
+          (
+            DiagnosticKind::IdCollision,
+            format!(
+              "`{}` and `{}` share constructor ID #{:08x}",
+              name, previous_name, id
+            ),
+          )
+        };
+        diagnostics.push(Diagnostic {
+          kind,
+          message,
+          locations: vec![location.clone(), previous.clone()],
+        });
+      } else {
+        ids.insert(id, (location.clone(), name.clone()));
+      }
+    }
+
+    match names.get(&(kind, name.clone())) {
+      Some((previous, previous_id)) => {
+        if id.is_none() || *previous_id != id {
+          let what = if kind == BlockKind::Functions {
+            "function"
+          } else {
+            "constructor"
+          };
+          diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::DuplicateName,
+            message: format!("{} `{}` is declared twice", what, name),
+            locations: vec![location, previous.clone()],
+          });
+        }
+      }
+      None => {
+        names.insert((kind, name), (location, id));
+      }
+    }
+  });
+}
@@ -0,0 +1,99 @@
+use super::*;
+use std::collections::HashSet;
+
+fn var_name(name: &Option<TLVarName>) -> Option<&str> {
+  match name {
+    Some(TLVarName::Name(name)) => Some(name),
+    None => None,
+  }
+}
+
+fn arg_mentions(arg: &TLArg, name: &str) -> bool {
+  match arg {
+    TLArg::Arg(_, expr) | TLArg::OptArg(_, expr) => TLType::from_expression(expr).mentions(name),
+    TLArg::ConditionalArg(_, TLCondition::Condition(TLVarName::Name(var), _), expr) => {
+      var == name || TLType::from_expression(expr).mentions(name)
+    }
+    TLArg::MultiplicityArg(_, count, args) => {
+      let in_count = match count {
+        Some(count) => TLType::from_expression(count).mentions(name),
+        None => false,
+      };
+      in_count || args.iter().any(|arg| arg_mentions(arg, name))
+    }
+  }
+}
+
+fn arg_names<'a>(args: &'a [TLArg], names: &mut HashSet<&'a str>) {
+  for arg in args {
+    match arg {
+      TLArg::OptArg(TLVarName::Name(name), _) => {
+        names.insert(name);
+      }
+      TLArg::Arg(name, _) | TLArg::ConditionalArg(name, _, _) => {
+        names.extend(var_name(name));
+      }
+      TLArg::MultiplicityArg(name, _, args) => {
+        names.extend(var_name(name));
+        arg_names(args, names);
+      }
+    }
+  }
+}
+
+/// Checks that every `{X:Type}`-style parameter is used by a later argument
+/// or by the result type, and that every variable in the result type is
+/// bound by an argument.
+pub fn check_params(files: &[(&str, &TLProgram)], diagnostics: &mut Vec<Diagnostic>) {
+  let combinators = resolve_combinators(files);
+  let mut known: HashSet<String> = HashSet::new();
+  for combinator in &combinators {
+    known.extend(combinator.name());
+    if combinator.kind == BlockKind::Types {
+      known.extend(combinator.result_type.name().map(|name| name.to_string()));
+    }
+  }
+
+  for combinator in &combinators {
+    let name = combinator.name().unwrap_or_else(|| "_".to_string());
+    let args = &combinator.combinator.args;
+
+    for (index, arg) in args.iter().enumerate() {
+      let param = match arg {
+        TLArg::OptArg(TLVarName::Name(param), _) => param,
+        _ => continue,
+      };
+      let used = args[index + 1..].iter().any(|arg| arg_mentions(arg, param))
+        || combinator.result_type.mentions(param);
+      if !used {
+        diagnostics.push(Diagnostic {
+          kind: DiagnosticKind::UnusedImplicitParam,
+          message: format!(
+            "implicit parameter `{}` of `{}` is not used by any argument or the result type",
+            param, name
+          ),
+          locations: vec![combinator.location.clone()],
+        });
+      }
+    }
+
+    let mut bound = HashSet::new();
+    arg_names(args, &mut bound);
+    for param in combinator.result_type.params() {
+      param.walk(&mut |ty| {
+        if let TLType::Named(var, _) = ty {
+          if !bound.contains(var.as_str()) && !known.contains(var) {
+            diagnostics.push(Diagnostic {
+              kind: DiagnosticKind::UnboundResultParam,
+              message: format!(
+                "`{}` in the result type of `{}` is not bound by any argument",
+                var, name
+              ),
+              locations: vec![combinator.location.clone()],
+            });
+          }
+        }
+      });
+    }
+  }
+}
@@ -0,0 +1,181 @@
+use super::ast::*;
+use super::lexer::TLTokenEnum;
+use super::parser::parse_tl;
+use super::types::TLType;
+use logos::Logos;
+
+/// CRC32 (IEEE 802.3), the checksum TL constructor IDs are built from.
+pub fn crc32(data: &[u8]) -> u32 {
+  let mut crc = !0u32;
+  for byte in data {
+    crc ^= u32::from(*byte);
+    for _ in 0..8 {
+      let mask = (crc & 1).wrapping_neg();
+      crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+    }
+  }
+  !crc
+}
+
+/// Types are written the way `<`/`>` are normalized away: `Vector<User>`
+/// becomes `Vector User`.
+fn write_type(ty: &TLType, text: &mut String) {
+  match ty {
+    TLType::Nat(nat) => text.push_str(&nat.to_string()),
+    TLType::NatType => text.push('#'),
+    TLType::Named(name, params) => {
+      text.push_str(name);
+      for param in params {
+        text.push(' ');
+        write_type(param, text);
+      }
+    }
+    TLType::Bare(inner) => {
+      text.push('%');
+      write_type(inner, text);
+    }
+    TLType::Bang(inner) => {
+      text.push('!');
+      write_type(inner, text);
+    }
+    TLType::Plus(base, nat) => {
+      write_type(base, text);
+      text.push('+');
+      text.push_str(&nat.to_string());
+    }
+  }
+}
+
+/// `bytes` is an alias of `string` and hashes as one.
+fn write_arg_type(expr: &TLExpression, text: &mut String) {
+  match TLType::from_expression(expr) {
+    TLType::Named(ref name, ref params) if name == "bytes" && params.is_empty() => {
+      text.push_str("string")
+    }
+    ty => write_type(&ty, text),
+  }
+}
+
+fn write_name(name: &Option<TLVarName>, text: &mut String) {
+  if let Some(TLVarName::Name(name)) = name {
+    text.push_str(name);
+    text.push(':');
+  }
+}
+
+fn write_args(args: &[TLArg], text: &mut String) {
+  for arg in args {
+    match arg {
+      TLArg::OptArg(TLVarName::Name(name), expr) => {
+        text.push(' ');
+        text.push_str(name);
+        text.push(':');
+        write_arg_type(expr, text);
+      }
+      TLArg::Arg(name, expr) => {
+        text.push(' ');
+        write_name(name, text);
+        write_arg_type(expr, text);
+      }
+      TLArg::ConditionalArg(name, TLCondition::Condition(TLVarName::Name(var), bit), expr) => {
+        // `flags.N?true` fields only set a bit and are left out of the ID
+        if let TLType::Named(ref ty, ref params) = TLType::from_expression(expr) {
+          if ty == "true" && params.is_empty() {
+            continue;
+          }
+        }
+        text.push(' ');
+        write_name(name, text);
+        text.push_str(&format!("{}.{}?", var, bit));
+        write_arg_type(expr, text);
+      }
+      TLArg::MultiplicityArg(name, count, args) => {
+        text.push(' ');
+        write_name(name, text);
+        if let Some(count) = count {
+          write_type(&TLType::from_expression(count), text);
+          text.push('*');
+        }
+        text.push('[');
+        write_args(args, text);
+        text.push_str(" ]");
+      }
+    }
+  }
+}
+
+/// Text the constructor ID of `combinator` is the CRC32 of: the
+/// declaration without its `#id` and `;`, with `{X:Type}` braces and
+/// `<`/`>` removed, `bytes` spelled `string` and `flags.N?true` fields
+/// dropped. `None` for the anonymous `_` combinator.
+pub fn normalized_text(combinator: &TLCombinator, builtin: bool) -> Option<String> {
+  let mut text = combinator.identifier.name()?.to_string();
+  if builtin {
+    text.push_str(" ?");
+  }
+  write_args(&combinator.args, &mut text);
+  text.push_str(" = ");
+  write_type(&TLType::from_expression(&combinator.result_type), &mut text);
+  Some(text)
+}
+
+/// ID computed from the declaration, ignoring any explicit `#id`.
+pub fn computed_id(combinator: &TLCombinator, builtin: bool) -> Option<Nat> {
+  normalized_text(combinator, builtin).map(|text| crc32(text.as_bytes()))
+}
+
+/// The explicit `#id` if there is one, the computed ID otherwise.
+pub fn combinator_id(combinator: &TLCombinator, builtin: bool) -> Option<Nat> {
+  match combinator.identifier {
+    TLCName::FullName(_, id) => Some(id),
+    _ => computed_id(combinator, builtin),
+  }
+}
+
+/// Rewrites every explicit `#id` in `source` that does not match the ID
+/// computed from its declaration. Everything else, comments and spacing
+/// included, is left untouched.
+pub fn fix_ids(source: &str) -> Result<String, String> {
+  let program = parse_tl(source)?;
+  let mut expected = vec![];
+  for block in &program.blocks {
+    let declarations = match block {
+      TLDeclarationBlock::Types(declarations) | TLDeclarationBlock::Functions(declarations) => {
+        declarations
+      }
+    };
+    for declaration in declarations {
+      let (combinator, builtin) = match declaration {
+        TLDeclaration::Combinator(combinator) => (combinator, false),
+        TLDeclaration::BuiltIn(combinator) => (combinator, true),
+        TLDeclaration::Final(_) => continue,
+      };
+      if let TLCName::FullName(_, _) = combinator.identifier {
+        expected.push(computed_id(combinator, builtin));
+      }
+    }
+  }
+
+  // `#hex` tokens only parse as part of a combinator name, so they line up
+  // with the explicit IDs collected above
+  let mut fixed = String::with_capacity(source.len());
+  let mut copied = 0;
+  let mut expected = expected.into_iter();
+  let mut lexer = TLTokenEnum::lexer(source);
+  while lexer.token != TLTokenEnum::END {
+    if lexer.token == TLTokenEnum::HEXNUMBER {
+      if let Some(Some(id)) = expected.next() {
+        let range = lexer.range();
+        let written = u32::from_str_radix(&source[range.start + 1..range.end], 16).ok();
+        if written != Some(id) {
+          fixed.push_str(&source[copied..range.start]);
+          fixed.push_str(&format!("#{:08x}", id));
+          copied = range.end;
+        }
+      }
+    }
+    lexer.advance();
+  }
+  fixed.push_str(&source[copied..]);
+  Ok(fixed)
+}
@@ -0,0 +1,10 @@
+pub mod ast;
+pub mod checks;
+pub mod id;
+pub mod lexer;
+pub mod lint;
+pub mod parser;
+pub mod rules;
+pub mod runtime;
+pub mod schema;
+pub mod types;
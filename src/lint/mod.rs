@@ -0,0 +1,187 @@
+use super::ast::*;
+use super::checks::{resolve_combinators, Location, ResolvedCombinator};
+use super::lexer::TLTokenEnum;
+use logos::Logos;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+pub mod rules;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+  Allow,
+  Warning,
+  Error,
+}
+
+impl Severity {
+  fn parse(level: &str) -> Option<Severity> {
+    match level {
+      "allow" | "off" => Some(Severity::Allow),
+      "warn" | "warning" => Some(Severity::Warning),
+      "error" | "deny" => Some(Severity::Error),
+      _ => None,
+    }
+  }
+}
+
+/// A named lint rule. `check` reports the offending declarations with a
+/// message for each.
+pub struct Rule {
+  pub name: &'static str,
+  pub description: &'static str,
+  pub default: Severity,
+  pub check: fn(&[ResolvedCombinator], &mut Vec<(Location, String)>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Lint {
+  pub rule: &'static str,
+  pub severity: Severity,
+  pub message: String,
+  pub location: Location,
+}
+
+impl fmt::Display for Lint {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let level = match self.severity {
+      Severity::Error => "error",
+      _ => "warning",
+    };
+    write!(
+      f,
+      "{}[{}]: {}\n  --> {}",
+      level, self.rule, self.message, self.location
+    )
+  }
+}
+
+/// Per-rule severities overriding the rule defaults.
+///
+/// The config file has one `rule = level` per line, where level is
+/// `allow`, `warn` or `error`. Lines starting with `#` are comments.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+  severities: HashMap<String, Severity>,
+}
+
+impl LintConfig {
+  pub fn parse(config: &str) -> Result<LintConfig, String> {
+    let known = rules::all()
+      .iter()
+      .map(|rule| rule.name)
+      .collect::<HashSet<_>>();
+    let mut severities = HashMap::new();
+    for (index, line) in config.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      let mut parts = line.splitn(2, '=').map(str::trim);
+      let (name, level) = match (parts.next(), parts.next()) {
+        (Some(name), Some(level)) => (name, level),
+        _ => return Err(format!("line {}: expected `rule = level`", index + 1)),
+      };
+      if !known.contains(name) {
+        return Err(format!("line {}: unknown lint rule `{}`", index + 1, name));
+      }
+      let severity = Severity::parse(level)
+        .ok_or_else(|| format!("line {}: unknown lint level `{}`", index + 1, level))?;
+      severities.insert(name.to_string(), severity);
+    }
+    Ok(LintConfig { severities })
+  }
+
+  pub fn set(&mut self, rule: &str, severity: Severity) {
+    self.severities.insert(rule.to_string(), severity);
+  }
+
+  pub fn severity(&self, rule: &Rule) -> Severity {
+    *self.severities.get(rule.name).unwrap_or(&rule.default)
+  }
+}
+
+/// Rules suppressed by `// lint: allow(rule, ...)` comments, keyed by the
+/// block and declaration indexes of the declaration following the comment.
+fn suppressions(source: &str) -> HashMap<(usize, usize), HashSet<String>> {
+  let mut suppressed: HashMap<(usize, usize), HashSet<String>> = HashMap::new();
+  let mut pending: HashSet<String> = HashSet::new();
+  let (mut block, mut declaration) = (0, 0);
+  let mut in_separator = false;
+  let mut lexer = TLTokenEnum::lexer(source);
+  while lexer.token != TLTokenEnum::END {
+    match lexer.token {
+      TLTokenEnum::COMMENT => {
+        let names = lexer.slice()[2..]
+          .trim()
+          .strip_prefix("lint:")
+          .and_then(|allow| allow.trim().strip_prefix("allow("))
+          .and_then(|allow| allow.strip_suffix(')'));
+        if let Some(names) = names {
+          pending.extend(names.split(',').map(|name| name.trim().to_string()));
+        }
+      }
+      TLTokenEnum::SEPARATOR => {
+        if !in_separator {
+          block += 1;
+          declaration = 0;
+        }
+        in_separator = !in_separator;
+      }
+      TLTokenEnum::SEMICOLON => {
+        if !pending.is_empty() {
+          suppressed
+            .entry((block, declaration))
+            .or_default()
+            .extend(pending.drain());
+        }
+        declaration += 1;
+      }
+      _ => {}
+    }
+    lexer.advance();
+  }
+  suppressed
+}
+
+/// Runs every enabled rule over `files`, given as file name, source text
+/// and parsed program.
+pub fn lint(files: &[(&str, &str, &TLProgram)], config: &LintConfig) -> Vec<Lint> {
+  let programs = files
+    .iter()
+    .map(|(name, _, program)| (*name, *program))
+    .collect::<Vec<_>>();
+  let combinators = resolve_combinators(&programs);
+  let suppressed = files
+    .iter()
+    .map(|(name, source, _)| (name.to_string(), suppressions(source)))
+    .collect::<HashMap<_, _>>();
+
+  let mut lints = vec![];
+  for rule in rules::all() {
+    let severity = config.severity(&rule);
+    if severity == Severity::Allow {
+      continue;
+    }
+    let mut found = vec![];
+    (rule.check)(&combinators, &mut found);
+    for (location, message) in found {
+      let allowed = match suppressed
+        .get(&location.file)
+        .and_then(|file| file.get(&(location.block, location.declaration)))
+      {
+        Some(rules) => rules.contains(rule.name),
+        None => false,
+      };
+      if !allowed {
+        lints.push(Lint {
+          rule: rule.name,
+          severity,
+          message,
+          location,
+        });
+      }
+    }
+  }
+  lints
+}
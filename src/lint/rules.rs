@@ -0,0 +1,198 @@
+use super::*;
+use crate::checks::BlockKind;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub fn all() -> Vec<Rule> {
+  vec![
+    Rule {
+      name: "constructor_case",
+      description: "constructor and function names are lowerCamelCase",
+      default: Severity::Warning,
+      check: constructor_case,
+    },
+    Rule {
+      name: "type_case",
+      description: "type names are UpperCamelCase",
+      default: Severity::Warning,
+      check: type_case,
+    },
+    Rule {
+      name: "field_case",
+      description: "field names contain no uppercase letters",
+      default: Severity::Warning,
+      check: field_case,
+    },
+    Rule {
+      name: "flag_bits_skipped",
+      description: "the bits of a flags field are used without gaps",
+      default: Severity::Warning,
+      check: flag_bits_skipped,
+    },
+    Rule {
+      name: "flags_field_name",
+      description: "`#` fields used in `flags.N?` conditions are named `flags`",
+      default: Severity::Warning,
+      check: flags_field_name,
+    },
+    Rule {
+      name: "mixed_namespaces",
+      description: "a constructor is in the same namespace as its type",
+      default: Severity::Warning,
+      check: mixed_namespaces,
+    },
+  ]
+}
+
+/// Splits `ns.name` into its namespace and the name itself.
+fn split_namespace(name: &str) -> (Option<&str>, &str) {
+  match name.find('.') {
+    Some(dot) => (Some(&name[..dot]), &name[dot + 1..]),
+    None => (None, name),
+  }
+}
+
+fn is_camel_case(name: &str, upper: bool) -> bool {
+  match name.chars().next() {
+    Some(first) if first.is_ascii_uppercase() == upper && first.is_ascii_alphabetic() => {
+      name.chars().all(|c| c.is_ascii_alphanumeric())
+    }
+    _ => false,
+  }
+}
+
+fn constructor_case(combinators: &[ResolvedCombinator], found: &mut Vec<(Location, String)>) {
+  for combinator in combinators {
+    if let Some(name) = combinator.name() {
+      if !is_camel_case(split_namespace(&name).1, false) {
+        found.push((
+          combinator.location.clone(),
+          format!("`{}` is not in lowerCamelCase", name),
+        ));
+      }
+    }
+  }
+}
+
+fn type_case(combinators: &[ResolvedCombinator], found: &mut Vec<(Location, String)>) {
+  for combinator in combinators {
+    if combinator.kind != BlockKind::Types {
+      continue;
+    }
+    if let Some(name) = combinator.result_type.name() {
+      if !is_camel_case(split_namespace(name).1, true) {
+        found.push((
+          combinator.location.clone(),
+          format!("type `{}` is not in UpperCamelCase", name),
+        ));
+      }
+    }
+  }
+}
+
+fn field_names<'a>(args: &'a [TLArg], names: &mut Vec<&'a str>) {
+  for arg in args {
+    match arg {
+      TLArg::Arg(Some(TLVarName::Name(name)), _)
+      | TLArg::ConditionalArg(Some(TLVarName::Name(name)), _, _) => names.push(name),
+      TLArg::MultiplicityArg(name, _, args) => {
+        if let Some(TLVarName::Name(name)) = name {
+          names.push(name);
+        }
+        field_names(args, names);
+      }
+      _ => {}
+    }
+  }
+}
+
+fn field_case(combinators: &[ResolvedCombinator], found: &mut Vec<(Location, String)>) {
+  for combinator in combinators {
+    let mut names = vec![];
+    field_names(&combinator.combinator.args, &mut names);
+    for name in names {
+      if name.chars().any(|c| c.is_ascii_uppercase()) {
+        found.push((
+          combinator.location.clone(),
+          format!("field `{}` contains uppercase letters", name),
+        ));
+      }
+    }
+  }
+}
+
+/// Bits used by `flags.N?` conditions, per flags field name.
+fn flag_bits(args: &[TLArg], bits: &mut BTreeMap<String, BTreeSet<Nat>>) {
+  for arg in args {
+    match arg {
+      TLArg::ConditionalArg(_, TLCondition::Condition(TLVarName::Name(field), bit), _) => {
+        bits.entry(field.clone()).or_default().insert(*bit);
+      }
+      TLArg::MultiplicityArg(_, _, args) => flag_bits(args, bits),
+      _ => {}
+    }
+  }
+}
+
+fn flag_bits_skipped(combinators: &[ResolvedCombinator], found: &mut Vec<(Location, String)>) {
+  for combinator in combinators {
+    let mut fields = BTreeMap::new();
+    flag_bits(&combinator.combinator.args, &mut fields);
+    for (field, bits) in fields {
+      let skipped = (0..*bits.iter().last().unwrap_or(&0))
+        .filter(|bit| !bits.contains(bit))
+        .map(|bit| bit.to_string())
+        .collect::<Vec<_>>();
+      if !skipped.is_empty() {
+        found.push((
+          combinator.location.clone(),
+          format!(
+            "`{}` skips bit(s) {} of `{}`",
+            combinator.name().unwrap_or_default(),
+            skipped.join(", "),
+            field
+          ),
+        ));
+      }
+    }
+  }
+}
+
+fn flags_field_name(combinators: &[ResolvedCombinator], found: &mut Vec<(Location, String)>) {
+  for combinator in combinators {
+    let mut fields = BTreeMap::new();
+    flag_bits(&combinator.combinator.args, &mut fields);
+    for field in fields.keys() {
+      let valid = match field.strip_prefix("flags") {
+        Some(suffix) => suffix.chars().all(|c| c.is_ascii_digit()),
+        None => false,
+      };
+      if !valid {
+        found.push((
+          combinator.location.clone(),
+          format!("flags field `{}` is not named `flags`", field),
+        ));
+      }
+    }
+  }
+}
+
+fn mixed_namespaces(combinators: &[ResolvedCombinator], found: &mut Vec<(Location, String)>) {
+  for combinator in combinators {
+    if combinator.kind != BlockKind::Types {
+      continue;
+    }
+    let (name, ty) = match (combinator.name(), combinator.result_type.name()) {
+      (Some(name), Some(ty)) => (name, ty.to_string()),
+      _ => continue,
+    };
+    if split_namespace(&name).0 != split_namespace(&ty).0 {
+      found.push((
+        combinator.location.clone(),
+        format!(
+          "constructor `{}` is not in the namespace of its type `{}`",
+          name, ty
+        ),
+      ));
+    }
+  }
+}
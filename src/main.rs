@@ -1,22 +1,189 @@
-pub mod ast;
-pub mod lexer;
-pub mod parser;
-pub mod rules;
-
-use lexer::lex;
-use nom::multi::many0;
-use parser::parse_tl;
-use rules::args::*;
-use rules::expressions::parse_expression;
+use std::env;
 use std::fs;
+use tl_steam::checks::ids::verify_ids;
+use tl_steam::id::fix_ids;
+use tl_steam::lint::{lint, LintConfig};
+use tl_steam::parser::parse_tl;
+use tl_steam::runtime::explain::{explain, explain_object};
+use tl_steam::runtime::generate::{self_test, Budget};
+use tl_steam::runtime::size::combinator_sizes;
+use tl_steam::schema::Schema;
+use tl_steam::types::TLType;
+
+/// What to print about the schema once it is read. Without one the parsed
+/// files are dumped, unless ids are checked or fixed or lints run.
+enum Mode {
+  Explain(String),
+  Sizes,
+  SelfTest(u64),
+}
+
+impl Mode {
+  fn flag(&self) -> &'static str {
+    match self {
+      Mode::Explain(_) => "--explain",
+      Mode::Sizes => "--sizes",
+      Mode::SelfTest(_) => "--self-test",
+    }
+  }
+}
 
 fn main() {
-  let contents = fs::read_to_string("/Users/terminal/Work/rust/tl-steam/example.tl")
-    .expect("Something went wrong reading the file");
-  match parse_tl(contents.as_str()) {
-    Ok(tl) => println!("{:#?}", tl),
-    Err(err) => print!("{}", err),
+  let mut paths = vec![];
+  let mut verify = false;
+  let mut fix = false;
+  let mut mode: Option<Mode> = None;
+  let mut dump_type = None;
+  let mut lint_config = None;
+  let mut args = env::args().skip(1);
+  while let Some(arg) = args.next() {
+    let chosen = match arg.as_str() {
+      "--verify-ids" => {
+        verify = true;
+        None
+      }
+      "--fix-ids" => {
+        fix = true;
+        None
+      }
+      "--sizes" => Some(Mode::Sizes),
+      "--self-test" => {
+        let seed = args.next().expect("--self-test needs a seed");
+        match seed.parse::<u64>() {
+          Ok(seed) => Some(Mode::SelfTest(seed)),
+          Err(_) => return println!("{}: not a seed", seed),
+        }
+      }
+      "--explain" => Some(Mode::Explain(args.next().expect("--explain needs a file of hex bytes"))),
+      "--type" => {
+        let ty = args.next().expect("--type needs a type");
+        match TLType::parse(&ty) {
+          Ok(ty) => dump_type = Some(ty),
+          Err(err) => return println!("{}", err),
+        }
+        None
+      }
+      "--lint" => {
+        lint_config = lint_config.or_else(|| Some(LintConfig::default()));
+        None
+      }
+      "--lint-config" => {
+        let path = args.next().expect("--lint-config needs a path");
+        let config = fs::read_to_string(&path).expect("Something went wrong reading the file");
+        match LintConfig::parse(config.as_str()) {
+          Ok(config) => lint_config = Some(config),
+          Err(err) => return println!("{}: {}", path, err),
+        }
+        None
+      }
+      _ => {
+        paths.push(arg);
+        None
+      }
+    };
+    match (&mode, chosen) {
+      (Some(mode), Some(chosen)) => {
+        return println!("{} cannot be used with {}", chosen.flag(), mode.flag())
+      }
+      (None, Some(chosen)) => mode = Some(chosen),
+      (_, None) => {}
+    }
+  }
+  let dump = mode.is_none() && !verify && !fix && lint_config.is_none();
+
+  let mut programs = vec![];
+  for path in paths {
+    let mut contents = fs::read_to_string(&path).expect("Something went wrong reading the file");
+    if fix {
+      match fix_ids(contents.as_str()) {
+        Ok(fixed) if fixed != contents => {
+          fs::write(&path, &fixed).expect("Something went wrong writing the file");
+          println!("fixed constructor IDs in {}", path);
+          contents = fixed;
+        }
+        Ok(_) => {}
+        Err(err) => return print!("{}", err),
+      }
+    }
+    match parse_tl(contents.as_str()) {
+      Ok(tl) => programs.push((path, contents, tl)),
+      Err(err) => return print!("{}", err),
+    };
+  }
+  let files = programs
+    .iter()
+    .map(|(path, _, tl)| (path.as_str(), tl))
+    .collect::<Vec<_>>();
+  if verify {
+    for diagnostic in verify_ids(&files) {
+      println!("{}", diagnostic);
+    }
+  }
+  if let Some(config) = lint_config {
+    let sources = programs
+      .iter()
+      .map(|(path, contents, tl)| (path.as_str(), contents.as_str(), tl))
+      .collect::<Vec<_>>();
+    for lint in lint(&sources, &config) {
+      println!("{}", lint);
+    }
+  }
+  let schema = match Schema::from_files(&files) {
+    Ok(schema) => schema,
+    Err(diagnostics) => {
+      for diagnostic in diagnostics {
+        println!("{}", diagnostic);
+      }
+      return;
+    }
   };
+  match mode {
+    Some(Mode::Explain(path)) => {
+      let hex = fs::read_to_string(&path).expect("Something went wrong reading the file");
+      let digits = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<_>>();
+      let data = digits
+        .chunks(2)
+        .map(|pair| match pair {
+          // A lone trailing digit or a sign is not a byte.
+          [high, low] if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+            u8::from_str_radix(&pair.iter().collect::<String>(), 16).ok()
+          }
+          _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+      match (data, &dump_type) {
+        (Some(data), Some(ty)) => print!("{}", explain(&schema, ty, &data)),
+        (Some(data), None) => print!("{}", explain_object(&schema, &data)),
+        (None, _) => println!("{}: not hex bytes", path),
+      }
+    }
+    Some(Mode::Sizes) => {
+      for (combinator, size) in combinator_sizes(&schema) {
+        println!("{}: {}", combinator.name, size);
+      }
+    }
+    Some(Mode::SelfTest(seed)) => {
+      let report = self_test(&schema, seed, 100, Budget::default());
+      for failure in &report.failures {
+        println!("{}", failure);
+      }
+      println!(
+        "{} passed, {} failed, {} skipped",
+        report.passed,
+        report.failures.len(),
+        report.skipped.len()
+      );
+    }
+    None if dump => {
+      for (_, tl) in &files {
+        println!("{:#?}", tl);
+      }
+    }
+    None => {}
+  }
   // let mut result = Vec::new();
   // let tokens = lex("1 + 2 + 8", &mut result);
   // println!("{:#?}", parse_expression(tokens));
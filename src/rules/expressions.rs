@@ -58,7 +58,7 @@ fn parse_term_operator(input: Tokens) -> TLParser<TLExpression> {
   let (i, term) = tuple((tag(TLTokenEnum::PERCENT), parse_term))(input)?;
   return Ok((
     i,
-    TLExpression::Operator(TLOperator::Bang, Box::from(term.1)),
+    TLExpression::Operator(TLOperator::Bare, Box::from(term.1)),
   ));
 }
 
@@ -0,0 +1,365 @@
+use super::de::{DecodeError, Reader};
+use super::generate::{Budget, Rng};
+use super::ser::EncodeError;
+use super::size::Size;
+use super::{Object, Value};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// ID of `boolTrue = Bool`.
+pub const BOOL_TRUE_ID: u32 = 0x9972_75b5;
+/// ID of `boolFalse = Bool`.
+pub const BOOL_FALSE_ID: u32 = 0xbc79_9737;
+
+/// Writes a value with a codec.
+pub type EncodeFn = dyn Fn(&Value, &mut Vec<u8>) -> Result<(), EncodeError> + Send + Sync;
+/// Reads a value with a codec.
+pub type DecodeFn = dyn for<'d> Fn(&mut Reader<'d>) -> Result<Value<'d>, DecodeError> + Send + Sync;
+/// Makes a random value for a codec.
+pub type GenerateFn = dyn Fn(&mut Rng, &Budget) -> Value<'static> + Send + Sync;
+
+/// Binary encoding of a builtin type such as `int`, or of a boxed type
+/// with a fixed encoding such as `Bool`.
+#[derive(Clone)]
+pub struct Codec {
+  /// The bare builtin or boxed type name the codec is used for.
+  pub name: Cow<'static, str>,
+  encode: Arc<EncodeFn>,
+  decode: Arc<DecodeFn>,
+  generate: Option<Arc<GenerateFn>>,
+  size: Option<Size>,
+}
+
+impl Codec {
+  /// A codec for `name`. The functions may be closures holding state, and
+  /// the name may be built at runtime.
+  pub fn new<N, E, D>(name: N, encode: E, decode: D) -> Codec
+  where
+    N: Into<Cow<'static, str>>,
+    E: Fn(&Value, &mut Vec<u8>) -> Result<(), EncodeError> + Send + Sync + 'static,
+    D: for<'d> Fn(&mut Reader<'d>) -> Result<Value<'d>, DecodeError> + Send + Sync + 'static,
+  {
+    Codec {
+      name: name.into(),
+      encode: Arc::new(encode),
+      decode: Arc::new(decode),
+      generate: None,
+      size: None,
+    }
+  }
+
+  /// The codec, its values taking `size` bytes. Without it the size
+  /// analysis knows nothing about them.
+  pub fn with_size(mut self, size: Size) -> Codec {
+    self.size = Some(size);
+    self
+  }
+
+  pub fn size(&self) -> Option<Size> {
+    self.size
+  }
+
+  /// The codec, with random values for `Generator` made by `generate`.
+  pub fn with_generate<G>(mut self, generate: G) -> Codec
+  where
+    G: Fn(&mut Rng, &Budget) -> Value<'static> + Send + Sync + 'static,
+  {
+    self.generate = Some(Arc::new(generate));
+    self
+  }
+
+  pub fn encode(&self, value: &Value, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    (self.encode)(value, out)
+  }
+
+  pub fn decode<'d>(&self, reader: &mut Reader<'d>) -> Result<Value<'d>, DecodeError> {
+    (self.decode)(reader)
+  }
+
+  /// A random value, `None` if the codec cannot make one.
+  pub fn generate(&self, rng: &mut Rng, budget: &Budget) -> Option<Value<'static>> {
+    self.generate.as_ref().map(|generate| generate(rng, budget))
+  }
+}
+
+pub fn builtins() -> Vec<Codec> {
+  vec![
+    Codec::new("int", encode_int, decode_int)
+      .with_generate(generate_int)
+      .with_size(Size::fixed(4)),
+    Codec::new("long", encode_long, decode_long)
+      .with_generate(generate_long)
+      .with_size(Size::fixed(8)),
+    Codec::new("double", encode_double, decode_double)
+      .with_generate(generate_double)
+      .with_size(Size::fixed(8)),
+    // one length byte and padding for the empty string
+    Codec::new("string", encode_string, decode_string)
+      .with_generate(generate_string)
+      .with_size(Size { min: 4, max: None }),
+    Codec::new("bytes", encode_string, decode_bytes)
+      .with_generate(generate_bytes)
+      .with_size(Size { min: 4, max: None }),
+    Codec::new("int128", encode_int128, decode_int128)
+      .with_generate(generate_int128)
+      .with_size(Size::fixed(16)),
+    Codec::new("int256", encode_int256, decode_int256)
+      .with_generate(generate_int256)
+      .with_size(Size::fixed(32)),
+    Codec::new("true", encode_true, decode_true)
+      .with_generate(generate_true)
+      .with_size(Size::fixed(0)),
+    Codec::new("Bool", encode_bool, decode_bool)
+      .with_generate(generate_bool)
+      .with_size(Size::fixed(4)),
+  ]
+}
+
+/// Codecs by type name, starting with the [`builtins`]. Register a codec
+/// for each `name ? = Type;` builtin a schema adds.
+#[derive(Clone)]
+pub struct Codecs {
+  codecs: HashMap<Cow<'static, str>, Codec>,
+}
+
+impl Default for Codecs {
+  fn default() -> Codecs {
+    let mut codecs = Codecs {
+      codecs: HashMap::new(),
+    };
+    for codec in builtins() {
+      codecs.register(codec);
+    }
+    codecs
+  }
+}
+
+impl Codecs {
+  /// Adds `codec`, replacing the codec registered under the same name.
+  pub fn register(&mut self, codec: Codec) {
+    self.codecs.insert(codec.name.clone(), codec);
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Codec> {
+    self.codecs.get(name)
+  }
+}
+
+fn wrong_value(expected: &str, value: &Value) -> EncodeError {
+  EncodeError::WrongValue {
+    expected: expected.to_string(),
+    found: value.kind(),
+  }
+}
+
+/// Writes a string or bytes value. Up to 253 bytes have a one byte length,
+/// longer ones `0xfe` and a three byte length. Either way the whole is
+/// padded to a multiple of four bytes.
+pub fn write_bytes(bytes: &[u8], out: &mut Vec<u8>) -> Result<(), EncodeError> {
+  let header = if bytes.len() <= 253 {
+    out.push(bytes.len() as u8);
+    1
+  } else if bytes.len() < 1 << 24 {
+    out.push(0xfe);
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes()[..3]);
+    4
+  } else {
+    return Err(EncodeError::TooLong(bytes.len()));
+  };
+  out.extend_from_slice(bytes);
+  let padding = (4 - (header + bytes.len()) % 4) % 4;
+  out.resize(out.len() + padding, 0);
+  Ok(())
+}
+
+fn encode_int(value: &Value, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+  match value {
+    Value::Int(int) => {
+      out.extend_from_slice(&int.to_le_bytes());
+      Ok(())
+    }
+    value => Err(wrong_value("int", value)),
+  }
+}
+
+fn decode_int<'d>(reader: &mut Reader<'d>) -> Result<Value<'d>, DecodeError> {
+  Ok(Value::Int(i32::from_le_bytes(reader.read_array()?)))
+}
+
+fn generate_int(rng: &mut Rng, _: &Budget) -> Value<'static> {
+  Value::Int(rng.next_u64() as i32)
+}
+
+fn encode_long(value: &Value, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+  match value {
+    Value::Long(long) => {
+      out.extend_from_slice(&long.to_le_bytes());
+      Ok(())
+    }
+    value => Err(wrong_value("long", value)),
+  }
+}
+
+fn decode_long<'d>(reader: &mut Reader<'d>) -> Result<Value<'d>, DecodeError> {
+  Ok(Value::Long(i64::from_le_bytes(reader.read_array()?)))
+}
+
+fn generate_long(rng: &mut Rng, _: &Budget) -> Value<'static> {
+  Value::Long(rng.next_u64() as i64)
+}
+
+fn encode_double(value: &Value, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+  match value {
+    Value::Double(double) => {
+      out.extend_from_slice(&double.to_le_bytes());
+      Ok(())
+    }
+    value => Err(wrong_value("double", value)),
+  }
+}
+
+fn decode_double<'d>(reader: &mut Reader<'d>) -> Result<Value<'d>, DecodeError> {
+  Ok(Value::Double(f64::from_le_bytes(reader.read_array()?)))
+}
+
+/// Exactly representable, so that values compare equal after a round trip
+/// through text.
+fn generate_double(rng: &mut Rng, _: &Budget) -> Value<'static> {
+  Value::Double((rng.next_u64() as i32) as f64 / 1024.0)
+}
+
+/// `string` and `bytes` share an encoding and take either kind of value.
+fn encode_string(value: &Value, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+  match value {
+    Value::String(string) => write_bytes(string.as_bytes(), out),
+    Value::Bytes(bytes) => write_bytes(bytes, out),
+    value => Err(wrong_value("string", value)),
+  }
+}
+
+fn decode_string<'d>(reader: &mut Reader<'d>) -> Result<Value<'d>, DecodeError> {
+  let offset = reader.offset();
+  let bytes = reader.read_bytes()?;
+  match std::str::from_utf8(bytes) {
+    Ok(string) => Ok(Value::String(Cow::Borrowed(string))),
+    Err(_) => Err(DecodeError::InvalidString { offset }),
+  }
+}
+
+fn decode_bytes<'d>(reader: &mut Reader<'d>) -> Result<Value<'d>, DecodeError> {
+  Ok(Value::Bytes(Cow::Borrowed(reader.read_bytes()?)))
+}
+
+/// Characters of generated strings: ASCII and a few that take two, three
+/// and four bytes in UTF-8.
+const ALPHABET: &[char] = &[
+  'a', 'b', 'c', 'x', 'y', 'z', 'A', 'Z', '0', '9', ' ', '_', '"', '\\', '\n', 'é', 'ж', '€', '😀',
+];
+
+fn generate_string(rng: &mut Rng, budget: &Budget) -> Value<'static> {
+  let length = rng.below(budget.max_length + 1);
+  let mut string = String::new();
+  while string.len() < length {
+    let c = ALPHABET[rng.below(ALPHABET.len())];
+    if string.len() + c.len_utf8() > length {
+      break;
+    }
+    string.push(c);
+  }
+  Value::String(Cow::Owned(string))
+}
+
+fn generate_bytes(rng: &mut Rng, budget: &Budget) -> Value<'static> {
+  let length = rng.below(budget.max_length + 1);
+  let bytes = (0..length).map(|_| rng.next_u64() as u8).collect();
+  Value::Bytes(Cow::Owned(bytes))
+}
+
+fn encode_int128(value: &Value, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+  match value {
+    Value::Int128(int) => {
+      out.extend_from_slice(int);
+      Ok(())
+    }
+    value => Err(wrong_value("int128", value)),
+  }
+}
+
+fn decode_int128<'d>(reader: &mut Reader<'d>) -> Result<Value<'d>, DecodeError> {
+  Ok(Value::Int128(reader.read_array()?))
+}
+
+fn generate_int128(rng: &mut Rng, _: &Budget) -> Value<'static> {
+  let mut int = [0; 16];
+  int.iter_mut().for_each(|byte| *byte = rng.next_u64() as u8);
+  Value::Int128(int)
+}
+
+fn encode_int256(value: &Value, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+  match value {
+    Value::Int256(int) => {
+      out.extend_from_slice(int);
+      Ok(())
+    }
+    value => Err(wrong_value("int256", value)),
+  }
+}
+
+fn decode_int256<'d>(reader: &mut Reader<'d>) -> Result<Value<'d>, DecodeError> {
+  Ok(Value::Int256(reader.read_array()?))
+}
+
+fn generate_int256(rng: &mut Rng, _: &Budget) -> Value<'static> {
+  let mut int = [0; 32];
+  int.iter_mut().for_each(|byte| *byte = rng.next_u64() as u8);
+  Value::Int256(int)
+}
+
+/// `true` has no fields and takes no space.
+fn encode_true(value: &Value, _: &mut Vec<u8>) -> Result<(), EncodeError> {
+  match value {
+    Value::Bool(true) => Ok(()),
+    Value::Object(Object {
+      constructor,
+      fields,
+    }) if constructor == "true" && fields.is_empty() => Ok(()),
+    value => Err(wrong_value("true", value)),
+  }
+}
+
+fn decode_true<'d>(_: &mut Reader<'d>) -> Result<Value<'d>, DecodeError> {
+  Ok(Value::Bool(true))
+}
+
+fn generate_true(_: &mut Rng, _: &Budget) -> Value<'static> {
+  Value::Bool(true)
+}
+
+fn encode_bool(value: &Value, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+  let id = match value {
+    Value::Bool(true) => BOOL_TRUE_ID,
+    Value::Bool(false) => BOOL_FALSE_ID,
+    Value::Object(object) if object.fields.is_empty() => match object.constructor.as_str() {
+      "boolTrue" => BOOL_TRUE_ID,
+      "boolFalse" => BOOL_FALSE_ID,
+      _ => return Err(wrong_value("Bool", value)),
+    },
+    value => return Err(wrong_value("Bool", value)),
+  };
+  out.extend_from_slice(&id.to_le_bytes());
+  Ok(())
+}
+
+fn decode_bool<'d>(reader: &mut Reader<'d>) -> Result<Value<'d>, DecodeError> {
+  let offset = reader.offset();
+  match reader.read_u32()? {
+    BOOL_TRUE_ID => Ok(Value::Bool(true)),
+    BOOL_FALSE_ID => Ok(Value::Bool(false)),
+    id => Err(DecodeError::UnknownConstructor { id, offset }),
+  }
+}
+
+fn generate_bool(rng: &mut Rng, _: &Budget) -> Value<'static> {
+  Value::Bool(rng.coin())
+}
@@ -0,0 +1,1079 @@
+use super::codec::Codecs;
+use super::size::Sizes;
+use super::value::field_key;
+use super::*;
+use crate::schema::{Combinator, Field, FieldType, Schema};
+use std::borrow::Cow;
+use std::fmt;
+use std::mem;
+
+/// Decoding failure, with the offset in the input where it happened.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+  UnknownConstructor {
+    id: u32,
+    offset: usize,
+  },
+  UnknownFunction {
+    id: u32,
+    offset: usize,
+  },
+  UnknownType(String),
+  /// The constructor at `offset` does not build the expected type.
+  WrongConstructor {
+    expected: String,
+    found: String,
+    offset: usize,
+  },
+  /// The input ends before `needed` more bytes could be read.
+  Truncated {
+    offset: usize,
+    needed: usize,
+  },
+  /// Non-zero padding after a string.
+  BadPadding {
+    offset: usize,
+  },
+  /// The count of a `[ ... ]` block does not evaluate to a number.
+  UnknownCount {
+    constructor: String,
+    field: String,
+  },
+  /// A string length prefix of `0xff`.
+  BadLength {
+    offset: usize,
+  },
+  /// A `string` that is not UTF-8. Use `bytes` for binary data.
+  InvalidString {
+    offset: usize,
+  },
+  /// Bytes left over after the value.
+  TrailingBytes {
+    offset: usize,
+  },
+  Unsupported(String),
+  /// Objects, vectors and records nested deeper than `Limits::max_depth`.
+  TooDeep {
+    offset: usize,
+    limit: usize,
+  },
+  VectorTooLong {
+    offset: usize,
+    length: u32,
+    limit: usize,
+  },
+  /// A string or bytes value longer than `Limits::max_bytes_length`.
+  BytesTooLong {
+    offset: usize,
+    length: usize,
+    limit: usize,
+  },
+  /// The decoded value would take more memory than
+  /// `Limits::max_allocation`.
+  AllocationLimit {
+    offset: usize,
+    limit: usize,
+  },
+  /// A `[ ... ]` block counting more records than `Limits::max_records`.
+  TooManyRecords {
+    offset: usize,
+    count: u32,
+    limit: usize,
+  },
+}
+
+impl fmt::Display for DecodeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      DecodeError::UnknownConstructor { id, offset } => {
+        write!(f, "unknown constructor ID #{:08x} at offset {}", id, offset)
+      }
+      DecodeError::UnknownFunction { id, offset } => {
+        write!(f, "unknown function ID #{:08x} at offset {}", id, offset)
+      }
+      DecodeError::UnknownType(name) => write!(f, "unknown type `{}`", name),
+      DecodeError::WrongConstructor {
+        expected,
+        found,
+        offset,
+      } => write!(
+        f,
+        "constructor `{}` at offset {} does not build type `{}`",
+        found, offset, expected
+      ),
+      DecodeError::Truncated { offset, needed } => write!(
+        f,
+        "input truncated at offset {}: {} more byte(s) needed",
+        offset, needed
+      ),
+      DecodeError::BadPadding { offset } => write!(f, "non-zero padding at offset {}", offset),
+      DecodeError::UnknownCount { constructor, field } => write!(
+        f,
+        "the number of records in `{}` field `{}` is unknown",
+        constructor, field
+      ),
+      DecodeError::BadLength { offset } => {
+        write!(f, "invalid string length prefix at offset {}", offset)
+      }
+      DecodeError::InvalidString { offset } => {
+        write!(f, "string at offset {} is not valid UTF-8", offset)
+      }
+      DecodeError::TrailingBytes { offset } => {
+        write!(f, "unexpected bytes after the value at offset {}", offset)
+      }
+      DecodeError::Unsupported(what) => write!(f, "{} is not supported", what),
+      DecodeError::TooDeep { offset, limit } => write!(
+        f,
+        "value at offset {} is nested more than {} levels deep",
+        offset, limit
+      ),
+      DecodeError::VectorTooLong {
+        offset,
+        length,
+        limit,
+      } => write!(
+        f,
+        "vector of {} items at offset {} is longer than the limit of {}",
+        length, offset, limit
+      ),
+      DecodeError::BytesTooLong {
+        offset,
+        length,
+        limit,
+      } => write!(
+        f,
+        "string of {} bytes at offset {} is longer than the limit of {}",
+        length, offset, limit
+      ),
+      DecodeError::AllocationLimit { offset, limit } => write!(
+        f,
+        "decoding the value at offset {} would allocate more than {} bytes",
+        offset, limit
+      ),
+      DecodeError::TooManyRecords {
+        offset,
+        count,
+        limit,
+      } => write!(
+        f,
+        "{} records at offset {} are more than the limit of {}",
+        count, offset, limit
+      ),
+    }
+  }
+}
+
+impl DecodeError {
+  /// Offset in the input the error is about, if it is about one.
+  pub fn offset(&self) -> Option<usize> {
+    match self {
+      DecodeError::UnknownConstructor { offset, .. }
+      | DecodeError::UnknownFunction { offset, .. }
+      | DecodeError::WrongConstructor { offset, .. }
+      | DecodeError::Truncated { offset, .. }
+      | DecodeError::BadPadding { offset }
+      | DecodeError::BadLength { offset }
+      | DecodeError::InvalidString { offset }
+      | DecodeError::TrailingBytes { offset }
+      | DecodeError::TooDeep { offset, .. }
+      | DecodeError::VectorTooLong { offset, .. }
+      | DecodeError::BytesTooLong { offset, .. }
+      | DecodeError::AllocationLimit { offset, .. }
+      | DecodeError::TooManyRecords { offset, .. } => Some(*offset),
+      DecodeError::UnknownType(_)
+      | DecodeError::UnknownCount { .. }
+      | DecodeError::Unsupported(_) => None,
+    }
+  }
+}
+
+/// Cursor over TL binary input, the part of decoding builtin codecs see.
+pub struct Reader<'d> {
+  data: &'d [u8],
+  offset: usize,
+  max_bytes_length: usize,
+}
+
+impl<'d> Reader<'d> {
+  pub fn new(data: &'d [u8]) -> Reader<'d> {
+    Reader {
+      data,
+      offset: 0,
+      max_bytes_length: usize::MAX,
+    }
+  }
+
+  /// Makes [`Reader::read_bytes`] fail on a length prefix over `limit`
+  /// rather than wait for that many bytes.
+  pub fn set_max_bytes_length(&mut self, limit: usize) {
+    self.max_bytes_length = limit;
+  }
+
+  /// Number of bytes read so far.
+  pub fn offset(&self) -> usize {
+    self.offset
+  }
+
+  /// Number of bytes left to read.
+  pub fn remaining(&self) -> usize {
+    self.data.len() - self.offset
+  }
+
+  /// The next `length` bytes.
+  pub fn take(&mut self, length: usize) -> Result<&'d [u8], DecodeError> {
+    if self.remaining() < length {
+      return Err(DecodeError::Truncated {
+        offset: self.offset,
+        needed: length - self.remaining(),
+      });
+    }
+    let bytes = &self.data[self.offset..self.offset + length];
+    self.offset += length;
+    Ok(bytes)
+  }
+
+  pub fn read_u32(&mut self) -> Result<u32, DecodeError> {
+    Ok(u32::from_le_bytes(self.read_array()?))
+  }
+
+  /// The next bytes as a fixed size array, e.g. `[u8; 4]`.
+  pub fn read_array<A: Default + AsMut<[u8]>>(&mut self) -> Result<A, DecodeError> {
+    let mut array = A::default();
+    let length = array.as_mut().len();
+    array.as_mut().copy_from_slice(self.take(length)?);
+    Ok(array)
+  }
+
+  /// Reads a string or bytes value, checking its length prefix and
+  /// padding.
+  pub fn read_bytes(&mut self) -> Result<&'d [u8], DecodeError> {
+    let offset = self.offset;
+    let (header, length) = match self.take(1)?[0] {
+      0xff => return Err(DecodeError::BadLength { offset }),
+      0xfe => {
+        let length = self.take(3)?;
+        (
+          4,
+          usize::from(length[0]) | usize::from(length[1]) << 8 | usize::from(length[2]) << 16,
+        )
+      }
+      length => (1, usize::from(length)),
+    };
+    if length > self.max_bytes_length {
+      return Err(DecodeError::BytesTooLong {
+        offset,
+        length,
+        limit: self.max_bytes_length,
+      });
+    }
+    let bytes = self.take(length)?;
+    let padding_offset = self.offset;
+    let padding = (4 - (header + length) % 4) % 4;
+    if self.take(padding)?.iter().any(|&byte| byte != 0) {
+      return Err(DecodeError::BadPadding {
+        offset: padding_offset,
+      });
+    }
+    Ok(bytes)
+  }
+}
+
+/// Bounds on the work decoding untrusted input may do. Going over one is
+/// an error rather than a crash or an exhausted memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+  /// Objects, vectors and `[ ... ]` records nested in each other.
+  pub max_depth: usize,
+  /// Items of a single vector.
+  pub max_vector_length: usize,
+  /// Bytes of a single string or bytes value.
+  pub max_bytes_length: usize,
+  /// Bytes the decoded value takes in memory, with strings and bytes
+  /// counted as if they were copied.
+  pub max_allocation: usize,
+  /// Records of a single `[ ... ]` block.
+  pub max_records: usize,
+  /// Times the input is decoded again while guessing the lengths of
+  /// unknown objects with `UnknownConstructors::Scan`.
+  pub max_scan_attempts: usize,
+}
+
+impl Default for Limits {
+  fn default() -> Limits {
+    Limits {
+      max_depth: 64,
+      max_vector_length: 1 << 20,
+      max_bytes_length: 1 << 24,
+      max_allocation: 256 << 20,
+      max_records: 1 << 20,
+      max_scan_attempts: 1 << 12,
+    }
+  }
+}
+
+impl Limits {
+  /// No limits, for input that is trusted.
+  pub fn none() -> Limits {
+    Limits {
+      max_depth: usize::MAX,
+      max_vector_length: usize::MAX,
+      max_bytes_length: usize::MAX,
+      max_allocation: usize::MAX,
+      max_records: usize::MAX,
+      max_scan_attempts: usize::MAX,
+    }
+  }
+}
+
+/// A run of input bytes and what a tracing [`Deserializer`] made of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+  pub offset: usize,
+  pub length: usize,
+  /// Objects, vectors and records the step is nested in.
+  pub depth: usize,
+  /// The field or `[index]` of the item the step starts.
+  pub field: Option<String>,
+  /// A constructor, an item count or a value, or the error decoding
+  /// stopped with.
+  pub text: String,
+  pub failed: bool,
+}
+
+/// What a [`Deserializer`] does with a constructor ID the schema does not
+/// know, as sent by peers on a newer layer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnknownConstructors {
+  Fail,
+  /// Keeps the object as a `Value::Unknown` when everything read after it
+  /// has a fixed size, so that where it ends is known. The value read must
+  /// be the whole input, as with [`Deserializer::finish`]: that is the
+  /// only place the end of the last unknown object can be.
+  Preserve,
+  /// Like `Preserve`, but guesses the length of an object that ends
+  /// nowhere known, trying one length after another until the rest of the
+  /// input decodes. A wrong guess that happens to decode goes unnoticed.
+  Scan,
+}
+
+/// Reads TL binary into dynamic values, looking up constructors by ID and
+/// field types in a schema. Strings and bytes are not copied: they borrow
+/// from the input.
+pub struct Deserializer<'a, 'd> {
+  schema: &'a Schema,
+  codecs: Cow<'a, Codecs>,
+  reader: Reader<'d>,
+  limits: Limits,
+  depth: usize,
+  allocated: usize,
+  trace: Option<Vec<Step>>,
+  /// Label of the next step.
+  field: Option<String>,
+  unknown: UnknownConstructors,
+  sizes: Option<Sizes<'a>>,
+  /// Bytes known to follow the value being read up to the end of the
+  /// input.
+  after: Option<usize>,
+  /// The read of the whole input under way when unknown constructors are
+  /// not errors.
+  scanning: Option<Scan>,
+}
+
+/// Attempts at reading the whole input with unknown objects in it.
+#[derive(Debug, Default)]
+struct Scan {
+  /// Length guessed for each unknown object met, and the most it can be.
+  guesses: Vec<(usize, usize)>,
+  /// Guesses used by the current attempt.
+  guessed: usize,
+  /// Unknown objects met by the current attempt, the most it may meet,
+  /// and whether one met more.
+  met: usize,
+  budget: usize,
+  over_budget: bool,
+}
+
+impl Scan {
+  /// Moves on to the next combination of guessed lengths, changing the
+  /// last guess first. Lengths grow four bytes at a time, as TL values
+  /// are aligned to four bytes.
+  fn next_guess(&mut self) -> bool {
+    while let Some((length, most)) = self.guesses.pop() {
+      if length + 4 <= most {
+        self.guesses.push((length + 4, most));
+        return true;
+      }
+    }
+    false
+  }
+}
+
+impl<'a, 'd> Deserializer<'a, 'd> {
+  pub fn new(schema: &'a Schema, data: &'d [u8]) -> Deserializer<'a, 'd> {
+    Deserializer::with_cow_codecs(schema, Cow::Owned(Codecs::default()), data)
+  }
+
+  /// Deserializer using `codecs` for builtin types.
+  pub fn with_codecs(
+    schema: &'a Schema,
+    codecs: &'a Codecs,
+    data: &'d [u8],
+  ) -> Deserializer<'a, 'd> {
+    Deserializer::with_cow_codecs(schema, Cow::Borrowed(codecs), data)
+  }
+
+  fn with_cow_codecs(
+    schema: &'a Schema,
+    codecs: Cow<'a, Codecs>,
+    data: &'d [u8],
+  ) -> Deserializer<'a, 'd> {
+    let mut deserializer = Deserializer {
+      schema,
+      codecs,
+      reader: Reader::new(data),
+      limits: Limits::default(),
+      depth: 0,
+      allocated: 0,
+      trace: None,
+      field: None,
+      unknown: UnknownConstructors::Fail,
+      sizes: None,
+      after: Some(0),
+      scanning: None,
+    };
+    deserializer.set_limits(Limits::default());
+    deserializer
+  }
+
+  /// Starts recording a [`Step`] for each constructor, count and value
+  /// read.
+  pub fn trace(&mut self) {
+    self.trace = Some(vec![]);
+  }
+
+  /// Steps recorded since tracing started.
+  pub fn take_steps(&mut self) -> Vec<Step> {
+    self.trace.as_mut().map(mem::take).unwrap_or_default()
+  }
+
+  pub fn set_limits(&mut self, limits: Limits) {
+    self.limits = limits;
+    self.reader.set_max_bytes_length(limits.max_bytes_length);
+  }
+
+  pub fn set_unknown_constructors(&mut self, unknown: UnknownConstructors) {
+    self.unknown = unknown;
+    if unknown != UnknownConstructors::Fail && self.sizes.is_none() {
+      self.sizes = Some(Sizes::with_cow_codecs(self.schema, self.codecs.clone()));
+    }
+  }
+
+  /// Number of bytes read so far.
+  pub fn offset(&self) -> usize {
+    self.reader.offset()
+  }
+
+  /// Fails unless every byte of the input has been read.
+  pub fn finish(&self) -> Result<(), DecodeError> {
+    if self.reader.remaining() > 0 {
+      return Err(DecodeError::TrailingBytes {
+        offset: self.offset(),
+      });
+    }
+    Ok(())
+  }
+
+  /// Reads a boxed object of any type: a constructor, or a function call
+  /// if the ID belongs to a function.
+  pub fn read_object(&mut self) -> Result<Value<'d>, DecodeError> {
+    if self.unknown != UnknownConstructors::Fail && self.scanning.is_none() {
+      return self.scan(Self::read_object);
+    }
+    self.allocate(mem::size_of::<Value>())?;
+    let offset = self.offset();
+    let id = self.reader.read_u32()?;
+    let combinator = match self.schema.constructor_by_id(id) {
+      Some(constructor) => constructor,
+      None => match self.schema.function_by_id(id) {
+        Some(function) => function,
+        None => {
+          return self.read_unknown(id, offset, DecodeError::UnknownConstructor { id, offset })
+        }
+      },
+    };
+    self.read_fields(combinator, &[], Some(offset))
+  }
+
+  /// Reads an instance of `ty`. Boxed types start with a constructor ID,
+  /// bare ones (`%T`, `int`, `user`) do not.
+  pub fn read(&mut self, ty: &TLType) -> Result<Value<'d>, DecodeError> {
+    if self.unknown != UnknownConstructors::Fail && self.scanning.is_none() {
+      return self.scan(|de| de.read(ty));
+    }
+    self.allocate(mem::size_of::<Value>())?;
+    match ty {
+      TLType::NatType => {
+        let offset = self.offset();
+        let nat = self.reader.read_u32()?;
+        self.step(offset, || nat.to_string());
+        Ok(Value::Nat(nat))
+      }
+      TLType::Bang(_) => self.read_call(),
+      TLType::Bare(inner) => self.read_bare(inner),
+      TLType::Named(name, params) if is_boxed(name) => self.read_boxed(name, params),
+      TLType::Named(_, _) => self.read_bare(ty),
+      TLType::Nat(_) | TLType::Plus(_, _) => Err(DecodeError::Unsupported(format!(
+        "reading a value of type `{}`",
+        ty
+      ))),
+    }
+  }
+
+  fn read_boxed(&mut self, name: &str, params: &[TLType]) -> Result<Value<'d>, DecodeError> {
+    let offset = self.offset();
+    if let Some(codec) = self.codecs.get(name) {
+      let value = codec.decode(&mut self.reader).map_err(|err| match err {
+        DecodeError::UnknownConstructor { id, offset } => self.unexpected(id, name, offset),
+        err => err,
+      })?;
+      self.step(offset, || describe(&value));
+      return Ok(value);
+    }
+    if name == "Vector" {
+      let expected = match self.schema.constructor("vector") {
+        Some(vector) => vector.id,
+        None => VECTOR_ID,
+      };
+      let id = self.reader.read_u32()?;
+      if id != expected {
+        return Err(self.unexpected(id, name, offset));
+      }
+      self.step(offset, || format!("vector#{:08x}", id));
+      return self.read_vector(params);
+    }
+
+    let constructors = self.schema.constructors_of(name);
+    if constructors.is_empty() {
+      return Err(DecodeError::UnknownType(name.to_string()));
+    }
+    let id = self.reader.read_u32()?;
+    if let [builtin] = constructors.as_slice() {
+      if builtin.builtin {
+        if id != builtin.id {
+          return Err(self.unexpected(id, name, offset));
+        }
+        self.step(offset, || format!("{}#{:08x}", builtin.name, id));
+        return self.read_builtin(&builtin.name);
+      }
+    }
+    match constructors.iter().find(|constructor| constructor.id == id) {
+      Some(constructor) => self.read_fields(constructor, params, Some(offset)),
+      None => match self.unexpected(id, name, offset) {
+        err @ DecodeError::UnknownConstructor { .. } => self.read_unknown(id, offset, err),
+        err => Err(err),
+      },
+    }
+  }
+
+  fn read_bare(&mut self, ty: &TLType) -> Result<Value<'d>, DecodeError> {
+    let (name, params) = match ty {
+      TLType::Named(name, params) => (name.as_str(), params.as_slice()),
+      ty => return self.read(ty),
+    };
+    if name == "vector" || name == "Vector" {
+      return self.read_vector(params);
+    }
+    let builtin = matches!(self.schema.constructor(name), Some(constructor) if constructor.builtin);
+    if !is_boxed(name) && (builtin || self.codecs.get(name).is_some()) {
+      return self.read_builtin(name);
+    }
+
+    let combinator = if is_boxed(name) {
+      // `%T` only says which constructor follows when there is one
+      match self.schema.constructors_of(name).as_slice() {
+        [] => return Err(DecodeError::UnknownType(name.to_string())),
+        [builtin] if builtin.builtin => return self.read_builtin(&builtin.name),
+        [constructor] => *constructor,
+        _ => {
+          return Err(DecodeError::Unsupported(format!(
+            "bare `%{}` with several constructors",
+            name
+          )))
+        }
+      }
+    } else {
+      self
+        .schema
+        .constructor(name)
+        .ok_or_else(|| DecodeError::UnknownType(name.to_string()))?
+    };
+    self.read_fields(combinator, params, None)
+  }
+
+  fn read_call(&mut self) -> Result<Value<'d>, DecodeError> {
+    let offset = self.offset();
+    let id = self.reader.read_u32()?;
+    match self.schema.function_by_id(id) {
+      Some(function) => self.read_fields(function, &[], Some(offset)),
+      None => self.read_unknown(id, offset, DecodeError::UnknownFunction { id, offset }),
+    }
+  }
+
+  /// Reads the fields of `combinator`, whose ID was read at `id_offset`
+  /// unless it is bare.
+  fn read_fields(
+    &mut self,
+    combinator: &Combinator,
+    params: &[TLType],
+    id_offset: Option<usize>,
+  ) -> Result<Value<'d>, DecodeError> {
+    match id_offset {
+      Some(offset) => self.step(offset, || {
+        format!("{}#{:08x}", combinator.name, combinator.id)
+      }),
+      None => self.step(self.offset(), || combinator.name.clone()),
+    }
+    let scope = Scope::for_combinator(combinator, params);
+    self.allocate(combinator.name.len())?;
+    let fields = self.nested(|de| de.read_block(&combinator.name, &combinator.fields, scope))?;
+    Ok(Value::Object(Object {
+      constructor: combinator.name.clone(),
+      fields,
+    }))
+  }
+
+  /// Reads the fields of the combinator `name` or of one record of one of
+  /// its `[ ... ]` blocks.
+  fn read_block(
+    &mut self,
+    name: &str,
+    fields: &[Field],
+    mut scope: Scope,
+  ) -> Result<Vec<(String, Value<'d>)>, DecodeError> {
+    let after = self.after;
+    let mut values = vec![];
+    for (index, field) in fields.iter().enumerate() {
+      let key = field_key(field);
+      self.allocate(mem::size_of::<(String, Value)>() + key.len())?;
+      self.label(|| key.clone());
+      if let Some(condition) = &field.condition {
+        // conditions only refer to `#` fields read before them
+        let flags = scope.nat(&condition.field).unwrap_or(0);
+        if flags
+          .checked_shr(condition.bit)
+          .is_none_or(|flags| flags & 1 == 0)
+        {
+          if field.is_flag() {
+            values.push((key, Value::Bool(false)));
+          }
+          continue;
+        }
+      }
+      self.after = self.after_field(fields, index, &scope, after);
+      let value = match &field.ty {
+        FieldType::Type(ty) => self.read(&scope.substitute(ty))?,
+        FieldType::Repeat(count, block) => {
+          let offset = self.offset();
+          let count = scope
+            .count(count)
+            .ok_or_else(|| DecodeError::UnknownCount {
+              constructor: name.to_string(),
+              field: key.clone(),
+            })?;
+          if count as usize > self.limits.max_records {
+            return Err(DecodeError::TooManyRecords {
+              offset,
+              count,
+              limit: self.limits.max_records,
+            });
+          }
+          self.allocate((count as usize).saturating_mul(mem::size_of::<Value>()))?;
+          self.step(offset, || plural(count, "record"));
+          let record = match plain_item(block) {
+            Some(ty) => self.fixed_size(&scope.substitute(ty)),
+            None => self.block_size(block, &scope),
+          };
+          let after = self.after;
+          let items = self.nested(|de| {
+            let mut items = vec![];
+            for index in 0..count {
+              de.after = after_items(after, count - index - 1, record);
+              de.label(|| format!("[{}]", index));
+              items.push(match plain_item(block) {
+                Some(ty) => de.read(&scope.substitute(ty))?,
+                None => {
+                  de.step(de.offset(), || "record".to_string());
+                  Value::Object(Object {
+                    constructor: String::new(),
+                    fields: de.nested(|de| de.read_block(name, block, scope.clone()))?,
+                  })
+                }
+              });
+            }
+            Ok(items)
+          })?;
+          Value::Vector(items)
+        }
+      };
+      if let Value::Nat(nat) = value {
+        scope.bind(&key, TLType::Nat(nat));
+      }
+      values.push((key, value));
+    }
+    self.after = after;
+    Ok(values)
+  }
+
+  fn read_vector(&mut self, params: &[TLType]) -> Result<Value<'d>, DecodeError> {
+    let item_type = match params {
+      [item_type] => item_type,
+      _ => return Err(DecodeError::UnknownType("Vector".to_string())),
+    };
+    let offset = self.offset();
+    let count = self.reader.read_u32()?;
+    if count as usize > self.limits.max_vector_length {
+      return Err(DecodeError::VectorTooLong {
+        offset,
+        length: count,
+        limit: self.limits.max_vector_length,
+      });
+    }
+    self.allocate((count as usize).saturating_mul(mem::size_of::<Value>()))?;
+    self.step(offset, || plural(count, "item"));
+    let item = self.fixed_size(item_type);
+    let after = self.after;
+    let items = self.nested(|de| {
+      let mut items = vec![];
+      for index in 0..count {
+        de.after = after_items(after, count - index - 1, item);
+        de.label(|| format!("[{}]", index));
+        items.push(de.read(item_type)?);
+      }
+      Ok(items)
+    })?;
+    self.after = after;
+    Ok(Value::Vector(items))
+  }
+
+  fn read_builtin(&mut self, name: &str) -> Result<Value<'d>, DecodeError> {
+    let offset = self.offset();
+    let value = match self.codecs.get(name) {
+      Some(codec) => codec.decode(&mut self.reader)?,
+      None => {
+        return Err(DecodeError::Unsupported(format!(
+          "the builtin type `{}` without a codec",
+          name
+        )))
+      }
+    };
+    self.step(offset, || describe(&value));
+    let length = match &value {
+      Value::String(string) => string.len(),
+      Value::Bytes(bytes) => bytes.len(),
+      _ => return Ok(value),
+    };
+    if length > self.limits.max_bytes_length {
+      return Err(DecodeError::BytesTooLong {
+        offset,
+        length,
+        limit: self.limits.max_bytes_length,
+      });
+    }
+    self.allocate(length)?;
+    Ok(value)
+  }
+
+  /// Keeps the object with the unknown ID `id` read at `offset` as a
+  /// `Value::Unknown` if the mode and where it ends allow, or fails with
+  /// `err`.
+  fn read_unknown(
+    &mut self,
+    id: u32,
+    offset: usize,
+    err: DecodeError,
+  ) -> Result<Value<'d>, DecodeError> {
+    let scan = match &mut self.scanning {
+      Some(scan) => scan,
+      None => return Err(err),
+    };
+    if scan.met == scan.budget {
+      scan.over_budget = true;
+      return Err(err);
+    }
+    scan.met += 1;
+    let remaining = self.reader.remaining();
+    let length = match self.after {
+      Some(after) if after <= remaining => remaining - after,
+      Some(after) => {
+        return Err(DecodeError::Truncated {
+          offset: offset + 4 + remaining,
+          needed: after - remaining,
+        })
+      }
+      None if self.unknown == UnknownConstructors::Scan => {
+        if scan.guessed == scan.guesses.len() {
+          scan.guesses.push((0, remaining));
+        }
+        scan.guessed += 1;
+        scan.guesses[scan.guessed - 1].0
+      }
+      None => return Err(err),
+    };
+    self.allocate(length)?;
+    let raw = self.reader.take(length)?;
+    self.step(offset, || format!("unknown #{:08x}, {} bytes", id, length));
+    Ok(Value::Unknown {
+      id,
+      raw: Cow::Borrowed(raw),
+    })
+  }
+
+  /// Reads the value with `read` as the whole input. With
+  /// `UnknownConstructors::Scan` it reads it again and again, changing the
+  /// guessed lengths of the unknown objects met, until it ends the input.
+  /// Guesses meeting fewer unknown objects are tried first, so that the
+  /// bytes after an unknown object are not taken for more of them. Fails
+  /// as the first attempt did when no guess works.
+  fn scan(
+    &mut self,
+    read: impl Fn(&mut Self) -> Result<Value<'d>, DecodeError>,
+  ) -> Result<Value<'d>, DecodeError> {
+    let start = self.offset();
+    let allocated = self.allocated;
+    let steps = self.trace.as_ref().map_or(0, Vec::len);
+    let guessing = self.unknown == UnknownConstructors::Scan;
+    let mut scan = Scan {
+      budget: if guessing { 0 } else { usize::MAX },
+      ..Scan::default()
+    };
+    let mut first: Option<(DecodeError, Vec<Step>)> = None;
+    let mut attempts = 0;
+    let result = loop {
+      self.reader.offset = start;
+      self.allocated = allocated;
+      self.after = Some(0);
+      self.field = None;
+      scan.guessed = 0;
+      scan.met = 0;
+      self.scanning = Some(scan);
+      let result = read(self).and_then(|value| self.finish().map(|_| value));
+      scan = self.scanning.take().unwrap_or_default();
+      let err = match result {
+        Ok(value) => break Ok(value),
+        Err(err) => err,
+      };
+      let failed = self
+        .trace
+        .as_mut()
+        .map_or(vec![], |trace| trace.split_off(steps));
+      let (err, failed) = first.get_or_insert((err, failed));
+      attempts += 1;
+      scan.guesses.truncate(scan.guessed);
+      let more = guessing
+        && (scan.next_guess() || {
+          // every guess within the budget failed: allow one more unknown
+          // object if an attempt met one
+          scan.budget += 1;
+          mem::take(&mut scan.over_budget)
+        });
+      if attempts >= self.limits.max_scan_attempts || !more {
+        if let Some(trace) = &mut self.trace {
+          trace.append(failed);
+        }
+        break Err(err.clone());
+      }
+    };
+    result
+  }
+
+  /// Bytes known to follow field `index` of `fields` up to the end of the
+  /// input, `after` following the fields.
+  fn after_field(
+    &mut self,
+    fields: &[Field],
+    index: usize,
+    scope: &Scope,
+    after: Option<usize>,
+  ) -> Option<usize> {
+    let mut after = after?;
+    for field in &fields[index + 1..] {
+      after = after.checked_add(self.field_size(field, scope)?)?;
+    }
+    Some(after)
+  }
+
+  /// Size of `field` if the values read so far make it fixed.
+  fn field_size(&mut self, field: &Field, scope: &Scope) -> Option<usize> {
+    if let Some(condition) = &field.condition {
+      let flags = scope.nat(&condition.field)?;
+      if flags
+        .checked_shr(condition.bit)
+        .is_none_or(|flags| flags & 1 == 0)
+      {
+        return Some(0);
+      }
+    }
+    match &field.ty {
+      FieldType::Type(ty) => self.fixed_size(&scope.substitute(ty)),
+      FieldType::Repeat(count, block) => {
+        let count = scope.count(count)?;
+        let record = match plain_item(block) {
+          Some(ty) => self.fixed_size(&scope.substitute(ty))?,
+          None => self.block_size(block, scope)?,
+        };
+        record.checked_mul(count as usize)
+      }
+    }
+  }
+
+  fn block_size(&mut self, fields: &[Field], scope: &Scope) -> Option<usize> {
+    let mut size = 0usize;
+    for field in fields {
+      size = size.checked_add(self.field_size(field, scope)?)?;
+    }
+    Some(size)
+  }
+
+  fn fixed_size(&mut self, ty: &TLType) -> Option<usize> {
+    let size = self.sizes.as_mut()?.type_size(ty);
+    if size.is_fixed() {
+      Some(size.min)
+    } else {
+      None
+    }
+  }
+
+  /// Runs `read` one level deeper.
+  fn nested<T>(
+    &mut self,
+    read: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+  ) -> Result<T, DecodeError> {
+    if self.depth >= self.limits.max_depth {
+      return Err(DecodeError::TooDeep {
+        offset: self.offset(),
+        limit: self.limits.max_depth,
+      });
+    }
+    self.depth += 1;
+    let result = read(self);
+    if let Err(err) = &result {
+      self.fail(err);
+    }
+    self.depth -= 1;
+    result
+  }
+
+  /// Names the field or item the next step starts when tracing.
+  fn label(&mut self, field: impl FnOnce() -> String) {
+    if self.trace.is_some() {
+      self.field = Some(field());
+    }
+  }
+
+  /// Records the bytes read since `offset` when tracing.
+  fn step(&mut self, offset: usize, text: impl FnOnce() -> String) {
+    if self.trace.is_none() {
+      return;
+    }
+    let step = Step {
+      offset,
+      length: self.offset().saturating_sub(offset),
+      depth: self.depth,
+      field: self.field.take(),
+      text: text(),
+      failed: false,
+    };
+    if let Some(trace) = &mut self.trace {
+      trace.push(step);
+    }
+  }
+
+  /// Records where decoding stopped, once, at the innermost level.
+  fn fail(&mut self, err: &DecodeError) {
+    if let Some(Step { failed: true, .. }) = self.trace.as_ref().and_then(|trace| trace.last()) {
+      return;
+    }
+    let offset = err.offset().unwrap_or_else(|| self.offset());
+    self.step(offset, || err.to_string());
+    if let Some(step) = self.trace.as_mut().and_then(|trace| trace.last_mut()) {
+      step.length = 0;
+      step.failed = true;
+    }
+  }
+
+  /// Counts `size` more bytes towards `Limits::max_allocation`.
+  fn allocate(&mut self, size: usize) -> Result<(), DecodeError> {
+    self.allocated = self.allocated.saturating_add(size);
+    if self.allocated > self.limits.max_allocation {
+      return Err(DecodeError::AllocationLimit {
+        offset: self.offset(),
+        limit: self.limits.max_allocation,
+      });
+    }
+    Ok(())
+  }
+
+  /// Error for a constructor ID that is not one of type `expected`.
+  fn unexpected(&self, id: u32, expected: &str, offset: usize) -> DecodeError {
+    match self.schema.constructor_by_id(id) {
+      Some(constructor) => DecodeError::WrongConstructor {
+        expected: expected.to_string(),
+        found: constructor.name.clone(),
+        offset,
+      },
+      None => DecodeError::UnknownConstructor { id, offset },
+    }
+  }
+}
+
+/// Bytes known to follow an item with `items` more of fixed size `item`
+/// after it, `after` following them all.
+fn after_items(after: Option<usize>, items: u32, item: Option<usize>) -> Option<usize> {
+  match items {
+    0 => after,
+    items => after?.checked_add(item?.checked_mul(items as usize)?),
+  }
+}
+
+fn plural(count: u32, noun: &str) -> String {
+  match count {
+    1 => format!("1 {}", noun),
+    count => format!("{} {}s", count, noun),
+  }
+}
+
+/// Short text for a value in a trace.
+fn describe(value: &Value) -> String {
+  match value {
+    Value::Nat(nat) => nat.to_string(),
+    Value::Bool(value) => value.to_string(),
+    Value::Int(int) => int.to_string(),
+    Value::Long(long) => long.to_string(),
+    Value::Double(double) => double.to_string(),
+    Value::String(string) => format!("{:?}", string),
+    Value::Bytes(bytes) => format!("{} bytes", bytes.len()),
+    value => value.kind(),
+  }
+}
+
+/// Deserializes a boxed object or function call taking up all of `data`.
+/// Strings and bytes in the result borrow from `data`.
+pub fn deserialize<'d>(schema: &Schema, data: &'d [u8]) -> Result<Value<'d>, DecodeError> {
+  let mut deserializer = Deserializer::new(schema, data);
+  let value = deserializer.read_object()?;
+  deserializer.finish()?;
+  Ok(value)
+}
+
+/// Deserializes an instance of `ty`, e.g. `Vector User` or `%User`, taking
+/// up all of `data`.
+pub fn deserialize_as<'d>(
+  schema: &Schema,
+  ty: &TLType,
+  data: &'d [u8],
+) -> Result<Value<'d>, DecodeError> {
+  let mut deserializer = Deserializer::new(schema, data);
+  let value = deserializer.read(ty)?;
+  deserializer.finish()?;
+  Ok(value)
+}
@@ -0,0 +1,96 @@
+use super::de::{DecodeError, Deserializer, Step};
+use super::*;
+use crate::schema::Schema;
+
+/// Input bytes shown on each line of a dump.
+const BYTES_PER_LINE: usize = 8;
+
+/// Annotated hex dump of `data` decoded as an instance of `ty`: for each
+/// constructor ID, count and value its offset, its bytes and what it was
+/// read as, indented by nesting. A line marked `!!` shows where and why
+/// decoding failed, with the bytes from there on.
+pub fn explain(schema: &Schema, ty: &TLType, data: &[u8]) -> String {
+  let mut deserializer = Deserializer::new(schema, data);
+  deserializer.trace();
+  let result = deserializer.read(ty).map(|_| ());
+  dump(deserializer, result, data)
+}
+
+/// Annotated hex dump of `data` decoded as a boxed object or function
+/// call of any type.
+pub fn explain_object(schema: &Schema, data: &[u8]) -> String {
+  let mut deserializer = Deserializer::new(schema, data);
+  deserializer.trace();
+  let result = deserializer.read_object().map(|_| ());
+  dump(deserializer, result, data)
+}
+
+fn dump(mut deserializer: Deserializer, result: Result<(), DecodeError>, data: &[u8]) -> String {
+  let result = result.and_then(|_| deserializer.finish());
+  let offset = deserializer.offset();
+  let mut steps = deserializer.take_steps();
+  if let Err(err) = &result {
+    if !steps.last().is_some_and(|step| step.failed) {
+      steps.push(Step {
+        offset: err.offset().unwrap_or(offset),
+        length: 0,
+        depth: 0,
+        field: None,
+        text: err.to_string(),
+        failed: true,
+      });
+    }
+  }
+
+  let mut text = String::new();
+  for step in &steps {
+    let mut annotation = "  ".repeat(step.depth);
+    if let Some(field) = &step.field {
+      annotation.push_str(field);
+      annotation.push_str(": ");
+    }
+    annotation.push_str(&step.text);
+    let bytes = if step.failed {
+      &data[step.offset.min(data.len())..]
+    } else {
+      &data[step.offset..step.offset + step.length]
+    };
+    let marker = if step.failed { "!!" } else { "  " };
+    write_line(&mut text, step.offset, chunk(bytes, 0), marker, &annotation);
+    if step.failed {
+      continue;
+    }
+    for start in (BYTES_PER_LINE..bytes.len()).step_by(BYTES_PER_LINE) {
+      write_line(
+        &mut text,
+        step.offset + start,
+        chunk(bytes, start),
+        "  ",
+        "",
+      );
+    }
+  }
+  text
+}
+
+fn chunk(bytes: &[u8], start: usize) -> &[u8] {
+  &bytes[start.min(bytes.len())..(start + BYTES_PER_LINE).min(bytes.len())]
+}
+
+fn write_line(text: &mut String, offset: usize, bytes: &[u8], marker: &str, annotation: &str) {
+  let hex = bytes
+    .iter()
+    .map(|byte| format!("{:02x}", byte))
+    .collect::<Vec<_>>()
+    .join(" ");
+  let line = format!(
+    "{} {:06x}  {:width$}  {}",
+    marker,
+    offset,
+    hex,
+    annotation,
+    width = BYTES_PER_LINE * 3 - 1
+  );
+  text.push_str(line.trim_end());
+  text.push('\n');
+}
@@ -0,0 +1,1175 @@
+//! A serde data format for TL binary, so types deriving `Serialize` and
+//! `Deserialize` can be written and read without a hand-written codec.
+//!
+//! Structs, tuple structs, unit structs and enum variants are constructors
+//! and their names give the constructor ID, in one of three forms:
+//!
+//! - `user#d23c81a3`, with the ID spelled out;
+//! - a whole declaration such as `user flags:# id:int = User`, whose ID is
+//!   computed the same way as for a schema;
+//! - a plain `user`, looked up in the schema the format is bound to.
+//!
+//! Use `#[serde(rename = "...")]` to give a type or variant its name. An
+//! enum reads and writes the ID of its variant; a newtype variant writes
+//! the value it wraps bare after it, so `User(UserData)` with `UserData`
+//! a struct is the same as a struct variant.
+//!
+//! Other values map to TL types as follows: `bool` is `Bool`, `u32` is
+//! `#`, other integers up to 32 bits are `int`, 64 bit integers `long`,
+//! 128 bit integers `int128`, floats `double`, `str` and `char` `string`,
+//! byte slices `bytes`, sequences boxed `Vector`, [`BareVector`]s bare
+//! `%vector` and tuples their items one after the other. Maps are not
+//! supported.
+//!
+//! A struct carries its `#` fields like any other. With a schema bound,
+//! `flags.N?true` fields are `bool`s that are left out of the output and
+//! read back from the flags, conditional fields read as `Option`s, and the
+//! bits of a `#` field that conditional fields refer to are written as
+//! the fields present say, its other bits as given. Without a schema,
+//! flags are written as given and reading an `Option` fails.
+
+use super::codec::{write_bytes, BOOL_FALSE_ID, BOOL_TRUE_ID};
+use super::de::{DecodeError, Reader};
+use super::ser::EncodeError;
+use super::VECTOR_ID;
+use crate::ast::{TLDeclaration, TLDeclarationBlock};
+use crate::id::combinator_id;
+use crate::parser::parse_tl;
+use crate::schema::{Combinator, FieldType, Schema};
+use crate::types::TLType;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+  Encode(EncodeError),
+  Decode(DecodeError),
+  /// A struct or variant name that does not give a constructor ID.
+  UnknownName(String),
+  /// The ID at `offset` is not the one of the struct or of any variant
+  /// of the enum expected there.
+  WrongConstructor {
+    expected: String,
+    found: u32,
+    offset: usize,
+  },
+  Unsupported(String),
+  /// An error raised by a `Serialize` or `Deserialize` implementation.
+  Message(String),
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Error::Encode(err) => err.fmt(f),
+      Error::Decode(err) => err.fmt(f),
+      Error::UnknownName(name) => write!(f, "no constructor ID for `{}`", name),
+      Error::WrongConstructor {
+        expected,
+        found,
+        offset,
+      } => write!(
+        f,
+        "constructor ID #{:08x} at offset {} is not one of `{}`",
+        found, offset, expected
+      ),
+      Error::Unsupported(what) => write!(f, "{} is not supported", what),
+      Error::Message(message) => f.write_str(message),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+  fn custom<T: fmt::Display>(message: T) -> Error {
+    Error::Message(message.to_string())
+  }
+}
+
+impl de::Error for Error {
+  fn custom<T: fmt::Display>(message: T) -> Error {
+    Error::Message(message.to_string())
+  }
+}
+
+impl From<EncodeError> for Error {
+  fn from(err: EncodeError) -> Error {
+    Error::Encode(err)
+  }
+}
+
+impl From<DecodeError> for Error {
+  fn from(err: DecodeError) -> Error {
+    Error::Decode(err)
+  }
+}
+
+/// Constructor IDs of struct and variant names, computed once per name.
+struct Names<'a> {
+  schema: Option<&'a Schema>,
+  ids: HashMap<&'static str, u32>,
+}
+
+impl<'a> Names<'a> {
+  fn new(schema: Option<&'a Schema>) -> Names<'a> {
+    Names {
+      schema,
+      ids: HashMap::new(),
+    }
+  }
+
+  fn id(&mut self, name: &'static str) -> Result<u32, Error> {
+    if let Some(id) = self.ids.get(name) {
+      return Ok(*id);
+    }
+    let id = if name.contains('=') {
+      declaration_id(name)
+    } else if let Some(hash) = name.find('#') {
+      u32::from_str_radix(&name[hash + 1..], 16).ok()
+    } else {
+      self.combinator(name).map(|combinator| combinator.id)
+    };
+    let id = id.ok_or_else(|| Error::UnknownName(name.to_string()))?;
+    self.ids.insert(name, id);
+    Ok(id)
+  }
+
+  /// The bound schema's constructor or function for `name`, which says
+  /// which fields are flags.
+  fn combinator(&self, name: &str) -> Option<&'a Combinator> {
+    let schema = self.schema?;
+    let name = name.split(|c: char| c == '#' || c.is_whitespace()).next()?;
+    schema.constructor(name).or_else(|| schema.function(name))
+  }
+}
+
+/// ID of a single declaration given without its `;`.
+fn declaration_id(declaration: &str) -> Option<u32> {
+  let program = parse_tl(&format!("{};", declaration)).ok()?;
+  let declarations = match program.blocks.first()? {
+    TLDeclarationBlock::Types(declarations) | TLDeclarationBlock::Functions(declarations) => {
+      declarations
+    }
+  };
+  match declarations.first()? {
+    TLDeclaration::Combinator(combinator) => combinator_id(combinator, false),
+    TLDeclaration::BuiltIn(combinator) => combinator_id(combinator, true),
+    TLDeclaration::Final(_) => None,
+  }
+}
+
+/// A `%vector`: a sequence written as its count and items, without the
+/// `Vector` constructor ID a `Vec` has.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BareVector<T>(pub Vec<T>);
+
+/// Name of the newtype struct a [`BareVector`] is.
+const BARE_VECTOR: &str = "%vector";
+
+impl<T: Serialize> Serialize for BareVector<T> {
+  fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_newtype_struct(BARE_VECTOR, &self.0)
+  }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for BareVector<T> {
+  fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<BareVector<T>, D::Error> {
+    struct BareVectorVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for BareVectorVisitor<T> {
+      type Value = BareVector<T>;
+
+      fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a bare vector")
+      }
+
+      fn visit_newtype_struct<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+      ) -> Result<BareVector<T>, D::Error> {
+        Vec::deserialize(deserializer).map(BareVector)
+      }
+    }
+
+    deserializer.deserialize_newtype_struct(BARE_VECTOR, BareVectorVisitor(PhantomData))
+  }
+}
+
+/// Writes serde values as TL binary. Flags are computed only with a
+/// schema bound, which says which fields they are for.
+pub struct Serializer<'a> {
+  names: Names<'a>,
+  out: Vec<u8>,
+  /// Set by a newtype variant, which writes the ID itself: the struct it
+  /// wraps is written bare.
+  bare: bool,
+  /// Set by a [`BareVector`]: the sequence it wraps has no ID.
+  bare_vector: bool,
+  /// Set by an absent `Option`.
+  none: bool,
+}
+
+impl<'a> Default for Serializer<'a> {
+  fn default() -> Serializer<'a> {
+    Serializer {
+      names: Names::new(None),
+      out: vec![],
+      bare: false,
+      bare_vector: false,
+      none: false,
+    }
+  }
+}
+
+impl<'a> Serializer<'a> {
+  /// Serializer taking constructor IDs from type and variant names only.
+  pub fn new() -> Serializer<'a> {
+    Serializer::default()
+  }
+
+  /// Serializer that also looks names up in `schema`.
+  pub fn with_schema(schema: &'a Schema) -> Serializer<'a> {
+    Serializer {
+      names: Names::new(Some(schema)),
+      ..Serializer::default()
+    }
+  }
+
+  pub fn finish(self) -> Vec<u8> {
+    self.out
+  }
+
+  fn write_u32(&mut self, value: u32) {
+    self.out.extend_from_slice(&value.to_le_bytes());
+  }
+
+  fn write_id(&mut self, name: &'static str) -> Result<(), Error> {
+    let id = self.names.id(name)?;
+    self.write_u32(id);
+    Ok(())
+  }
+
+  /// Writes the ID of a constructor unless it is the one a newtype
+  /// variant wraps.
+  fn write_constructor(&mut self, name: &'static str) -> Result<Option<&'a Combinator>, Error> {
+    if !std::mem::replace(&mut self.bare, false) {
+      self.write_id(name)?;
+    }
+    Ok(self.names.combinator(name))
+  }
+
+  fn compound<'s>(&'s mut self, combinator: Option<&'a Combinator>) -> Compound<'s, 'a> {
+    Compound {
+      ser: self,
+      combinator,
+      flags: vec![],
+    }
+  }
+}
+
+pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+  let mut serializer = Serializer::new();
+  value.serialize(&mut serializer)?;
+  Ok(serializer.finish())
+}
+
+pub fn to_bytes_with_schema<T: Serialize + ?Sized>(
+  schema: &Schema,
+  value: &T,
+) -> Result<Vec<u8>, Error> {
+  let mut serializer = Serializer::with_schema(schema);
+  value.serialize(&mut serializer)?;
+  Ok(serializer.finish())
+}
+
+impl<'s, 'a> ser::Serializer for &'s mut Serializer<'a> {
+  type Ok = ();
+  type Error = Error;
+  type SerializeSeq = Compound<'s, 'a>;
+  type SerializeTuple = Compound<'s, 'a>;
+  type SerializeTupleStruct = Compound<'s, 'a>;
+  type SerializeTupleVariant = Compound<'s, 'a>;
+  type SerializeMap = Impossible<(), Error>;
+  type SerializeStruct = Compound<'s, 'a>;
+  type SerializeStructVariant = Compound<'s, 'a>;
+
+  fn is_human_readable(&self) -> bool {
+    false
+  }
+
+  fn serialize_bool(self, value: bool) -> Result<(), Error> {
+    self.write_u32(if value { BOOL_TRUE_ID } else { BOOL_FALSE_ID });
+    Ok(())
+  }
+
+  fn serialize_i8(self, value: i8) -> Result<(), Error> {
+    self.serialize_i32(i32::from(value))
+  }
+
+  fn serialize_i16(self, value: i16) -> Result<(), Error> {
+    self.serialize_i32(i32::from(value))
+  }
+
+  fn serialize_i32(self, value: i32) -> Result<(), Error> {
+    self.out.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+  }
+
+  fn serialize_i64(self, value: i64) -> Result<(), Error> {
+    self.out.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+  }
+
+  fn serialize_i128(self, value: i128) -> Result<(), Error> {
+    self.out.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+  }
+
+  fn serialize_u8(self, value: u8) -> Result<(), Error> {
+    self.serialize_i32(i32::from(value))
+  }
+
+  fn serialize_u16(self, value: u16) -> Result<(), Error> {
+    self.serialize_i32(i32::from(value))
+  }
+
+  fn serialize_u32(self, value: u32) -> Result<(), Error> {
+    self.write_u32(value);
+    Ok(())
+  }
+
+  fn serialize_u64(self, value: u64) -> Result<(), Error> {
+    self.out.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+  }
+
+  fn serialize_u128(self, value: u128) -> Result<(), Error> {
+    self.out.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+  }
+
+  fn serialize_f32(self, value: f32) -> Result<(), Error> {
+    self.serialize_f64(f64::from(value))
+  }
+
+  fn serialize_f64(self, value: f64) -> Result<(), Error> {
+    self.out.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+  }
+
+  fn serialize_char(self, value: char) -> Result<(), Error> {
+    self.serialize_str(value.encode_utf8(&mut [0; 4]))
+  }
+
+  fn serialize_str(self, value: &str) -> Result<(), Error> {
+    self.serialize_bytes(value.as_bytes())
+  }
+
+  fn serialize_bytes(self, value: &[u8]) -> Result<(), Error> {
+    Ok(write_bytes(value, &mut self.out)?)
+  }
+
+  /// Absent conditional fields take no space.
+  fn serialize_none(self) -> Result<(), Error> {
+    self.none = true;
+    Ok(())
+  }
+
+  fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+    value.serialize(self)
+  }
+
+  fn serialize_unit(self) -> Result<(), Error> {
+    Ok(())
+  }
+
+  fn serialize_unit_struct(self, name: &'static str) -> Result<(), Error> {
+    self.write_constructor(name)?;
+    Ok(())
+  }
+
+  fn serialize_unit_variant(
+    self,
+    _: &'static str,
+    _: u32,
+    variant: &'static str,
+  ) -> Result<(), Error> {
+    self.bare = false;
+    self.write_id(variant)
+  }
+
+  fn serialize_newtype_struct<T: Serialize + ?Sized>(
+    self,
+    name: &'static str,
+    value: &T,
+  ) -> Result<(), Error> {
+    self.bare_vector = name == BARE_VECTOR;
+    value.serialize(self)
+  }
+
+  fn serialize_newtype_variant<T: Serialize + ?Sized>(
+    self,
+    _: &'static str,
+    _: u32,
+    variant: &'static str,
+    value: &T,
+  ) -> Result<(), Error> {
+    self.write_id(variant)?;
+    self.bare = true;
+    let result = value.serialize(&mut *self);
+    self.bare = false;
+    result
+  }
+
+  fn serialize_seq(self, length: Option<usize>) -> Result<Compound<'s, 'a>, Error> {
+    let length = length.ok_or_else(|| Error::Unsupported("a sequence of unknown length".into()))?;
+    self.bare = false;
+    if !std::mem::replace(&mut self.bare_vector, false) {
+      self.write_u32(VECTOR_ID);
+    }
+    self.write_u32(length as u32);
+    Ok(self.compound(None))
+  }
+
+  fn serialize_tuple(self, _: usize) -> Result<Compound<'s, 'a>, Error> {
+    self.bare = false;
+    Ok(self.compound(None))
+  }
+
+  fn serialize_tuple_struct(self, name: &'static str, _: usize) -> Result<Compound<'s, 'a>, Error> {
+    let combinator = self.write_constructor(name)?;
+    Ok(self.compound(combinator))
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _: &'static str,
+    _: u32,
+    variant: &'static str,
+    _: usize,
+  ) -> Result<Compound<'s, 'a>, Error> {
+    self.bare = false;
+    self.write_id(variant)?;
+    let combinator = self.names.combinator(variant);
+    Ok(self.compound(combinator))
+  }
+
+  fn serialize_map(self, _: Option<usize>) -> Result<Impossible<(), Error>, Error> {
+    Err(Error::Unsupported("a map".into()))
+  }
+
+  fn serialize_struct(self, name: &'static str, _: usize) -> Result<Compound<'s, 'a>, Error> {
+    let combinator = self.write_constructor(name)?;
+    Ok(self.compound(combinator))
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _: &'static str,
+    _: u32,
+    variant: &'static str,
+    _: usize,
+  ) -> Result<Compound<'s, 'a>, Error> {
+    self.bare = false;
+    self.write_id(variant)?;
+    let combinator = self.names.combinator(variant);
+    Ok(self.compound(combinator))
+  }
+}
+
+/// The fields of a constructor or the items of a sequence or tuple.
+pub struct Compound<'s, 'a> {
+  ser: &'s mut Serializer<'a>,
+  combinator: Option<&'a Combinator>,
+  flags: Vec<Flags<'a>>,
+}
+
+/// A `#` field written, whose bits for conditional fields are set once
+/// the struct ends.
+struct Flags<'a> {
+  field: &'a str,
+  /// Offset of the field in the output.
+  at: usize,
+  /// Bits conditional fields refer to, and those of the fields present.
+  mask: u32,
+  bits: u32,
+}
+
+impl<'s, 'a> Compound<'s, 'a> {
+  fn item<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+    value.serialize(&mut *self.ser)
+  }
+
+  /// Writes a field unless the bound schema makes it a `flags.N?true`
+  /// bit, which the flags field holds, and notes whether a conditional
+  /// field is present.
+  fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+    let (combinator, field) = match self.combinator {
+      Some(combinator) => match combinator.field(key) {
+        Some(field) => (combinator, field),
+        None => return self.item(value),
+      },
+      None => return self.item(value),
+    };
+    let at = self.ser.out.len();
+    let present = if field.is_flag() {
+      let mut probe = Serializer::new();
+      value.serialize(&mut probe)?;
+      probe.out == BOOL_TRUE_ID.to_le_bytes()
+    } else {
+      self.ser.none = false;
+      self.item(value)?;
+      !(self.ser.none && self.ser.out.len() == at)
+    };
+    if let Some(condition) = &field.condition {
+      let flags = self
+        .flags
+        .iter_mut()
+        .find(|flags| flags.field == condition.field);
+      if let (Some(flags), true) = (flags, present) {
+        flags.bits |= 1u32.checked_shl(condition.bit).unwrap_or(0);
+      }
+    }
+    let mask = combinator
+      .fields
+      .iter()
+      .filter_map(|field| field.condition.as_ref())
+      .filter(|condition| condition.field == key)
+      .fold(0, |mask, condition| {
+        mask | 1u32.checked_shl(condition.bit).unwrap_or(0)
+      });
+    if mask != 0 {
+      self.flags.push(Flags {
+        field: field.name.as_deref().unwrap_or_default(),
+        at,
+        mask,
+        bits: 0,
+      });
+    }
+    Ok(())
+  }
+
+  /// Rewrites each flags field with the bits of the fields present.
+  fn end_struct(self) -> Result<(), Error> {
+    for flags in &self.flags {
+      let bytes = match self.ser.out.get_mut(flags.at..flags.at + 4) {
+        Some(bytes) => bytes,
+        None => continue,
+      };
+      let mut given = [0; 4];
+      given.copy_from_slice(bytes);
+      let nat = u32::from_le_bytes(given) & !flags.mask | flags.bits;
+      bytes.copy_from_slice(&nat.to_le_bytes());
+    }
+    Ok(())
+  }
+}
+
+impl<'s, 'a> ser::SerializeSeq for Compound<'s, 'a> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+    self.item(value)
+  }
+
+  fn end(self) -> Result<(), Error> {
+    Ok(())
+  }
+}
+
+impl<'s, 'a> ser::SerializeTuple for Compound<'s, 'a> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+    self.item(value)
+  }
+
+  fn end(self) -> Result<(), Error> {
+    Ok(())
+  }
+}
+
+impl<'s, 'a> ser::SerializeTupleStruct for Compound<'s, 'a> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+    self.item(value)
+  }
+
+  fn end(self) -> Result<(), Error> {
+    Ok(())
+  }
+}
+
+impl<'s, 'a> ser::SerializeTupleVariant for Compound<'s, 'a> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+    self.item(value)
+  }
+
+  fn end(self) -> Result<(), Error> {
+    Ok(())
+  }
+}
+
+impl<'s, 'a> ser::SerializeStruct for Compound<'s, 'a> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> Result<(), Error> {
+    self.field(key, value)
+  }
+
+  fn end(self) -> Result<(), Error> {
+    self.end_struct()
+  }
+}
+
+impl<'s, 'a> ser::SerializeStructVariant for Compound<'s, 'a> {
+  type Ok = ();
+  type Error = Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> Result<(), Error> {
+    self.field(key, value)
+  }
+
+  fn end(self) -> Result<(), Error> {
+    self.end_struct()
+  }
+}
+
+/// Whether the struct field about to be read is in the input, according
+/// to its `flags.N?` condition.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Presence {
+  /// No schema says, or the value is not a struct field.
+  Unknown,
+  Present,
+  Absent,
+  /// A `flags.N?true` bit, which takes no space either way.
+  Flag(bool),
+}
+
+/// Reads serde values from TL binary. Strings and bytes can be borrowed
+/// from the input.
+pub struct Deserializer<'a, 'd> {
+  names: Names<'a>,
+  reader: Reader<'d>,
+  /// Set by a newtype variant, which reads the ID itself: the struct it
+  /// wraps is read bare.
+  bare: bool,
+  /// Set by a [`BareVector`]: the sequence it wraps has no ID.
+  bare_vector: bool,
+  presence: Presence,
+  /// The last `#` read, kept for the conditions of later fields.
+  nat: u32,
+}
+
+impl<'a, 'd> Deserializer<'a, 'd> {
+  /// Deserializer taking constructor IDs from type and variant names only.
+  pub fn new(data: &'d [u8]) -> Deserializer<'a, 'd> {
+    Deserializer {
+      names: Names::new(None),
+      reader: Reader::new(data),
+      bare: false,
+      bare_vector: false,
+      presence: Presence::Unknown,
+      nat: 0,
+    }
+  }
+
+  /// Deserializer that also looks names up in `schema`.
+  pub fn with_schema(schema: &'a Schema, data: &'d [u8]) -> Deserializer<'a, 'd> {
+    Deserializer {
+      names: Names::new(Some(schema)),
+      ..Deserializer::new(data)
+    }
+  }
+
+  pub fn offset(&self) -> usize {
+    self.reader.offset()
+  }
+
+  /// Checks that the whole input has been read.
+  pub fn finish(&self) -> Result<(), Error> {
+    if self.reader.remaining() > 0 {
+      return Err(
+        DecodeError::TrailingBytes {
+          offset: self.offset(),
+        }
+        .into(),
+      );
+    }
+    Ok(())
+  }
+
+  /// Checks that a value other than an `Option` or a `bool` is in the
+  /// input.
+  fn expect_value(&mut self) -> Result<(), Error> {
+    match std::mem::replace(&mut self.presence, Presence::Unknown) {
+      Presence::Unknown | Presence::Present => Ok(()),
+      Presence::Absent => Err(Error::Unsupported(
+        "an absent conditional field that is not an `Option`".into(),
+      )),
+      Presence::Flag(_) => Err(Error::Unsupported(
+        "a `flags.N?true` field that is not a `bool`".into(),
+      )),
+    }
+  }
+
+  fn read_u32(&mut self) -> Result<u32, Error> {
+    Ok(self.reader.read_u32()?)
+  }
+
+  fn read_id(&mut self, name: &'static str) -> Result<(), Error> {
+    if std::mem::replace(&mut self.bare, false) {
+      return Ok(());
+    }
+    let offset = self.offset();
+    let found = self.read_u32()?;
+    if found != self.names.id(name)? {
+      return Err(Error::WrongConstructor {
+        expected: name.to_string(),
+        found,
+        offset,
+      });
+    }
+    Ok(())
+  }
+
+  fn read_str(&mut self) -> Result<&'d str, Error> {
+    let offset = self.offset();
+    let bytes = self.reader.read_bytes()?;
+    std::str::from_utf8(bytes).map_err(|_| DecodeError::InvalidString { offset }.into())
+  }
+
+  fn fields<'s>(
+    &'s mut self,
+    combinator: Option<&'a Combinator>,
+    fields: &'static [&'static str],
+  ) -> Fields<'s, 'a, 'd> {
+    Fields {
+      de: self,
+      combinator,
+      fields,
+      index: 0,
+      flags: HashMap::new(),
+    }
+  }
+}
+
+pub fn from_bytes<'d, T: Deserialize<'d>>(data: &'d [u8]) -> Result<T, Error> {
+  let mut deserializer = Deserializer::new(data);
+  let value = T::deserialize(&mut deserializer)?;
+  deserializer.finish()?;
+  Ok(value)
+}
+
+pub fn from_bytes_with_schema<'d, T: Deserialize<'d>>(
+  schema: &Schema,
+  data: &'d [u8],
+) -> Result<T, Error> {
+  let mut deserializer = Deserializer::with_schema(schema, data);
+  let value = T::deserialize(&mut deserializer)?;
+  deserializer.finish()?;
+  Ok(value)
+}
+
+impl<'de, 's, 'a> de::Deserializer<'de> for &'s mut Deserializer<'a, 'de> {
+  type Error = Error;
+
+  fn is_human_readable(&self) -> bool {
+    false
+  }
+
+  fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+    Err(Error::Unsupported(
+      "deserializing without a type, as TL binary does not describe itself,".into(),
+    ))
+  }
+
+  fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    match std::mem::replace(&mut self.presence, Presence::Unknown) {
+      Presence::Flag(set) => return visitor.visit_bool(set),
+      Presence::Absent => return visitor.visit_bool(false),
+      Presence::Unknown | Presence::Present => {}
+    }
+    let offset = self.offset();
+    match self.read_u32()? {
+      BOOL_TRUE_ID => visitor.visit_bool(true),
+      BOOL_FALSE_ID => visitor.visit_bool(false),
+      found => Err(Error::WrongConstructor {
+        expected: "Bool".to_string(),
+        found,
+        offset,
+      }),
+    }
+  }
+
+  fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.deserialize_i32(visitor)
+  }
+
+  fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.deserialize_i32(visitor)
+  }
+
+  fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    visitor.visit_i32(i32::from_le_bytes(self.reader.read_array()?))
+  }
+
+  fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    visitor.visit_i64(i64::from_le_bytes(self.reader.read_array()?))
+  }
+
+  fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    visitor.visit_i128(i128::from_le_bytes(self.reader.read_array()?))
+  }
+
+  fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.deserialize_i32(visitor)
+  }
+
+  fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.deserialize_i32(visitor)
+  }
+
+  fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    self.nat = self.read_u32()?;
+    visitor.visit_u32(self.nat)
+  }
+
+  fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    visitor.visit_u64(u64::from_le_bytes(self.reader.read_array()?))
+  }
+
+  fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    visitor.visit_u128(u128::from_le_bytes(self.reader.read_array()?))
+  }
+
+  fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.deserialize_f64(visitor)
+  }
+
+  fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    visitor.visit_f64(f64::from_le_bytes(self.reader.read_array()?))
+  }
+
+  fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.deserialize_str(visitor)
+  }
+
+  fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    visitor.visit_borrowed_str(self.read_str()?)
+  }
+
+  fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.deserialize_str(visitor)
+  }
+
+  fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    visitor.visit_borrowed_bytes(self.reader.read_bytes()?)
+  }
+
+  fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.deserialize_bytes(visitor)
+  }
+
+  fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    match self.presence {
+      Presence::Present => {
+        self.presence = Presence::Unknown;
+        visitor.visit_some(self)
+      }
+      Presence::Flag(true) => visitor.visit_some(self),
+      Presence::Absent | Presence::Flag(false) => {
+        self.presence = Presence::Unknown;
+        visitor.visit_none()
+      }
+      Presence::Unknown => Err(Error::Unsupported(
+        "an `Option` that is not a conditional field of a schema constructor".into(),
+      )),
+    }
+  }
+
+  fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.presence = Presence::Unknown;
+    visitor.visit_unit()
+  }
+
+  fn deserialize_unit_struct<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    visitor: V,
+  ) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    self.read_id(name)?;
+    visitor.visit_unit()
+  }
+
+  fn deserialize_newtype_struct<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    visitor: V,
+  ) -> Result<V::Value, Error> {
+    self.bare_vector = name == BARE_VECTOR;
+    visitor.visit_newtype_struct(self)
+  }
+
+  fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    self.bare = false;
+    if !std::mem::replace(&mut self.bare_vector, false) {
+      let offset = self.offset();
+      let found = self.read_u32()?;
+      if found != VECTOR_ID {
+        return Err(Error::WrongConstructor {
+          expected: "Vector".to_string(),
+          found,
+          offset,
+        });
+      }
+    }
+    let length = self.read_u32()?;
+    visitor.visit_seq(Items { de: self, length })
+  }
+
+  fn deserialize_tuple<V: Visitor<'de>>(
+    self,
+    length: usize,
+    visitor: V,
+  ) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    self.bare = false;
+    visitor.visit_seq(Items {
+      de: self,
+      length: length as u32,
+    })
+  }
+
+  fn deserialize_tuple_struct<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    length: usize,
+    visitor: V,
+  ) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    self.read_id(name)?;
+    visitor.visit_seq(Items {
+      de: self,
+      length: length as u32,
+    })
+  }
+
+  fn deserialize_map<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+    Err(Error::Unsupported("a map".into()))
+  }
+
+  fn deserialize_struct<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    fields: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    self.read_id(name)?;
+    let combinator = self.names.combinator(name);
+    visitor.visit_seq(self.fields(combinator, fields))
+  }
+
+  fn deserialize_enum<V: Visitor<'de>>(
+    self,
+    name: &'static str,
+    variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Error> {
+    self.expect_value()?;
+    self.bare = false;
+    let offset = self.offset();
+    let found = self.read_u32()?;
+    for (index, variant) in variants.iter().enumerate() {
+      if self.names.id(variant)? == found {
+        return visitor.visit_enum(Variant {
+          de: self,
+          index: index as u32,
+          name: variant,
+        });
+      }
+    }
+    Err(Error::WrongConstructor {
+      expected: name.to_string(),
+      found,
+      offset,
+    })
+  }
+
+  fn deserialize_identifier<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+    Err(Error::Unsupported("an identifier".into()))
+  }
+
+  fn deserialize_ignored_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+    Err(Error::Unsupported(
+      "skipping a value, as TL binary does not describe itself,".into(),
+    ))
+  }
+}
+
+/// The items of a sequence or tuple.
+struct Items<'s, 'a, 'd> {
+  de: &'s mut Deserializer<'a, 'd>,
+  length: u32,
+}
+
+impl<'de, 's, 'a> de::SeqAccess<'de> for Items<'s, 'a, 'de> {
+  type Error = Error;
+
+  fn next_element_seed<T: DeserializeSeed<'de>>(
+    &mut self,
+    seed: T,
+  ) -> Result<Option<T::Value>, Error> {
+    if self.length == 0 {
+      return Ok(None);
+    }
+    self.length -= 1;
+    seed.deserialize(&mut *self.de).map(Some)
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.length as usize)
+  }
+}
+
+/// The fields of a struct, with the `#` fields read so far for the
+/// conditions of the fields after them.
+struct Fields<'s, 'a, 'd> {
+  de: &'s mut Deserializer<'a, 'd>,
+  combinator: Option<&'a Combinator>,
+  fields: &'static [&'static str],
+  index: usize,
+  flags: HashMap<&'static str, u32>,
+}
+
+impl<'de, 's, 'a> de::SeqAccess<'de> for Fields<'s, 'a, 'de> {
+  type Error = Error;
+
+  fn next_element_seed<T: DeserializeSeed<'de>>(
+    &mut self,
+    seed: T,
+  ) -> Result<Option<T::Value>, Error> {
+    let name = match self.fields.get(self.index) {
+      Some(name) => *name,
+      None => return Ok(None),
+    };
+    self.index += 1;
+    let field = self
+      .combinator
+      .and_then(|combinator| combinator.field(name));
+    self.de.presence = match field {
+      Some(field) => match &field.condition {
+        Some(condition) => {
+          let set = self
+            .flags
+            .get(condition.field.as_str())
+            .and_then(|flags| flags.checked_shr(condition.bit))
+            .is_some_and(|flags| flags & 1 == 1);
+          if field.is_flag() {
+            Presence::Flag(set)
+          } else if set {
+            Presence::Present
+          } else {
+            Presence::Absent
+          }
+        }
+        None => Presence::Present,
+      },
+      None => Presence::Unknown,
+    };
+    let value = seed.deserialize(&mut *self.de)?;
+    self.de.presence = Presence::Unknown;
+    if let Some(field) = field {
+      if field.ty == FieldType::Type(TLType::NatType) {
+        self.flags.insert(name, self.de.nat);
+      }
+    }
+    Ok(Some(value))
+  }
+
+  fn size_hint(&self) -> Option<usize> {
+    Some(self.fields.len() - self.index)
+  }
+}
+
+/// The variant of an enum whose ID has been read.
+struct Variant<'s, 'a, 'd> {
+  de: &'s mut Deserializer<'a, 'd>,
+  index: u32,
+  name: &'static str,
+}
+
+impl<'de, 's, 'a> de::EnumAccess<'de> for Variant<'s, 'a, 'de> {
+  type Error = Error;
+  type Variant = Self;
+
+  fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+    let index: de::value::U32Deserializer<Error> = self.index.into_deserializer();
+    Ok((seed.deserialize(index)?, self))
+  }
+}
+
+impl<'de, 's, 'a> de::VariantAccess<'de> for Variant<'s, 'a, 'de> {
+  type Error = Error;
+
+  fn unit_variant(self) -> Result<(), Error> {
+    Ok(())
+  }
+
+  fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+    self.de.bare = true;
+    let result = seed.deserialize(&mut *self.de);
+    self.de.bare = false;
+    result
+  }
+
+  fn tuple_variant<V: Visitor<'de>>(self, length: usize, visitor: V) -> Result<V::Value, Error> {
+    visitor.visit_seq(Items {
+      de: self.de,
+      length: length as u32,
+    })
+  }
+
+  fn struct_variant<V: Visitor<'de>>(
+    self,
+    fields: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, Error> {
+    let combinator = self.de.names.combinator(self.name);
+    visitor.visit_seq(self.de.fields(combinator, fields))
+  }
+}
@@ -0,0 +1,619 @@
+use super::codec::Codecs;
+use super::de::{deserialize, deserialize_as};
+use super::ser::{serialize, serialize_as};
+use super::value::field_key;
+use super::*;
+use crate::checks::BlockKind;
+use crate::schema::{Combinator, Count, Field, FieldType, Schema};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenerateError {
+  UnknownType(String),
+  /// A type none of whose constructors has a finite value, such as
+  /// `tree left:Tree right:Tree = Tree` alone.
+  NoFiniteValue(String),
+  /// The count of a `[ ... ]` block does not evaluate to a number.
+  UnknownCount {
+    constructor: String,
+    field: String,
+  },
+  Unsupported(String),
+}
+
+impl fmt::Display for GenerateError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      GenerateError::UnknownType(name) => write!(f, "unknown type `{}`", name),
+      GenerateError::NoFiniteValue(name) => write!(f, "`{}` has no finite values", name),
+      GenerateError::UnknownCount { constructor, field } => write!(
+        f,
+        "the number of records in `{}` field `{}` is unknown",
+        constructor, field
+      ),
+      GenerateError::Unsupported(what) => write!(f, "generating {} is not supported", what),
+    }
+  }
+}
+
+impl std::error::Error for GenerateError {}
+
+/// How large generated values get.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Budget {
+  /// Objects, vectors and records nested in each other. Past it vectors
+  /// are empty, conditional fields absent and constructors the ones
+  /// needing the least nesting, so values still end.
+  pub max_depth: usize,
+  /// Items of a vector, records of a `[ ... ]` block and values of other
+  /// `#` fields.
+  pub max_items: usize,
+  /// Bytes of a string or bytes value.
+  pub max_length: usize,
+}
+
+impl Default for Budget {
+  fn default() -> Budget {
+    Budget {
+      max_depth: 6,
+      max_items: 4,
+      max_length: 32,
+    }
+  }
+}
+
+/// Seedable pseudo-random numbers (xorshift64*). Kept here rather than
+/// taken from a crate so that the values a seed gives never change.
+#[derive(Debug, Clone)]
+pub struct Rng {
+  state: u64,
+}
+
+impl Rng {
+  pub fn new(seed: u64) -> Rng {
+    // splitmix64 spreads nearby seeds apart and never gives a zero state
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    Rng {
+      state: (z ^ (z >> 31)) | 1,
+    }
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    self.state ^= self.state >> 12;
+    self.state ^= self.state << 25;
+    self.state ^= self.state >> 27;
+    self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+  }
+
+  /// A number in `0..bound`, `0` if `bound` is.
+  pub fn below(&mut self, bound: usize) -> usize {
+    match bound {
+      0 => 0,
+      bound => (self.next_u64() % bound as u64) as usize,
+    }
+  }
+
+  pub fn coin(&mut self) -> bool {
+    self.next_u64() & 1 == 1
+  }
+}
+
+/// Generates random values that the schema accepts: constructors picked
+/// among the alternatives of a type, every field filled, and flags set to
+/// match the conditional fields present.
+pub struct Generator<'a> {
+  schema: &'a Schema,
+  codecs: Cow<'a, Codecs>,
+  rng: Rng,
+  budget: Budget,
+  /// Least nesting a value of each constructor needs, by name, and of each
+  /// function, by name after `!`. Missing for those with no finite value.
+  heights: HashMap<String, usize>,
+  depth: usize,
+}
+
+impl<'a> Generator<'a> {
+  pub fn new(schema: &'a Schema, seed: u64) -> Generator<'a> {
+    Generator::with_cow_codecs(schema, Cow::Owned(Codecs::default()), seed)
+  }
+
+  /// Generator making builtin values with `codecs`.
+  pub fn with_codecs(schema: &'a Schema, codecs: &'a Codecs, seed: u64) -> Generator<'a> {
+    Generator::with_cow_codecs(schema, Cow::Borrowed(codecs), seed)
+  }
+
+  fn with_cow_codecs(schema: &'a Schema, codecs: Cow<'a, Codecs>, seed: u64) -> Generator<'a> {
+    Generator {
+      schema,
+      codecs,
+      rng: Rng::new(seed),
+      budget: Budget::default(),
+      heights: heights(schema),
+      depth: 0,
+    }
+  }
+
+  pub fn set_budget(&mut self, budget: Budget) {
+    self.budget = budget;
+  }
+
+  /// A random value of `ty`, e.g. `Vector User` or `%User`.
+  pub fn generate(&mut self, ty: &TLType) -> Result<Value<'static>, GenerateError> {
+    match shape(self.schema, &self.codecs, ty) {
+      Shape::Nat => Ok(Value::Nat(self.count() as u32)),
+      Shape::Builtin(name) => self.builtin(name),
+      Shape::Vector(params) => self.vector(params),
+      Shape::Object(expected, params) => self.object(expected, params),
+      Shape::UnknownType(name) => Err(GenerateError::UnknownType(name.to_string())),
+      Shape::NoValues(ty) => Err(GenerateError::Unsupported(format!(
+        "values of type `{}`",
+        ty
+      ))),
+    }
+  }
+
+  /// A random object built by `combinator`, a constructor or a function
+  /// call, as `Serializer::write_object` takes it.
+  pub fn generate_object(
+    &mut self,
+    combinator: &Combinator,
+  ) -> Result<Value<'static>, GenerateError> {
+    self.fields_of(combinator, &[])
+  }
+
+  /// Whether values of `ty` are vectors rather than objects.
+  fn is_vector(&self, ty: &TLType) -> bool {
+    matches!(shape(self.schema, &self.codecs, ty), Shape::Vector(_))
+  }
+
+  /// A random object built by one of the `expected` constructors.
+  fn object(
+    &mut self,
+    expected: Expected,
+    params: &[TLType],
+  ) -> Result<Value<'static>, GenerateError> {
+    let combinator = match expected {
+      Expected::Any | Expected::Function => self.pick(self.functions(), "!")?,
+      Expected::Type(name) => self.pick(self.schema.constructors_of(name), name)?,
+      Expected::Bare(name) => match self.schema.constructors_of(name)[..] {
+        [constructor] => constructor,
+        _ => {
+          return Err(GenerateError::Unsupported(format!(
+            "bare `%{}` with several constructors",
+            name
+          )))
+        }
+      },
+      Expected::Constructor(name) => match self.schema.constructor(name) {
+        Some(constructor) => constructor,
+        None => return Err(GenerateError::UnknownType(name.to_string())),
+      },
+    };
+    self.fields_of(combinator, params)
+  }
+
+  fn vector(&mut self, params: &[TLType]) -> Result<Value<'static>, GenerateError> {
+    let item_type = match params {
+      [item_type] => item_type,
+      _ => return Err(GenerateError::UnknownType("Vector".to_string())),
+    };
+    let count = self.count();
+    self.nested(|generator| {
+      let items = (0..count)
+        .map(|_| generator.generate(item_type))
+        .collect::<Result<_, _>>()?;
+      Ok(Value::Vector(items))
+    })
+  }
+
+  /// Picks one of `combinators`, among those needing the least nesting
+  /// once past the depth budget.
+  fn pick<'c>(
+    &mut self,
+    combinators: Vec<&'c Combinator>,
+    name: &str,
+  ) -> Result<&'c Combinator, GenerateError> {
+    let heights = combinators
+      .iter()
+      .map(|combinator| self.height(combinator))
+      .collect::<Vec<_>>();
+    let least = heights.iter().flatten().min().copied();
+    let candidates = combinators
+      .into_iter()
+      .zip(heights)
+      .filter(|(_, height)| match (height, least) {
+        (Some(height), Some(least)) => self.depth < self.budget.max_depth || *height == least,
+        _ => false,
+      })
+      .map(|(combinator, _)| combinator)
+      .collect::<Vec<_>>();
+    match candidates.len() {
+      0 => Err(GenerateError::NoFiniteValue(name.to_string())),
+      count => Ok(candidates[self.rng.below(count)]),
+    }
+  }
+
+  fn height(&self, combinator: &Combinator) -> Option<usize> {
+    self.heights.get(&height_key(combinator)).copied()
+  }
+
+  fn functions(&self) -> Vec<&'a Combinator> {
+    self
+      .schema
+      .combinators()
+      .iter()
+      .filter(|combinator| combinator.kind == BlockKind::Functions)
+      .collect()
+  }
+
+  fn fields_of(
+    &mut self,
+    combinator: &Combinator,
+    params: &[TLType],
+  ) -> Result<Value<'static>, GenerateError> {
+    let scope = Scope::for_combinator(combinator, params);
+    let fields =
+      self.nested(|generator| generator.block(&combinator.name, &combinator.fields, scope))?;
+    Ok(Value::Object(Object {
+      constructor: combinator.name.clone(),
+      fields,
+    }))
+  }
+
+  /// Fills the fields of the combinator `name` or of one record of one of
+  /// its `[ ... ]` blocks. Which conditional fields are present is decided
+  /// first, and the `#` fields they test are set to match.
+  fn block(
+    &mut self,
+    name: &str,
+    fields: &[Field],
+    mut scope: Scope,
+  ) -> Result<Vec<(String, Value<'static>)>, GenerateError> {
+    let past_budget = self.depth >= self.budget.max_depth;
+    let mut flags: HashMap<&str, u32> = HashMap::new();
+    let mut present = vec![];
+    for field in fields {
+      let condition = match &field.condition {
+        Some(condition) => condition,
+        None => {
+          present.push(true);
+          continue;
+        }
+      };
+      // flags of an enclosing combinator are out of reach from a record
+      let known = fields
+        .iter()
+        .any(|flags| field_key(flags) == condition.field);
+      let set = known && condition.bit < 32 && !past_budget && self.rng.coin();
+      let bits = flags.entry(&condition.field).or_default();
+      if set {
+        *bits |= 1 << condition.bit;
+      }
+      present.push(set);
+    }
+
+    let mut values = vec![];
+    for (field, present) in fields.iter().zip(present) {
+      let key = field_key(field);
+      if !present {
+        if field.is_flag() {
+          values.push((key, Value::Bool(false)));
+        }
+        continue;
+      }
+      let value = match &field.ty {
+        FieldType::Type(TLType::NatType) if flags.contains_key(key.as_str()) => {
+          Value::Nat(flags[key.as_str()])
+        }
+        FieldType::Type(ty) => self.generate(&scope.substitute(ty))?,
+        FieldType::Repeat(count, block) => {
+          let count = scope
+            .count(count)
+            .ok_or_else(|| GenerateError::UnknownCount {
+              constructor: name.to_string(),
+              field: key.clone(),
+            })?;
+          self.nested(|generator| {
+            let mut records = vec![];
+            for _ in 0..count {
+              records.push(match plain_item(block) {
+                Some(ty) => generator.generate(&scope.substitute(ty))?,
+                None => Value::Object(Object {
+                  constructor: String::new(),
+                  fields: generator
+                    .nested(|generator| generator.block(name, block, scope.clone()))?,
+                }),
+              });
+            }
+            Ok(Value::Vector(records))
+          })?
+        }
+      };
+      if let Value::Nat(nat) = value {
+        scope.bind(&key, TLType::Nat(nat));
+      }
+      values.push((key, value));
+    }
+    Ok(values)
+  }
+
+  /// A random value of the builtin type `name`.
+  fn builtin(&mut self, name: &str) -> Result<Value<'static>, GenerateError> {
+    let generated = match self.codecs.get(name) {
+      Some(codec) => codec.generate(&mut self.rng, &self.budget),
+      None => None,
+    };
+    generated
+      .ok_or_else(|| GenerateError::Unsupported(format!("values of the builtin type `{}`", name)))
+  }
+
+  /// A number of items, none once past the depth budget.
+  fn count(&mut self) -> usize {
+    match self.depth >= self.budget.max_depth {
+      true => 0,
+      false => self.rng.below(self.budget.max_items + 1),
+    }
+  }
+
+  /// Runs `generate` one level deeper.
+  fn nested<T>(
+    &mut self,
+    generate: impl FnOnce(&mut Self) -> Result<T, GenerateError>,
+  ) -> Result<T, GenerateError> {
+    self.depth += 1;
+    let result = generate(self);
+    self.depth -= 1;
+    result
+  }
+}
+
+fn height_key(combinator: &Combinator) -> String {
+  match combinator.kind {
+    BlockKind::Types => combinator.name.clone(),
+    BlockKind::Functions => format!("!{}", combinator.name),
+  }
+}
+
+/// Least nesting a value of each combinator needs, refined until none
+/// changes as a type can end in one constructor even if its others refer
+/// back to it. Vectors and blocks of a free count can be empty and
+/// conditional fields absent, so they need none.
+fn heights(schema: &Schema) -> HashMap<String, usize> {
+  let mut heights = HashMap::new();
+  loop {
+    let mut changed = false;
+    for combinator in schema.combinators() {
+      let height = match combinator.builtin {
+        true => Some(0),
+        false => block_height(schema, &heights, &combinator.fields).map(|height| height + 1),
+      };
+      let key = height_key(combinator);
+      if let Some(height) = height {
+        if heights.get(&key).is_none_or(|&known| height < known) {
+          heights.insert(key, height);
+          changed = true;
+        }
+      }
+    }
+    if !changed {
+      return heights;
+    }
+  }
+}
+
+fn block_height(
+  schema: &Schema,
+  heights: &HashMap<String, usize>,
+  fields: &[Field],
+) -> Option<usize> {
+  let mut height = 0;
+  for field in fields {
+    if field.condition.is_some() {
+      continue;
+    }
+    let field_height = match &field.ty {
+      FieldType::Type(ty) => type_height(schema, heights, ty)?,
+      FieldType::Repeat(Count::Expr(TLType::Plus(_, _)), block)
+      | FieldType::Repeat(Count::Expr(TLType::Nat(_)), block) => {
+        block_height(schema, heights, block)? + 1
+      }
+      FieldType::Repeat(_, _) => 0,
+    };
+    height = height.max(field_height);
+  }
+  Some(height)
+}
+
+fn type_height(schema: &Schema, heights: &HashMap<String, usize>, ty: &TLType) -> Option<usize> {
+  let least = |combinators: Vec<&Combinator>| {
+    combinators
+      .iter()
+      .filter_map(|combinator| heights.get(&height_key(combinator)).copied())
+      .min()
+  };
+  match ty {
+    TLType::Bang(_) => least(
+      schema
+        .combinators()
+        .iter()
+        .filter(|combinator| combinator.kind == BlockKind::Functions)
+        .collect(),
+    ),
+    TLType::Bare(inner) => type_height(schema, heights, inner),
+    TLType::Named(name, _) if name == "Vector" || name == "vector" => Some(0),
+    TLType::Named(name, _) if is_boxed(name) && schema.has_type(name) => {
+      least(schema.constructors_of(name))
+    }
+    TLType::Named(name, _) => match schema.constructor(name) {
+      Some(constructor) => heights.get(&height_key(constructor)).copied(),
+      // builtins and type variables
+      None => Some(0),
+    },
+    TLType::NatType | TLType::Nat(_) | TLType::Plus(_, _) => Some(0),
+  }
+}
+
+/// A value that did not come back the same from
+/// `generate -> encode -> decode -> compare`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+  pub combinator: String,
+  /// The type a polymorphic combinator was instantiated at, such as
+  /// `Vector int` for `vector`.
+  pub instance: Option<TLType>,
+  /// Seed of a `Generator` whose `generate_object` gives the value.
+  pub seed: u64,
+  pub problem: String,
+}
+
+impl fmt::Display for Failure {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match &self.instance {
+      Some(ty) => write!(
+        f,
+        "{} as `{}` (seed {}): {}",
+        self.combinator, ty, self.seed, self.problem
+      ),
+      None => write!(
+        f,
+        "{} (seed {}): {}",
+        self.combinator, self.seed, self.problem
+      ),
+    }
+  }
+}
+
+/// Outcome of [`self_test`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SelfTest {
+  /// Values that came back the same.
+  pub passed: usize,
+  /// Builtins, polymorphic functions and combinators that cannot be
+  /// instantiated, and those with values of a type the generator has no
+  /// values for.
+  pub skipped: Vec<String>,
+  pub failures: Vec<Failure>,
+}
+
+/// Generates `rounds` values of each constructor and function of
+/// `schema`, encodes them, decodes them back and compares. Constructors
+/// whose fields depend on their parameters, such as `vector`, are tested
+/// as an instance of their type, e.g. `Vector int`.
+pub fn self_test(schema: &Schema, seed: u64, rounds: usize, budget: Budget) -> SelfTest {
+  let mut report = SelfTest::default();
+  let mut seeds = Rng::new(seed);
+  for combinator in schema.combinators() {
+    if combinator.builtin {
+      report.skipped.push(combinator.name.clone());
+      continue;
+    }
+    let instance = match polymorphic(combinator) {
+      false => None,
+      true => match instance(schema, combinator) {
+        Some(instance) => Some(instance),
+        None => {
+          report.skipped.push(combinator.name.clone());
+          continue;
+        }
+      },
+    };
+    for _ in 0..rounds {
+      let seed = seeds.next_u64();
+      let mut generator = Generator::new(schema, seed);
+      generator.set_budget(budget);
+      let generated = match &instance {
+        Some((ty, _)) if generator.is_vector(ty) => generator.generate(ty),
+        Some((_, instance)) => generator.generate_object(instance),
+        None => generator.generate_object(combinator),
+      };
+      let ty = instance.as_ref().map(|(ty, _)| ty);
+      let problem = match generated {
+        Ok(value) => match round_trip(schema, ty, &value) {
+          Ok(()) => {
+            report.passed += 1;
+            continue;
+          }
+          Err(problem) => problem,
+        },
+        Err(GenerateError::Unsupported(_)) => {
+          report.skipped.push(combinator.name.clone());
+          break;
+        }
+        Err(err) => format!("generating: {}", err),
+      };
+      report.failures.push(Failure {
+        combinator: combinator.name.clone(),
+        instance: ty.cloned(),
+        seed,
+        problem,
+      });
+    }
+  }
+  report
+}
+
+/// The type of a polymorphic constructor with `int` for its type
+/// parameters and 2 for its `#` ones, and the constructor instantiated
+/// at it.
+fn instance(schema: &Schema, combinator: &Combinator) -> Option<(TLType, Combinator)> {
+  if combinator.kind != BlockKind::Types {
+    return None;
+  }
+  let bindings = combinator
+    .params
+    .iter()
+    .map(|param| {
+      let value = match param.ty {
+        TLType::NatType => TLType::Nat(2),
+        _ => TLType::Named("int".to_string(), vec![]),
+      };
+      (param.name.clone(), value)
+    })
+    .collect();
+  let ty = combinator.result_type.substitute(&bindings);
+  let instance = schema
+    .instantiate(&ty)
+    .ok()?
+    .into_iter()
+    .find(|instance| instance.name == combinator.name)?;
+  Some((ty, instance))
+}
+
+fn round_trip(schema: &Schema, ty: Option<&TLType>, value: &Value) -> Result<(), String> {
+  let data = match ty {
+    Some(ty) => serialize_as(schema, ty, value),
+    None => serialize(schema, value),
+  }
+  .map_err(|err| format!("encoding: {}", err))?;
+  let decoded = match ty {
+    Some(ty) => deserialize_as(schema, ty, &data),
+    None => deserialize(schema, &data),
+  }
+  .map_err(|err| format!("decoding: {}", err))?;
+  if &decoded != value {
+    return Err(format!("decoded {:?}, generated {:?}", decoded, value));
+  }
+  Ok(())
+}
+
+/// Whether the fields of `combinator` depend on its implicit parameters,
+/// other than through `!X`.
+fn polymorphic(combinator: &Combinator) -> bool {
+  fn mentions(fields: &[Field], name: &str) -> bool {
+    fields.iter().any(|field| match &field.ty {
+      FieldType::Type(TLType::Bang(_)) => false,
+      FieldType::Type(ty) => ty.mentions(name),
+      FieldType::Repeat(count, block) => {
+        matches!(count, Count::Expr(expr) if expr.mentions(name)) || mentions(block, name)
+      }
+    })
+  }
+  combinator
+    .params
+    .iter()
+    .any(|param| mentions(&combinator.fields, &param.name))
+}
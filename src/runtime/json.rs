@@ -0,0 +1,496 @@
+//! Transcoding between TL binary and the JSON of the tdlib interface, in
+//! which an object is `{"@type": "constructorName", ...fields}`.
+//!
+//! Values map to JSON as follows: `int`, `#` and `double` are numbers,
+//! `long` is a string of digits, as JSON numbers lose precision past 53
+//! bits, and `Bool` and `true` are booleans. `string` is a string; `bytes`,
+//! `int128` and `int256` are base64 strings. Vectors and the records of
+//! `[ ... ]` blocks are arrays, records being objects without `@type`.
+//!
+//! The `#` fields holding flags and record counts are computed when
+//! serializing, so they are left out of the JSON written and may be left
+//! out of the JSON read. Conditional fields may be left out or `null`.
+//! Keys starting with `@` other than `@type`, such as tdlib's `@extra`, are
+//! ignored.
+//!
+//! An object of an unknown constructor is
+//! `{"@type": "#1cb5c415", "@raw": "..."}`, its ID and its bytes.
+
+use super::codec::Codecs;
+use super::de::{deserialize, deserialize_as, DecodeError};
+use super::ser::{serialize, serialize_as, EncodeError};
+use super::value::field_key;
+use super::*;
+use crate::schema::{Combinator, Field, FieldType, Schema};
+use serde_json::{Map, Number, Value as Json};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonError {
+  /// Text that is not JSON.
+  Syntax(String),
+  /// JSON that does not describe a value of the expected type, at the
+  /// path of the offending part, such as `$.chats[3].photo`.
+  Invalid {
+    path: String,
+    message: String,
+  },
+  Encode(EncodeError),
+  Decode(DecodeError),
+}
+
+impl fmt::Display for JsonError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      JsonError::Syntax(message) => write!(f, "invalid JSON: {}", message),
+      JsonError::Invalid { path, message } => write!(f, "{}: {}", path, message),
+      JsonError::Encode(err) => err.fmt(f),
+      JsonError::Decode(err) => err.fmt(f),
+    }
+  }
+}
+
+impl std::error::Error for JsonError {}
+
+impl From<EncodeError> for JsonError {
+  fn from(err: EncodeError) -> JsonError {
+    JsonError::Encode(err)
+  }
+}
+
+impl From<DecodeError> for JsonError {
+  fn from(err: DecodeError) -> JsonError {
+    JsonError::Decode(err)
+  }
+}
+
+/// JSON of `value`.
+pub fn to_json(schema: &Schema, value: &Value) -> Result<Json, JsonError> {
+  Writer { schema }.value(value, "$")
+}
+
+/// Value of the boxed object or function call of any type that `json`
+/// describes.
+pub fn from_json(schema: &Schema, json: &Json) -> Result<Value<'static>, JsonError> {
+  Reader::new(schema).object(Expected::Any, &[], json, "$")
+}
+
+/// Value of type `ty` that `json` describes, checked against the schema.
+pub fn from_json_as(
+  schema: &Schema,
+  ty: &TLType,
+  json: &Json,
+) -> Result<Value<'static>, JsonError> {
+  Reader::new(schema).value(ty, json, "$")
+}
+
+/// Serializes the boxed object or function call the JSON text describes.
+pub fn json_to_binary(schema: &Schema, text: &str) -> Result<Vec<u8>, JsonError> {
+  let value = from_json(schema, &parse(text)?)?;
+  Ok(serialize(schema, &value)?)
+}
+
+/// Serializes the instance of `ty` the JSON text describes.
+pub fn json_to_binary_as(schema: &Schema, ty: &TLType, text: &str) -> Result<Vec<u8>, JsonError> {
+  let value = from_json_as(schema, ty, &parse(text)?)?;
+  Ok(serialize_as(schema, ty, &value)?)
+}
+
+/// JSON text of the boxed object or function call taking up all of
+/// `data`.
+pub fn binary_to_json(schema: &Schema, data: &[u8]) -> Result<String, JsonError> {
+  let value = deserialize(schema, data)?;
+  Ok(to_json(schema, &value)?.to_string())
+}
+
+/// JSON text of the instance of `ty` taking up all of `data`.
+pub fn binary_to_json_as(schema: &Schema, ty: &TLType, data: &[u8]) -> Result<String, JsonError> {
+  let value = deserialize_as(schema, ty, data)?;
+  Ok(to_json(schema, &value)?.to_string())
+}
+
+fn parse(text: &str) -> Result<Json, JsonError> {
+  serde_json::from_str(text).map_err(|err| JsonError::Syntax(err.to_string()))
+}
+
+fn invalid(path: &str, message: String) -> JsonError {
+  JsonError::Invalid {
+    path: path.to_string(),
+    message,
+  }
+}
+
+/// What sort of JSON value `json` is, for error messages.
+fn kind(json: &Json) -> &'static str {
+  match json {
+    Json::Null => "null",
+    Json::Bool(_) => "a boolean",
+    Json::Number(_) => "a number",
+    Json::String(_) => "a string",
+    Json::Array(_) => "an array",
+    Json::Object(_) => "an object",
+  }
+}
+
+struct Writer<'a> {
+  schema: &'a Schema,
+}
+
+impl<'a> Writer<'a> {
+  fn value(&self, value: &Value, path: &str) -> Result<Json, JsonError> {
+    Ok(match value {
+      Value::Nat(nat) => Json::from(*nat),
+      Value::Bool(value) => Json::Bool(*value),
+      Value::Int(int) => Json::from(*int),
+      Value::Long(long) => Json::String(long.to_string()),
+      Value::Double(double) => match Number::from_f64(*double) {
+        Some(number) => Json::Number(number),
+        None => return Err(invalid(path, format!("{} has no JSON form", double))),
+      },
+      Value::String(string) => Json::String(string.to_string()),
+      Value::Bytes(bytes) => Json::String(base64::encode(bytes)),
+      Value::Int128(int) => Json::String(base64::encode(int)),
+      Value::Int256(int) => Json::String(base64::encode(int)),
+      Value::Vector(items) => Json::Array(
+        items
+          .iter()
+          .enumerate()
+          .map(|(index, item)| self.value(item, &format!("{}[{}]", path, index)))
+          .collect::<Result<_, _>>()?,
+      ),
+      Value::Object(object) => self.object(object, path)?,
+      Value::Unknown { id, raw } => {
+        let mut map = Map::new();
+        map.insert("@type".to_string(), Json::String(format!("#{:08x}", id)));
+        map.insert("@raw".to_string(), Json::String(base64::encode(raw)));
+        Json::Object(map)
+      }
+    })
+  }
+
+  fn object(&self, object: &Object, path: &str) -> Result<Json, JsonError> {
+    let combinator = self
+      .schema
+      .constructor(&object.constructor)
+      .or_else(|| self.schema.function(&object.constructor));
+    let computed = combinator
+      .map(|combinator| computed_fields(&combinator.fields))
+      .unwrap_or_default();
+    let mut map = Map::new();
+    if !object.constructor.is_empty() {
+      map.insert(
+        "@type".to_string(),
+        Json::String(object.constructor.clone()),
+      );
+    }
+    for (key, value) in &object.fields {
+      if let Value::Nat(_) = value {
+        if computed.contains(key.as_str()) {
+          continue;
+        }
+      }
+      let json = self.value(value, &format!("{}.{}", path, key))?;
+      map.insert(key.clone(), json);
+    }
+    Ok(Json::Object(map))
+  }
+}
+
+struct Reader<'a> {
+  schema: &'a Schema,
+  codecs: Codecs,
+}
+
+impl<'a> Reader<'a> {
+  fn new(schema: &'a Schema) -> Reader<'a> {
+    Reader {
+      schema,
+      codecs: Codecs::default(),
+    }
+  }
+
+  fn value(&self, ty: &TLType, json: &Json, path: &str) -> Result<Value<'static>, JsonError> {
+    match shape(self.schema, &self.codecs, ty) {
+      Shape::Nat => match json.as_u64().filter(|&nat| nat <= u64::from(u32::MAX)) {
+        Some(nat) => Ok(Value::Nat(nat as u32)),
+        None => Err(invalid(path, format!("expected a #, found {}", json))),
+      },
+      Shape::Builtin(name) => self.builtin(name, json, path),
+      Shape::Vector(params) => self.vector(params, json, path),
+      Shape::Object(expected, params) => self.object(expected, params, json, path),
+      Shape::UnknownType(name) => Err(invalid(path, format!("unknown type `{}`", name))),
+      Shape::NoValues(ty) => Err(invalid(
+        path,
+        format!("values of type `{}` have no JSON form", ty),
+      )),
+    }
+  }
+
+  fn vector(
+    &self,
+    params: &[TLType],
+    json: &Json,
+    path: &str,
+  ) -> Result<Value<'static>, JsonError> {
+    let item_type = match params {
+      [item_type] => item_type,
+      _ => return Err(invalid(path, "`Vector` needs an item type".to_string())),
+    };
+    let items = match json {
+      Json::Array(items) => items,
+      json => {
+        return Err(invalid(
+          path,
+          format!("expected an array, found {}", kind(json)),
+        ))
+      }
+    };
+    let items = items
+      .iter()
+      .enumerate()
+      .map(|(index, item)| self.value(item_type, item, &format!("{}[{}]", path, index)))
+      .collect::<Result<_, _>>()?;
+    Ok(Value::Vector(items))
+  }
+
+  fn object(
+    &self,
+    expected: Expected,
+    params: &[TLType],
+    json: &Json,
+    path: &str,
+  ) -> Result<Value<'static>, JsonError> {
+    let map = match json {
+      Json::Object(map) => map,
+      json => {
+        return Err(invalid(
+          path,
+          format!("expected an object, found {}", kind(json)),
+        ))
+      }
+    };
+    let name = match (map.get("@type"), expected) {
+      (Some(Json::String(name)), _) => name.as_str(),
+      // a single bare constructor may leave `@type` out
+      (None, Expected::Constructor(name)) => name,
+      (None, _) => return Err(invalid(path, "missing @type".to_string())),
+      (Some(json), _) => {
+        return Err(invalid(
+          path,
+          format!("@type must be a string, not {}", kind(json)),
+        ))
+      }
+    };
+    if name.starts_with('#') {
+      return self.unknown(name, map, path);
+    }
+
+    let combinator = self.combinator(name, expected, path)?;
+    let scope = Scope::for_combinator(combinator, params);
+    Ok(Value::Object(Object {
+      constructor: combinator.name.clone(),
+      fields: self.fields(&combinator.name, &combinator.fields, map, scope, path)?,
+    }))
+  }
+
+  /// An object of the unknown constructor `#id`, with its bytes in
+  /// `@raw`.
+  fn unknown(
+    &self,
+    id: &str,
+    map: &Map<String, Json>,
+    path: &str,
+  ) -> Result<Value<'static>, JsonError> {
+    let id = u32::from_str_radix(&id[1..], 16)
+      .map_err(|_| invalid(path, format!("unknown @type `{}`", id)))?;
+    let raw = match map.get("@raw") {
+      Some(Json::String(raw)) => base64::decode(raw).map_err(|err| {
+        invalid(
+          &format!("{}.@raw", path),
+          format!("invalid base64: {}", err),
+        )
+      })?,
+      _ => {
+        return Err(invalid(
+          path,
+          "an unknown constructor needs its bytes in @raw".to_string(),
+        ))
+      }
+    };
+    Ok(Value::Unknown {
+      id,
+      raw: Cow::Owned(raw),
+    })
+  }
+
+  fn combinator(
+    &self,
+    name: &str,
+    expected: Expected,
+    path: &str,
+  ) -> Result<&'a Combinator, JsonError> {
+    expected
+      .combinator(self.schema, name)
+      .map_err(|mismatch| match mismatch {
+        Mismatch::Unknown => invalid(path, format!("unknown @type `{}`", name)),
+        Mismatch::Unexpected(message) => invalid(path, message),
+      })
+  }
+
+  /// Reads `fields` from `map` in declaration order, binding `#` fields
+  /// in `scope` as they are read.
+  fn fields(
+    &self,
+    name: &str,
+    fields: &[Field],
+    map: &Map<String, Json>,
+    mut scope: Scope,
+    path: &str,
+  ) -> Result<Vec<(String, Value<'static>)>, JsonError> {
+    if let Some(key) = map
+      .keys()
+      .find(|key| !key.starts_with('@') && !fields.iter().any(|field| field_key(field) == **key))
+    {
+      return Err(invalid(
+        &format!("{}.{}", path, key),
+        format!("`{}` has no such field", name),
+      ));
+    }
+    let computed = computed_fields(fields);
+    let mut values = vec![];
+    for field in fields {
+      let key = field_key(field);
+      let json = match map.get(&key) {
+        Some(Json::Null) | None => {
+          if field.condition.is_some() || computed.contains(key.as_str()) {
+            continue;
+          }
+          return Err(invalid(path, format!("missing field `{}`", key)));
+        }
+        Some(json) => json,
+      };
+      let path = format!("{}.{}", path, key);
+      let value = match &field.ty {
+        FieldType::Type(ty) => self.value(&scope.substitute(ty), json, &path)?,
+        FieldType::Repeat(_, block) => self.records(name, block, json, &scope, &path)?,
+      };
+      if let Value::Nat(nat) = value {
+        scope.bind(&key, TLType::Nat(nat));
+      }
+      values.push((key, value));
+    }
+    Ok(values)
+  }
+
+  /// Reads the records of a `[ ... ]` block: values for a block of a
+  /// single unnamed field, objects without `@type` otherwise.
+  fn records(
+    &self,
+    name: &str,
+    block: &[Field],
+    json: &Json,
+    scope: &Scope,
+    path: &str,
+  ) -> Result<Value<'static>, JsonError> {
+    let records = match json {
+      Json::Array(records) => records,
+      json => {
+        return Err(invalid(
+          path,
+          format!("expected an array, found {}", kind(json)),
+        ))
+      }
+    };
+    let mut values = vec![];
+    for (index, record) in records.iter().enumerate() {
+      let path = format!("{}[{}]", path, index);
+      if let Some(ty) = plain_item(block) {
+        values.push(self.value(&scope.substitute(ty), record, &path)?);
+        continue;
+      }
+      let map = match record {
+        Json::Object(map) => map,
+        json => {
+          return Err(invalid(
+            &path,
+            format!("expected an object, found {}", kind(json)),
+          ))
+        }
+      };
+      values.push(Value::Object(Object {
+        constructor: String::new(),
+        fields: self.fields(name, block, map, scope.clone(), &path)?,
+      }));
+    }
+    Ok(Value::Vector(values))
+  }
+
+  /// Reads a value of the builtin type `name`.
+  fn builtin(&self, name: &str, json: &Json, path: &str) -> Result<Value<'static>, JsonError> {
+    let expected = |what: &str| invalid(path, format!("expected {}, found {}", what, json));
+    Ok(match name {
+      "int" => Value::Int(
+        json
+          .as_i64()
+          .and_then(|int| i32::try_from(int).ok())
+          .ok_or_else(|| expected("an int"))?,
+      ),
+      "long" => Value::Long(
+        match json {
+          Json::String(long) => long.parse().ok(),
+          json => json.as_i64(),
+        }
+        .ok_or_else(|| expected("a long"))?,
+      ),
+      "double" => Value::Double(json.as_f64().ok_or_else(|| expected("a double"))?),
+      "string" => Value::String(Cow::Owned(
+        json
+          .as_str()
+          .ok_or_else(|| expected("a string"))?
+          .to_string(),
+      )),
+      "bytes" => Value::Bytes(Cow::Owned(self.base64(json, path)?)),
+      "int128" => Value::Int128(self.fixed(json, path)?),
+      "int256" => Value::Int256(self.fixed(json, path)?),
+      "Bool" | "true" => Value::Bool(json.as_bool().ok_or_else(|| expected("a boolean"))?),
+      name => {
+        return Err(invalid(
+          path,
+          format!("values of the builtin type `{}` have no JSON form", name),
+        ))
+      }
+    })
+  }
+
+  fn base64(&self, json: &Json, path: &str) -> Result<Vec<u8>, JsonError> {
+    match json {
+      Json::String(text) => {
+        base64::decode(text).map_err(|err| invalid(path, format!("invalid base64: {}", err)))
+      }
+      json => Err(invalid(
+        path,
+        format!("expected a base64 string, found {}", kind(json)),
+      )),
+    }
+  }
+
+  /// Reads the base64 bytes of an `int128` or `int256`.
+  fn fixed<A: Default + AsMut<[u8]>>(&self, json: &Json, path: &str) -> Result<A, JsonError> {
+    let bytes = self.base64(json, path)?;
+    let mut array = A::default();
+    if bytes.len() != array.as_mut().len() {
+      return Err(invalid(
+        path,
+        format!(
+          "expected {} bytes, found {}",
+          array.as_mut().len(),
+          bytes.len()
+        ),
+      ));
+    }
+    array.as_mut().copy_from_slice(&bytes);
+    Ok(array)
+  }
+}
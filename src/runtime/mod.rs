@@ -0,0 +1,348 @@
+use self::codec::Codecs;
+use self::value::field_key;
+use super::checks::Recovery;
+use super::schema::{Combinator, Count, Field, FieldType, Schema};
+use super::types::{Bindings, TLType};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+pub mod codec;
+pub mod de;
+pub mod explain;
+pub mod format;
+pub mod generate;
+pub mod json;
+pub mod ser;
+pub mod size;
+pub mod stream;
+pub mod text;
+pub mod validate;
+pub mod value;
+
+pub use self::value::{Object, Value};
+
+/// ID of `vector {t:Type} # [ t ] = Vector t`, used when the schema does
+/// not declare `vector` itself.
+pub const VECTOR_ID: u32 = 0x1cb5_c415;
+
+/// Whether `name` is a boxed type rather than a bare constructor or
+/// builtin, i.e. whether it starts with an uppercase letter once the
+/// namespace is removed.
+pub fn is_boxed(name: &str) -> bool {
+  match name.rsplit('.').next().and_then(|name| name.chars().next()) {
+    Some(first) => first.is_ascii_uppercase(),
+    None => false,
+  }
+}
+
+/// What the values of a type are, as code walking a value along its type
+/// tells them apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape<'t> {
+  /// `#`
+  Nat,
+  /// A value the codec of the builtin type `name` reads and writes.
+  Builtin(&'t str),
+  /// A vector, with the parameters of `Vector` giving the item type.
+  Vector(&'t [TLType]),
+  /// An object built by one of the `expected` constructors, with the
+  /// parameters of its type.
+  Object(Expected<'t>, &'t [TLType]),
+  /// A boxed type the schema has no constructors for.
+  UnknownType(&'t str),
+  /// A type without values, such as a natural number `n + 1`.
+  NoValues(&'t TLType),
+}
+
+/// The [`Shape`] of the values of `ty`.
+pub fn shape<'t>(schema: &'t Schema, codecs: &Codecs, ty: &'t TLType) -> Shape<'t> {
+  match ty {
+    TLType::NatType => Shape::Nat,
+    TLType::Bang(_) => Shape::Object(Expected::Function, &[]),
+    TLType::Bare(inner) => bare_shape(schema, codecs, inner),
+    TLType::Named(name, params) if is_boxed(name) => boxed_shape(schema, codecs, name, params),
+    TLType::Named(_, _) => bare_shape(schema, codecs, ty),
+    TLType::Nat(_) | TLType::Plus(_, _) => Shape::NoValues(ty),
+  }
+}
+
+fn boxed_shape<'t>(
+  schema: &'t Schema,
+  codecs: &Codecs,
+  name: &'t str,
+  params: &'t [TLType],
+) -> Shape<'t> {
+  if codecs.get(name).is_some() {
+    return Shape::Builtin(name);
+  }
+  if name == "Vector" {
+    return Shape::Vector(params);
+  }
+  match schema.constructors_of(name)[..] {
+    [] => Shape::UnknownType(name),
+    [builtin] if builtin.builtin => Shape::Builtin(&builtin.name),
+    _ => Shape::Object(Expected::Type(name), params),
+  }
+}
+
+fn bare_shape<'t>(schema: &'t Schema, codecs: &Codecs, ty: &'t TLType) -> Shape<'t> {
+  let (name, params) = match ty {
+    TLType::Named(name, params) => (name.as_str(), params.as_slice()),
+    ty => return shape(schema, codecs, ty),
+  };
+  if name == "vector" || name == "Vector" {
+    return Shape::Vector(params);
+  }
+  let builtin = matches!(schema.constructor(name), Some(constructor) if constructor.builtin);
+  if !is_boxed(name) && (builtin || codecs.get(name).is_some()) {
+    return Shape::Builtin(name);
+  }
+  if !is_boxed(name) {
+    return Shape::Object(Expected::Constructor(name), params);
+  }
+  match schema.constructors_of(name)[..] {
+    [] => Shape::UnknownType(name),
+    [builtin] if builtin.builtin => Shape::Builtin(&builtin.name),
+    _ => Shape::Object(Expected::Bare(name), params),
+  }
+}
+
+/// Constructors an object may be built by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expected<'t> {
+  /// Any constructor or function.
+  Any,
+  /// Any function.
+  Function,
+  /// The constructors of a boxed type.
+  Type(&'t str),
+  /// The constructors of a boxed type written bare, as in `%User`. Only
+  /// a type with a single constructor can be written this way.
+  Bare(&'t str),
+  /// A single bare constructor.
+  Constructor(&'t str),
+}
+
+/// Why a name is not one of the [`Expected`] constructors.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+  /// The schema has no constructor or function of that name.
+  Unknown,
+  /// The schema has one, but it is not expected; the message says why.
+  Unexpected(String),
+}
+
+impl<'t> Expected<'t> {
+  /// The constructor or function `name`, if it is one of those expected.
+  pub fn combinator<'a>(self, schema: &'a Schema, name: &str) -> Result<&'a Combinator, Mismatch> {
+    let constructor = schema.constructor(name);
+    let function = schema.function(name);
+    let (combinator, mismatch) = match self {
+      Expected::Any => (constructor.or(function), None),
+      Expected::Function => (
+        function.or(constructor),
+        Some(format!("`{}` is not a function", name)).filter(|_| function.is_none()),
+      ),
+      Expected::Type(ty) | Expected::Bare(ty) => (
+        constructor,
+        Some(format!("`{}` does not build type `{}`", name, ty))
+          .filter(|_| constructor.is_none_or(|constructor| constructor.type_name() != ty)),
+      ),
+      Expected::Constructor(expected) => (
+        constructor,
+        Some(format!("expected `{}`, found `{}`", expected, name)).filter(|_| name != expected),
+      ),
+    };
+    match (combinator, mismatch) {
+      (None, _) => Err(Mismatch::Unknown),
+      (Some(_), Some(mismatch)) => Err(Mismatch::Unexpected(mismatch)),
+      (Some(combinator), None) => Ok(combinator),
+    }
+  }
+}
+
+/// What is expected, as error messages name it: `object`, `function
+/// call` or the type or constructor name.
+impl<'t> fmt::Display for Expected<'t> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Expected::Any => f.write_str("object"),
+      Expected::Function => f.write_str("function call"),
+      Expected::Type(name) | Expected::Bare(name) | Expected::Constructor(name) => {
+        f.write_str(name)
+      }
+    }
+  }
+}
+
+/// Type of the items of a `[ ... ]` block made of a single unnamed field,
+/// as in `[ t ]`. The records of such blocks are the values themselves
+/// rather than objects.
+pub fn plain_item(block: &[Field]) -> Option<&TLType> {
+  match block {
+    [Field {
+      name: None,
+      ty: FieldType::Type(ty),
+      condition: None,
+      ..
+    }] => Some(ty),
+    _ => None,
+  }
+}
+
+/// Keys of the `#` fields in `fields` that serializing computes: those
+/// conditions test and those counting the records of a block.
+pub fn computed_fields(fields: &[Field]) -> HashSet<&str> {
+  let mut computed = HashSet::new();
+  for field in fields {
+    if let Some(condition) = &field.condition {
+      computed.insert(condition.field.as_str());
+    }
+    if let FieldType::Repeat(count, _) = &field.ty {
+      match count {
+        Count::Expr(TLType::Named(name, params)) if params.is_empty() => {
+          computed.insert(name.as_str());
+        }
+        Count::Expr(TLType::Plus(base, _)) => {
+          if let TLType::Named(name, _) = &**base {
+            computed.insert(name.as_str());
+          }
+        }
+        _ => {}
+      }
+    }
+  }
+  computed
+}
+
+/// Bits of each `#` field used by conditions, as the mask of the bits
+/// conditions test and the bits of the fields present in `object`. Absent
+/// and `false` `flags.N?true` fields leave their bit clear.
+pub fn computed_flags(fields: &[Field], object: &Object) -> HashMap<String, (u32, u32)> {
+  let mut flags: HashMap<String, (u32, u32)> = HashMap::new();
+  for field in fields {
+    let condition = match &field.condition {
+      Some(condition) => condition,
+      None => continue,
+    };
+    let present = match object.get(&field_key(field)) {
+      Some(Value::Bool(set)) if field.is_flag() => *set,
+      Some(_) => true,
+      None => false,
+    };
+    let entry = flags.entry(condition.field.clone()).or_default();
+    entry.0 |= 1 << condition.bit;
+    if present {
+      entry.1 |= 1 << condition.bit;
+    }
+  }
+  flags
+}
+
+/// Values of the `#` fields counting the records of `[ ... ]` blocks, taken
+/// from the number of records given in `object`.
+pub fn computed_counts(fields: &[Field], object: &Object) -> HashMap<String, u32> {
+  let mut counts = HashMap::new();
+  for field in fields {
+    let (count, items) = match (&field.ty, object.get(&field_key(field))) {
+      (FieldType::Repeat(count, _), Some(Value::Vector(items))) => (count, items.len() as u32),
+      _ => continue,
+    };
+    match count {
+      Count::Field(position) => {
+        counts.insert(position.to_string(), items);
+      }
+      Count::Expr(TLType::Named(name, params)) if params.is_empty() => {
+        counts.insert(name.clone(), items);
+      }
+      Count::Expr(TLType::Plus(base, nat)) if items >= *nat => {
+        if let TLType::Named(name, params) = &**base {
+          if params.is_empty() {
+            counts.insert(name.clone(), items - nat);
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+  counts
+}
+
+/// Value of a `#` field, given as a natural or a non-negative `int`.
+pub fn nat_value(value: &Value) -> Option<u32> {
+  match value {
+    Value::Nat(nat) => Some(*nat),
+    Value::Int(int) if *int >= 0 => Some(*int as u32),
+    _ => None,
+  }
+}
+
+/// Values of the implicit parameters and `#` fields of the combinator
+/// being written or read. Types are bound to type expressions and naturals
+/// to `TLType::Nat`.
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+  bindings: Bindings,
+}
+
+impl Scope {
+  /// Binds the implicit parameters of `combinator` that are recovered from
+  /// its result type to the matching parameters of `ty`, and the names in
+  /// the other parameters of its result type to the matching parts of
+  /// `params`. A parameter that does not match leaves the others bound.
+  pub fn for_combinator(combinator: &Combinator, params: &[TLType]) -> Scope {
+    let mut scope = Scope::default();
+    for param in &combinator.params {
+      if let Recovery::ResultType(position) = param.recovery {
+        if let Some(ty) = params.get(position) {
+          scope.bind(&param.name, ty.clone());
+        }
+      }
+    }
+    let mut variables = combinator
+      .params
+      .iter()
+      .map(|param| param.name.as_str())
+      .collect::<HashSet<_>>();
+    variables.extend(
+      combinator
+        .fields
+        .iter()
+        .filter_map(|field| field.name.as_deref()),
+    );
+    for (pattern, ty) in combinator.result_type.params().iter().zip(params) {
+      let mut bindings = scope.bindings.clone();
+      if pattern.unify(ty, &variables, &mut bindings) {
+        scope.bindings = bindings;
+      }
+    }
+    scope
+  }
+
+  pub fn bind(&mut self, name: &str, ty: TLType) {
+    self.bindings.insert(name.to_string(), ty);
+  }
+
+  /// Number of records of a `[ ... ]` block.
+  pub fn count(&self, count: &Count) -> Option<u32> {
+    match count {
+      Count::Expr(expr) => match self.substitute(expr) {
+        TLType::Nat(nat) => Some(nat),
+        _ => None,
+      },
+      Count::Field(position) => self.nat(&position.to_string()),
+    }
+  }
+
+  pub fn nat(&self, name: &str) -> Option<u32> {
+    match self.bindings.get(name) {
+      Some(TLType::Nat(nat)) => Some(*nat),
+      _ => None,
+    }
+  }
+
+  /// Replaces the bound names in `ty` and folds `n+k` once `n` is known.
+  pub fn substitute(&self, ty: &TLType) -> TLType {
+    ty.substitute(&self.bindings)
+  }
+}
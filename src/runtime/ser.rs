@@ -0,0 +1,553 @@
+use super::codec::{Codec, Codecs};
+use super::value::field_key;
+use super::*;
+use crate::schema::{Combinator, Count, Field, FieldType, Schema};
+use std::borrow::Cow;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodeError {
+  UnknownConstructor(String),
+  UnknownFunction(String),
+  UnknownType(String),
+  /// The constructor does not build the expected type.
+  WrongConstructor {
+    expected: String,
+    found: String,
+  },
+  /// A primitive or vector value where another kind was expected.
+  WrongValue {
+    expected: String,
+    found: String,
+  },
+  MissingField {
+    constructor: String,
+    field: String,
+  },
+  UnknownField {
+    constructor: String,
+    field: String,
+  },
+  /// An explicit flags value disagrees with the conditional fields given.
+  InconsistentFlags {
+    constructor: String,
+    field: String,
+    value: u32,
+    expected: u32,
+  },
+  /// The count of a `[ ... ]` block does not evaluate to a number.
+  UnknownCount {
+    constructor: String,
+    field: String,
+  },
+  /// A `[ ... ]` block with a different number of records than its count.
+  WrongCount {
+    constructor: String,
+    field: String,
+    expected: u32,
+    found: usize,
+  },
+  /// A string or bytes value longer than TL can encode.
+  TooLong(usize),
+  Unsupported(String),
+}
+
+impl fmt::Display for EncodeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      EncodeError::UnknownConstructor(name) => write!(f, "unknown constructor `{}`", name),
+      EncodeError::UnknownFunction(name) => write!(f, "unknown function `{}`", name),
+      EncodeError::UnknownType(name) => write!(f, "unknown type `{}`", name),
+      EncodeError::WrongConstructor { expected, found } => write!(
+        f,
+        "constructor `{}` does not build type `{}`",
+        found, expected
+      ),
+      EncodeError::WrongValue { expected, found } => {
+        write!(
+          f,
+          "expected a value of type `{}`, found {}",
+          expected, found
+        )
+      }
+      EncodeError::MissingField { constructor, field } => {
+        write!(f, "`{}` is missing field `{}`", constructor, field)
+      }
+      EncodeError::UnknownField { constructor, field } => {
+        write!(f, "`{}` has no field `{}`", constructor, field)
+      }
+      EncodeError::InconsistentFlags {
+        constructor,
+        field,
+        value,
+        expected,
+      } => write!(
+        f,
+        "`{}` field `{}` is {:#x} but the fields present need {:#x}",
+        constructor, field, value, expected
+      ),
+      EncodeError::UnknownCount { constructor, field } => write!(
+        f,
+        "the number of records in `{}` field `{}` is unknown",
+        constructor, field
+      ),
+      EncodeError::WrongCount {
+        constructor,
+        field,
+        expected,
+        found,
+      } => write!(
+        f,
+        "`{}` field `{}` has {} record(s) but its count is {}",
+        constructor, field, found, expected
+      ),
+      EncodeError::TooLong(length) => write!(f, "{} bytes is too long for a TL string", length),
+      EncodeError::Unsupported(what) => write!(f, "{} is not supported", what),
+    }
+  }
+}
+
+/// Where the bytes a [`Serializer`] writes go.
+enum Output {
+  Bytes(Vec<u8>),
+  /// Only the number of bytes is kept. Builtin values are encoded one at
+  /// a time into `scratch` to be measured.
+  Length {
+    length: usize,
+    scratch: Vec<u8>,
+  },
+}
+
+impl Output {
+  fn put(&mut self, bytes: &[u8]) {
+    match self {
+      Output::Bytes(out) => out.extend_from_slice(bytes),
+      Output::Length { length, .. } => *length += bytes.len(),
+    }
+  }
+
+  fn encode(&mut self, codec: &Codec, value: &Value) -> Result<(), EncodeError> {
+    match self {
+      Output::Bytes(out) => codec.encode(value, out),
+      Output::Length { length, scratch } => {
+        scratch.clear();
+        codec.encode(value, scratch)?;
+        *length += scratch.len();
+        Ok(())
+      }
+    }
+  }
+}
+
+/// Writes dynamic values as TL binary, looking up constructors and field
+/// types in a schema.
+pub struct Serializer<'a> {
+  schema: &'a Schema,
+  codecs: Cow<'a, Codecs>,
+  out: Output,
+}
+
+impl<'a> Serializer<'a> {
+  pub fn new(schema: &'a Schema) -> Serializer<'a> {
+    Serializer {
+      schema,
+      codecs: Cow::Owned(Codecs::default()),
+      out: Output::Bytes(vec![]),
+    }
+  }
+
+  /// Serializer using `codecs` for builtin types.
+  pub fn with_codecs(schema: &'a Schema, codecs: &'a Codecs) -> Serializer<'a> {
+    Serializer {
+      schema,
+      codecs: Cow::Borrowed(codecs),
+      out: Output::Bytes(vec![]),
+    }
+  }
+
+  /// Serializer that counts the bytes it would write without keeping
+  /// them.
+  pub fn measuring(schema: &'a Schema, codecs: &'a Codecs) -> Serializer<'a> {
+    Serializer {
+      schema,
+      codecs: Cow::Borrowed(codecs),
+      out: Output::Length {
+        length: 0,
+        scratch: vec![],
+      },
+    }
+  }
+
+  /// Number of bytes written so far.
+  pub fn written(&self) -> usize {
+    match &self.out {
+      Output::Bytes(out) => out.len(),
+      Output::Length { length, .. } => *length,
+    }
+  }
+
+  /// Bytes written so far, none for a measuring serializer.
+  pub fn finish(self) -> Vec<u8> {
+    match self.out {
+      Output::Bytes(out) => out,
+      Output::Length { .. } => vec![],
+    }
+  }
+
+  /// Writes `value` as a boxed object: a function call if its constructor
+  /// names a function, a constructor of its own result type otherwise.
+  pub fn write_object(&mut self, value: &Value) -> Result<(), EncodeError> {
+    let object = match value {
+      Value::Object(object) => object,
+      Value::Unknown { id, raw } => return self.write_unknown(*id, raw),
+      value => {
+        return Err(EncodeError::WrongValue {
+          expected: "object".to_string(),
+          found: value.kind(),
+        })
+      }
+    };
+    if self.schema.function(&object.constructor).is_some() {
+      return self.write_call(value);
+    }
+    let combinator = self.constructor(&object.constructor)?;
+    self.write_u32(combinator.id);
+    self.write_fields(combinator, &[], object)
+  }
+
+  /// Writes `value` as an instance of `ty`. Boxed types are prefixed with
+  /// the constructor ID, bare ones (`%T`, `int`, `user`) are not.
+  pub fn write(&mut self, ty: &TLType, value: &Value) -> Result<(), EncodeError> {
+    match ty {
+      TLType::NatType => {
+        let nat = expect_nat(value)?;
+        self.write_u32(nat);
+        Ok(())
+      }
+      TLType::Bang(_) => self.write_call(value),
+      TLType::Bare(inner) => self.write_bare(inner, value),
+      TLType::Named(name, params) if is_boxed(name) => self.write_boxed(name, params, value),
+      TLType::Named(_, _) => self.write_bare(ty, value),
+      TLType::Nat(_) | TLType::Plus(_, _) => Err(EncodeError::Unsupported(format!(
+        "writing a value of type `{}`",
+        ty
+      ))),
+    }
+  }
+
+  fn write_boxed(
+    &mut self,
+    name: &str,
+    params: &[TLType],
+    value: &Value,
+  ) -> Result<(), EncodeError> {
+    if let Some(codec) = self.codecs.get(name) {
+      return self.out.encode(codec, value);
+    }
+    if name == "Vector" {
+      let id = match self.schema.constructor("vector") {
+        Some(vector) => vector.id,
+        None => VECTOR_ID,
+      };
+      self.write_u32(id);
+      return self.write_vector(params, value);
+    }
+    if let Value::Unknown { id, raw } = value {
+      return self.write_unknown(*id, raw);
+    }
+
+    let constructors = self.schema.constructors_of(name);
+    if constructors.is_empty() {
+      return Err(EncodeError::UnknownType(name.to_string()));
+    }
+    // builtin types such as `Int` have their value written after the ID of
+    // their single builtin constructor
+    if let [builtin] = constructors.as_slice() {
+      if builtin.builtin {
+        self.write_u32(builtin.id);
+        return self.write_builtin(&builtin.name, value);
+      }
+    }
+
+    let object = object_value(value, name)?;
+    let combinator = self.constructor(&object.constructor)?;
+    if combinator.type_name() != name {
+      return Err(EncodeError::WrongConstructor {
+        expected: name.to_string(),
+        found: object.constructor.clone(),
+      });
+    }
+    self.write_u32(combinator.id);
+    self.write_fields(combinator, params, object)
+  }
+
+  fn write_bare(&mut self, ty: &TLType, value: &Value) -> Result<(), EncodeError> {
+    let (name, params) = match ty {
+      TLType::Named(name, params) => (name.as_str(), params.as_slice()),
+      ty => return self.write(ty, value),
+    };
+    if name == "vector" || name == "Vector" {
+      return self.write_vector(params, value);
+    }
+    let builtin = matches!(self.schema.constructor(name), Some(constructor) if constructor.builtin);
+    if !is_boxed(name) && (builtin || self.codecs.get(name).is_some()) {
+      return self.write_builtin(name, value);
+    }
+    if let [builtin] = self.schema.constructors_of(name).as_slice() {
+      if builtin.builtin {
+        return self.write_builtin(&builtin.name, value);
+      }
+    }
+
+    let object = object_value(value, name)?;
+    let combinator = self.constructor(&object.constructor)?;
+    if is_boxed(name) {
+      // `%T` of a boxed type takes any of its constructors
+      if combinator.type_name() != name {
+        return Err(EncodeError::WrongConstructor {
+          expected: name.to_string(),
+          found: object.constructor.clone(),
+        });
+      }
+    } else if combinator.name != name {
+      return Err(EncodeError::WrongConstructor {
+        expected: name.to_string(),
+        found: object.constructor.clone(),
+      });
+    }
+    self.write_fields(combinator, params, object)
+  }
+
+  fn write_call(&mut self, value: &Value) -> Result<(), EncodeError> {
+    if let Value::Unknown { id, raw } = value {
+      return self.write_unknown(*id, raw);
+    }
+    let object = object_value(value, "function call")?;
+    let function = self
+      .schema
+      .function(&object.constructor)
+      .ok_or_else(|| EncodeError::UnknownFunction(object.constructor.clone()))?;
+    self.write_u32(function.id);
+    self.write_fields(function, &[], object)
+  }
+
+  fn write_fields(
+    &mut self,
+    combinator: &Combinator,
+    params: &[TLType],
+    object: &Object,
+  ) -> Result<(), EncodeError> {
+    let scope = Scope::for_combinator(combinator, params);
+    self.write_block(&combinator.name, &combinator.fields, object, scope)
+  }
+
+  /// Writes the fields of the combinator `name` or of one record of one of
+  /// its `[ ... ]` blocks.
+  fn write_block(
+    &mut self,
+    name: &str,
+    fields: &[Field],
+    object: &Object,
+    mut scope: Scope,
+  ) -> Result<(), EncodeError> {
+    for (key, _) in &object.fields {
+      if !fields.iter().any(|field| &field_key(field) == key) {
+        return Err(EncodeError::UnknownField {
+          constructor: name.to_string(),
+          field: key.clone(),
+        });
+      }
+    }
+
+    let flags = computed_flags(fields, object);
+    let counts = computed_counts(fields, object);
+    for field in fields {
+      let key = field_key(field);
+      let value = object.get(&key);
+      if let Some(condition) = &field.condition {
+        let flags = match scope.nat(&condition.field) {
+          Some(flags) => flags,
+          None => {
+            return Err(EncodeError::MissingField {
+              constructor: name.to_string(),
+              field: condition.field.clone(),
+            })
+          }
+        };
+        if flags & (1 << condition.bit) == 0 || field.is_flag() {
+          continue;
+        }
+      }
+      if let Some(&(mask, bits)) = flags.get(&key) {
+        let nat = match value {
+          Some(value) => expect_nat(value)?,
+          None => bits,
+        };
+        if nat & mask != bits {
+          return Err(EncodeError::InconsistentFlags {
+            constructor: name.to_string(),
+            field: key,
+            value: nat,
+            expected: bits,
+          });
+        }
+        self.write_u32(nat);
+        scope.bind(&key, TLType::Nat(nat));
+        continue;
+      }
+      let value = match (value, counts.get(&key)) {
+        (Some(value), _) => value,
+        (None, Some(&count)) => {
+          self.write_u32(count);
+          scope.bind(&key, TLType::Nat(count));
+          continue;
+        }
+        (None, None) => {
+          return Err(EncodeError::MissingField {
+            constructor: name.to_string(),
+            field: key,
+          })
+        }
+      };
+      match &field.ty {
+        FieldType::Type(ty) => {
+          let ty = scope.substitute(ty);
+          self.write(&ty, value)?;
+          if ty == TLType::NatType {
+            scope.bind(&key, TLType::Nat(expect_nat(value)?));
+          }
+        }
+        FieldType::Repeat(count, block) => {
+          self.write_repeat(name, &key, count, block, value, &scope)?
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Writes the records of a `[ ... ]` block, `count` of them.
+  fn write_repeat(
+    &mut self,
+    name: &str,
+    key: &str,
+    count: &Count,
+    block: &[Field],
+    value: &Value,
+    scope: &Scope,
+  ) -> Result<(), EncodeError> {
+    let items = match value {
+      Value::Vector(items) => items,
+      value => {
+        return Err(EncodeError::WrongValue {
+          expected: "vector".to_string(),
+          found: value.kind(),
+        })
+      }
+    };
+    let count = scope
+      .count(count)
+      .ok_or_else(|| EncodeError::UnknownCount {
+        constructor: name.to_string(),
+        field: key.to_string(),
+      })?;
+    if items.len() != count as usize {
+      return Err(EncodeError::WrongCount {
+        constructor: name.to_string(),
+        field: key.to_string(),
+        expected: count,
+        found: items.len(),
+      });
+    }
+    for item in items {
+      match plain_item(block) {
+        Some(ty) => self.write(&scope.substitute(ty), item)?,
+        None => self.write_block(name, block, object_value(item, "record")?, scope.clone())?,
+      }
+    }
+    Ok(())
+  }
+
+  fn write_vector(&mut self, params: &[TLType], value: &Value) -> Result<(), EncodeError> {
+    let items = match value {
+      Value::Vector(items) => items,
+      value => {
+        return Err(EncodeError::WrongValue {
+          expected: "vector".to_string(),
+          found: value.kind(),
+        })
+      }
+    };
+    let item_type = match params {
+      [item_type] => item_type,
+      _ => return Err(EncodeError::UnknownType("Vector".to_string())),
+    };
+    self.write_u32(items.len() as u32);
+    for item in items {
+      self.write(item_type, item)?;
+    }
+    Ok(())
+  }
+
+  fn write_builtin(&mut self, name: &str, value: &Value) -> Result<(), EncodeError> {
+    match self.codecs.get(name) {
+      Some(codec) => self.out.encode(codec, value),
+      None => Err(EncodeError::Unsupported(format!(
+        "the builtin type `{}` without a codec",
+        name
+      ))),
+    }
+  }
+
+  fn write_u32(&mut self, value: u32) {
+    self.out.put(&value.to_le_bytes());
+  }
+
+  /// Writes back an object decoded with an unknown constructor ID.
+  fn write_unknown(&mut self, id: u32, raw: &[u8]) -> Result<(), EncodeError> {
+    self.write_u32(id);
+    self.out.put(raw);
+    Ok(())
+  }
+
+  fn constructor(&self, name: &str) -> Result<&'a Combinator, EncodeError> {
+    self
+      .schema
+      .constructor(name)
+      .ok_or_else(|| EncodeError::UnknownConstructor(name.to_string()))
+  }
+}
+
+fn expect_nat(value: &Value) -> Result<u32, EncodeError> {
+  nat_value(value).ok_or_else(|| EncodeError::WrongValue {
+    expected: "#".to_string(),
+    found: value.kind(),
+  })
+}
+
+fn object_value<'v, 'd>(
+  value: &'v Value<'d>,
+  expected: &str,
+) -> Result<&'v Object<'d>, EncodeError> {
+  match value {
+    Value::Object(object) => Ok(object),
+    value => Err(EncodeError::WrongValue {
+      expected: expected.to_string(),
+      found: value.kind(),
+    }),
+  }
+}
+
+/// Serializes a boxed object or function call.
+pub fn serialize(schema: &Schema, value: &Value) -> Result<Vec<u8>, EncodeError> {
+  let mut serializer = Serializer::new(schema);
+  serializer.write_object(value)?;
+  Ok(serializer.finish())
+}
+
+/// Serializes `value` as an instance of `ty`, e.g. `Vector User` or
+/// `%User`.
+pub fn serialize_as(schema: &Schema, ty: &TLType, value: &Value) -> Result<Vec<u8>, EncodeError> {
+  let mut serializer = Serializer::new(schema);
+  serializer.write(ty, value)?;
+  Ok(serializer.finish())
+}
@@ -0,0 +1,486 @@
+use super::codec::{Codec, Codecs};
+use super::ser::{EncodeError, Serializer};
+use super::*;
+use crate::schema::{Combinator, Schema};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Bounds on the encoded size of the values of a type or combinator, in
+/// bytes. `max` is `None` when values can be arbitrarily large.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Size {
+  pub min: usize,
+  pub max: Option<usize>,
+}
+
+/// Size of values nothing is known about, such as those of type variables.
+const UNKNOWN: Size = Size { min: 0, max: None };
+
+/// Most a parameter of a type or constructor may nest before the type is
+/// taken as unbounded. Polymorphic recursion such as
+/// `cons {t:Type} x:t rest:(Foo (Foo t)) = Foo t` reaches ever deeper
+/// types.
+const MAX_NESTING: usize = 16;
+
+/// Most instances of one type or constructor analysed, past which the
+/// others are taken as unbounded, should parameters grow without nesting,
+/// e.g. `Foo 1`, `Foo 2` and so on.
+const MAX_INSTANCES: usize = 64;
+
+impl Size {
+  pub fn fixed(size: usize) -> Size {
+    Size {
+      min: size,
+      max: Some(size),
+    }
+  }
+
+  pub fn is_fixed(&self) -> bool {
+    self.max == Some(self.min)
+  }
+
+  /// Size of a value of this size followed by one of size `other`.
+  fn then(self, other: Size) -> Size {
+    Size {
+      min: self.min.saturating_add(other.min),
+      max: match (self.max, other.max) {
+        (Some(max), Some(other)) => max.checked_add(other),
+        _ => None,
+      },
+    }
+  }
+
+  /// Size of a value that has either this size or size `other`.
+  fn or(self, other: Size) -> Size {
+    Size {
+      min: self.min.min(other.min),
+      max: match (self.max, other.max) {
+        (Some(max), Some(other)) => Some(max.max(other)),
+        _ => None,
+      },
+    }
+  }
+
+  fn times(self, count: u32) -> Size {
+    let count = count as usize;
+    Size {
+      min: self.min.saturating_mul(count),
+      max: self.max.and_then(|max| max.checked_mul(count)),
+    }
+  }
+}
+
+impl fmt::Display for Size {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.max {
+      Some(max) if max == self.min => write!(f, "{} bytes", max),
+      Some(max) => write!(f, "{} to {} bytes", self.min, max),
+      None => write!(f, "at least {} bytes", self.min),
+    }
+  }
+}
+
+/// Exact length of `value` written as a boxed object, computed without
+/// keeping the bytes.
+pub fn encoded_len(schema: &Schema, value: &Value) -> Result<usize, EncodeError> {
+  encoded_len_with_codecs(schema, &Codecs::default(), value)
+}
+
+/// Like [`encoded_len`], with `codecs` for builtin types.
+pub fn encoded_len_with_codecs(
+  schema: &Schema,
+  codecs: &Codecs,
+  value: &Value,
+) -> Result<usize, EncodeError> {
+  let mut serializer = Serializer::measuring(schema, codecs);
+  serializer.write_object(value)?;
+  Ok(serializer.written())
+}
+
+/// Exact length of `value` written as an instance of `ty`.
+pub fn encoded_len_as(schema: &Schema, ty: &TLType, value: &Value) -> Result<usize, EncodeError> {
+  encoded_len_as_with_codecs(schema, &Codecs::default(), ty, value)
+}
+
+/// Like [`encoded_len_as`], with `codecs` for builtin types.
+pub fn encoded_len_as_with_codecs(
+  schema: &Schema,
+  codecs: &Codecs,
+  ty: &TLType,
+  value: &Value,
+) -> Result<usize, EncodeError> {
+  let mut serializer = Serializer::measuring(schema, codecs);
+  serializer.write(ty, value)?;
+  Ok(serializer.written())
+}
+
+/// Size of each combinator of `schema` written as a boxed object, its ID
+/// included. The parameters of polymorphic combinators, such as `t` in
+/// `vector {t:Type} # [ t ] = Vector t`, can have values of any size.
+pub fn combinator_sizes(schema: &Schema) -> Vec<(&Combinator, Size)> {
+  combinator_sizes_with_codecs(schema, &Codecs::default())
+}
+
+/// Like [`combinator_sizes`], with the sizes of builtin values taken from
+/// `codecs`.
+pub fn combinator_sizes_with_codecs<'a>(
+  schema: &'a Schema,
+  codecs: &Codecs,
+) -> Vec<(&'a Combinator, Size)> {
+  let mut analysis = Analysis::new(schema, Cow::Borrowed(codecs));
+  analysis.run(&[]);
+  schema
+    .combinators()
+    .iter()
+    .map(|combinator| (combinator, analysis.combinator_size(combinator)))
+    .collect()
+}
+
+/// Size of the values of `ty`, e.g. `Vector int` or `%User`.
+pub fn type_size(schema: &Schema, ty: &TLType) -> Size {
+  type_size_with_codecs(schema, &Codecs::default(), ty)
+}
+
+/// Like [`type_size`], with the sizes of builtin values taken from
+/// `codecs`.
+pub fn type_size_with_codecs(schema: &Schema, codecs: &Codecs, ty: &TLType) -> Size {
+  let mut analysis = Analysis::new(schema, Cow::Borrowed(codecs));
+  analysis.run(std::slice::from_ref(ty));
+  analysis.type_size(ty, &[])
+}
+
+/// Sizes of the types of one schema, analysed once for many lookups.
+pub struct Sizes<'a> {
+  analysis: Analysis<'a>,
+}
+
+impl<'a> Sizes<'a> {
+  pub fn new(schema: &'a Schema) -> Sizes<'a> {
+    Sizes::with_cow_codecs(schema, Cow::Owned(Codecs::default()))
+  }
+
+  /// Sizes with those of builtin values taken from `codecs`.
+  pub fn with_codecs(schema: &'a Schema, codecs: &'a Codecs) -> Sizes<'a> {
+    Sizes::with_cow_codecs(schema, Cow::Borrowed(codecs))
+  }
+
+  pub(crate) fn with_cow_codecs(schema: &'a Schema, codecs: Cow<'a, Codecs>) -> Sizes<'a> {
+    let mut analysis = Analysis::new(schema, codecs);
+    analysis.run(&[]);
+    Sizes { analysis }
+  }
+
+  /// Size of the values of `ty`. The minimum of a type no combinator of
+  /// the schema refers to, such as `Vector (Vector User)`, is not refined
+  /// and may be too low.
+  pub fn type_size(&mut self, ty: &TLType) -> Size {
+    self.analysis.type_size(ty, &[])
+  }
+}
+
+/// How deep the parameters of a type nest, `Foo (Foo t)` being 2 deep and
+/// `n + 1` one deeper than `n`.
+fn nesting(ty: &TLType) -> usize {
+  match ty {
+    TLType::Named(_, params) => 1 + params.iter().map(nesting).max().unwrap_or(0),
+    TLType::Bare(inner) | TLType::Bang(inner) => nesting(inner),
+    TLType::Plus(base, _) => 1 + nesting(base),
+    TLType::Nat(_) | TLType::NatType => 0,
+  }
+}
+
+/// The values of a boxed type or of a single constructor, without the
+/// constructor ID.
+#[derive(Debug, Clone)]
+enum Target {
+  Type(String, Vec<TLType>),
+  Constructor(String, Vec<TLType>),
+}
+
+impl Target {
+  fn name(&self) -> String {
+    match self {
+      Target::Type(name, _) => name.clone(),
+      Target::Constructor(name, _) => format!("%{}", name),
+    }
+  }
+
+  fn params(&self) -> &[TLType] {
+    match self {
+      Target::Type(_, params) | Target::Constructor(_, params) => params,
+    }
+  }
+
+  fn key(&self) -> String {
+    let (prefix, name, params) = match self {
+      Target::Type(name, params) => ("", name, params),
+      Target::Constructor(name, params) => ("%", name, params),
+    };
+    TLType::Named(format!("{}{}", prefix, name), params.clone()).to_string()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+  Min,
+  Max,
+}
+
+/// Bounds on the sizes of the types of a schema. Minimums are refined
+/// over every type reached until none changes, as a type can be as small
+/// as its smallest constructor even if its others refer back to it.
+/// Maximums are then found depth first, a type that contains itself being
+/// unbounded.
+struct Analysis<'a> {
+  schema: &'a Schema,
+  codecs: Cow<'a, Codecs>,
+  phase: Phase,
+  targets: Vec<Target>,
+  mins: HashMap<String, usize>,
+  maxes: HashMap<String, Option<usize>>,
+  visiting: HashSet<String>,
+  /// Instances of each type or constructor met, by target name.
+  instances: HashMap<String, usize>,
+}
+
+impl<'a> Analysis<'a> {
+  fn new(schema: &'a Schema, codecs: Cow<'a, Codecs>) -> Analysis<'a> {
+    Analysis {
+      schema,
+      codecs,
+      phase: Phase::Min,
+      targets: vec![],
+      mins: HashMap::new(),
+      maxes: HashMap::new(),
+      visiting: HashSet::new(),
+      instances: HashMap::new(),
+    }
+  }
+
+  /// Computes the minimums of every type reachable from the combinators
+  /// of the schema and from `types`, then switches to finding maximums.
+  fn run(&mut self, types: &[TLType]) {
+    loop {
+      let known = self.targets.len();
+      for combinator in self.schema.combinators() {
+        self.combinator_size(combinator);
+      }
+      for ty in types {
+        self.type_size(ty, &[]);
+      }
+      let mut changed = self.targets.len() != known;
+      for index in 0..self.targets.len() {
+        let target = self.targets[index].clone();
+        let min = self.target_size(&target).min;
+        let key = target.key();
+        if min < self.mins[&key] {
+          self.mins.insert(key, min);
+          changed = true;
+        }
+      }
+      if !changed {
+        break;
+      }
+    }
+    self.phase = Phase::Max;
+  }
+
+  /// Size of the values of the builtin type `name`, as its codec gives it.
+  fn builtin_size(&self, name: &str) -> Option<Size> {
+    self.codecs.get(name).and_then(Codec::size)
+  }
+
+  fn combinator_size(&mut self, combinator: &Combinator) -> Size {
+    let fields = if combinator.builtin {
+      self.builtin_size(&combinator.name).unwrap_or(UNKNOWN)
+    } else {
+      self.fields_size(combinator, &[])
+    };
+    Size::fixed(4).then(fields)
+  }
+
+  fn fields_size(&mut self, combinator: &Combinator, params: &[TLType]) -> Size {
+    let scope = Scope::for_combinator(combinator, params);
+    let vars = combinator
+      .params
+      .iter()
+      .map(|param| param.name.as_str())
+      .collect::<Vec<_>>();
+    self.block_size(&combinator.fields, &scope, &vars)
+  }
+
+  fn block_size(&mut self, fields: &[Field], scope: &Scope, vars: &[&str]) -> Size {
+    let mut size = Size::fixed(0);
+    for field in fields {
+      let field_size = match &field.ty {
+        FieldType::Type(ty) => self.type_size(&scope.substitute(ty), vars),
+        FieldType::Repeat(count, block) => {
+          let record = match plain_item(block) {
+            Some(ty) => self.type_size(&scope.substitute(ty), vars),
+            None => self.block_size(block, scope, vars),
+          };
+          match scope.count(count) {
+            Some(count) => record.times(count),
+            None => {
+              let least = match count {
+                Count::Expr(TLType::Plus(_, nat)) => *nat,
+                _ => 0,
+              };
+              Size {
+                min: record.times(least).min,
+                max: if record.max == Some(0) { Some(0) } else { None },
+              }
+            }
+          }
+        }
+      };
+      size = size.then(match field.condition {
+        Some(_) => Size::fixed(0).or(field_size),
+        None => field_size,
+      });
+    }
+    size
+  }
+
+  fn type_size(&mut self, ty: &TLType, vars: &[&str]) -> Size {
+    match ty {
+      TLType::NatType => Size::fixed(4),
+      TLType::Bang(_) => Size { min: 4, max: None },
+      TLType::Bare(inner) => self.bare_size(inner, vars),
+      TLType::Named(name, params) if params.is_empty() && vars.contains(&name.as_str()) => UNKNOWN,
+      TLType::Named(name, params) if is_boxed(name) => self.boxed_size(name, params, vars),
+      TLType::Named(_, _) => self.bare_size(ty, vars),
+      TLType::Nat(_) | TLType::Plus(_, _) => UNKNOWN,
+    }
+  }
+
+  fn boxed_size(&mut self, name: &str, params: &[TLType], vars: &[&str]) -> Size {
+    if let Some(size) = self.builtin_size(name) {
+      return size;
+    }
+    if name == "Vector" {
+      return Size::fixed(4).then(self.vector_size(params, vars));
+    }
+    let constructors = self.schema.constructors_of(name);
+    if let [builtin] = constructors.as_slice() {
+      if builtin.builtin {
+        return Size::fixed(4).then(self.builtin_size(&builtin.name).unwrap_or(UNKNOWN));
+      }
+    }
+    if constructors.is_empty() {
+      return UNKNOWN;
+    }
+    Size::fixed(4).then(self.target(Target::Type(name.to_string(), params.to_vec())))
+  }
+
+  fn bare_size(&mut self, ty: &TLType, vars: &[&str]) -> Size {
+    let (name, params) = match ty {
+      TLType::Named(name, params) if !(params.is_empty() && vars.contains(&name.as_str())) => {
+        (name.as_str(), params.as_slice())
+      }
+      ty => return self.type_size(ty, vars),
+    };
+    if name == "vector" || name == "Vector" {
+      return self.vector_size(params, vars);
+    }
+    if !is_boxed(name) {
+      return match self.schema.constructor(name) {
+        Some(constructor) if constructor.builtin => self.builtin_size(name).unwrap_or(UNKNOWN),
+        Some(_) => self.target(Target::Constructor(name.to_string(), params.to_vec())),
+        None => self.builtin_size(name).unwrap_or(UNKNOWN),
+      };
+    }
+    let constructors = self.schema.constructors_of(name);
+    if let [builtin] = constructors.as_slice() {
+      if builtin.builtin {
+        return self.builtin_size(&builtin.name).unwrap_or(UNKNOWN);
+      }
+    }
+    if constructors.is_empty() {
+      return UNKNOWN;
+    }
+    self.target(Target::Type(name.to_string(), params.to_vec()))
+  }
+
+  fn vector_size(&mut self, params: &[TLType], vars: &[&str]) -> Size {
+    let item = match params {
+      [item] => self.type_size(item, vars),
+      _ => return UNKNOWN,
+    };
+    Size {
+      min: 4,
+      max: if item.max == Some(0) { Some(4) } else { None },
+    }
+  }
+
+  /// Size of a target as known so far: its current minimum while
+  /// minimums are computed, its maximum found depth first afterwards.
+  /// Targets nesting too deep, or met after too many instances of their
+  /// type or constructor, are unbounded.
+  fn target(&mut self, target: Target) -> Size {
+    let key = target.key();
+    if self.phase == Phase::Min {
+      let min = match self.mins.get(&key).copied() {
+        Some(min) => min,
+        None if !self.admit(&target) => return UNKNOWN,
+        None => {
+          self.mins.insert(key, usize::MAX);
+          self.targets.push(target);
+          usize::MAX
+        }
+      };
+      return Size { min, max: None };
+    }
+    let min = self.mins.get(&key).copied().unwrap_or(0);
+    if let Some(max) = self.maxes.get(&key) {
+      return Size { min, max: *max };
+    }
+    if !self.mins.contains_key(&key) && !self.admit(&target) {
+      return UNKNOWN;
+    }
+    if !self.visiting.insert(key.clone()) {
+      return Size { min, max: None };
+    }
+    let size = self.target_size(&target);
+    self.visiting.remove(&key);
+    self.maxes.insert(key.clone(), size.max);
+    // a target first met now has the minimum of its fields as they are
+    // known, not refined
+    let min = *self.mins.entry(key).or_insert(size.min);
+    Size { min, max: size.max }
+  }
+
+  /// Counts a target met for the first time, unless it nests too deep or
+  /// its type or constructor has had too many instances.
+  fn admit(&mut self, target: &Target) -> bool {
+    if target
+      .params()
+      .iter()
+      .any(|param| nesting(param) > MAX_NESTING)
+    {
+      return false;
+    }
+    let instances = self.instances.entry(target.name()).or_insert(0);
+    *instances += 1;
+    *instances <= MAX_INSTANCES
+  }
+
+  fn target_size(&mut self, target: &Target) -> Size {
+    let schema = self.schema;
+    match target {
+      Target::Type(name, params) => schema
+        .constructors_of(name)
+        .into_iter()
+        .map(|constructor| self.fields_size(constructor, params))
+        .fold(None, |size: Option<Size>, other| {
+          Some(size.map_or(other, |size| size.or(other)))
+        })
+        .unwrap_or(UNKNOWN),
+      Target::Constructor(name, params) => match schema.constructor(name) {
+        Some(constructor) => self.fields_size(constructor, params),
+        None => UNKNOWN,
+      },
+    }
+  }
+}
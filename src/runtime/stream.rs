@@ -0,0 +1,135 @@
+use super::codec::Codecs;
+use super::de::{DecodeError, Deserializer, Limits};
+use super::*;
+use crate::schema::Schema;
+use std::borrow::Cow;
+
+/// Result of asking a [`StreamDecoder`] for the next object.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decoded {
+  Object(Value<'static>),
+  /// The buffered bytes end in the middle of an object.
+  NeedMoreData,
+}
+
+/// Decodes a sequence of top-level values from input that arrives in
+/// pieces. Each value is returned as soon as all of its bytes have been
+/// fed, and its bytes are then dropped from the buffer.
+///
+/// A value cut short is decoded again from its start once enough bytes
+/// are buffered, so error offsets are relative to the start of the value
+/// being decoded.
+///
+/// Any other error poisons the stream: the decoder cannot tell where the
+/// bad value ends, so it keeps returning the error until the caller drops
+/// bytes with [`StreamDecoder::skip`] or [`StreamDecoder::reset`].
+pub struct StreamDecoder<'a> {
+  schema: &'a Schema,
+  codecs: Cow<'a, Codecs>,
+  limits: Limits,
+  ty: Option<TLType>,
+  buffer: Vec<u8>,
+  /// Offset in the buffer of the next value. The bytes before it are
+  /// dropped when more input is fed.
+  start: usize,
+  /// Buffer length below which decoding is known to run out of bytes.
+  wanted: usize,
+  error: Option<DecodeError>,
+}
+
+impl<'a> StreamDecoder<'a> {
+  /// Decoder for boxed objects and function calls of any type.
+  pub fn new(schema: &'a Schema) -> StreamDecoder<'a> {
+    StreamDecoder {
+      schema,
+      codecs: Cow::Owned(Codecs::default()),
+      limits: Limits::default(),
+      ty: None,
+      buffer: vec![],
+      start: 0,
+      wanted: 0,
+      error: None,
+    }
+  }
+
+  /// Decoder for instances of `ty`.
+  pub fn for_type(schema: &'a Schema, ty: TLType) -> StreamDecoder<'a> {
+    StreamDecoder {
+      ty: Some(ty),
+      ..StreamDecoder::new(schema)
+    }
+  }
+
+  pub fn set_codecs(&mut self, codecs: &'a Codecs) {
+    self.codecs = Cow::Borrowed(codecs);
+  }
+
+  /// Limits applied to each value decoded.
+  pub fn set_limits(&mut self, limits: Limits) {
+    self.limits = limits;
+  }
+
+  /// Appends `data` to the buffered input.
+  pub fn feed(&mut self, data: &[u8]) {
+    if self.start > 0 {
+      self.buffer.drain(..self.start);
+      self.wanted = self.wanted.saturating_sub(self.start);
+      self.start = 0;
+    }
+    self.buffer.extend_from_slice(data);
+  }
+
+  /// Number of bytes fed but not yet part of a decoded value.
+  pub fn buffered(&self) -> usize {
+    self.buffer.len() - self.start
+  }
+
+  /// Drops the next `count` buffered bytes, e.g. the rest of a frame whose
+  /// length the transport knows, and clears the error they caused.
+  pub fn skip(&mut self, count: usize) {
+    self.start += count.min(self.buffered());
+    self.wanted = 0;
+    self.error = None;
+  }
+
+  /// Drops all buffered bytes and clears the error they caused.
+  pub fn reset(&mut self) {
+    self.buffer.clear();
+    self.start = 0;
+    self.wanted = 0;
+    self.error = None;
+  }
+
+  /// Decodes the next value if all of its bytes are buffered.
+  pub fn next_object(&mut self) -> Result<Decoded, DecodeError> {
+    if let Some(err) = &self.error {
+      return Err(err.clone());
+    }
+    if self.buffered() == 0 || self.buffer.len() < self.wanted {
+      return Ok(Decoded::NeedMoreData);
+    }
+    let data = &self.buffer[self.start..];
+    let mut deserializer = Deserializer::with_codecs(self.schema, &self.codecs, data);
+    deserializer.set_limits(self.limits);
+    let value = match &self.ty {
+      Some(ty) => deserializer.read(ty),
+      None => deserializer.read_object(),
+    };
+    match value {
+      Ok(value) => {
+        let value = value.into_owned();
+        self.start += deserializer.offset();
+        self.wanted = 0;
+        Ok(Decoded::Object(value))
+      }
+      Err(DecodeError::Truncated { offset, needed }) => {
+        self.wanted = self.start + offset + needed;
+        Ok(Decoded::NeedMoreData)
+      }
+      Err(err) => {
+        self.error = Some(err.clone());
+        Err(err)
+      }
+    }
+  }
+}
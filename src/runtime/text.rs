@@ -0,0 +1,740 @@
+use super::codec::Codecs;
+use super::value::field_key;
+use super::*;
+use crate::lexer::TLTokenEnum;
+use crate::schema::{Combinator, Field, FieldType, Schema};
+use logos::Logos;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Failure to read a value from text, at a line and column of the text,
+/// both counted from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextError {
+  pub line: usize,
+  pub column: usize,
+  pub message: String,
+}
+
+impl fmt::Display for TextError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(
+      f,
+      "line {}, column {}: {}",
+      self.line, self.column, self.message
+    )
+  }
+}
+
+impl std::error::Error for TextError {}
+
+/// Renders `value` in the text form of TL values, e.g.
+/// `user id:42 first_name:"Ann" photo:(userProfilePhotoEmpty)`:
+///
+/// - objects are their constructor followed by `field:value` pairs, in
+///   parentheses unless at the top, and `[ ... ]` records are pairs in
+///   parentheses;
+/// - vectors are their items in square brackets;
+/// - numbers are decimal, strings quoted, bytes `b"..."` with `\xNN`
+///   escapes, and `int128` and `int256` are `0x` and their bytes in wire
+///   order;
+/// - objects of unknown constructors are their `#id` and `b"..."` bytes.
+///
+/// `#` fields the conditions of other fields test, and flags that are not
+/// set, are left out: serializing computes them.
+pub fn print(schema: &Schema, value: &Value) -> String {
+  let mut printer = Printer {
+    schema,
+    out: String::new(),
+  };
+  printer.value(value, false);
+  printer.out
+}
+
+/// Reads a boxed object or function call of any type from text in the
+/// form [`print`] writes.
+pub fn parse(schema: &Schema, text: &str) -> Result<Value<'static>, TextError> {
+  let mut parser = Parser::new(schema, text)?;
+  let value = parser.object(Expected::Any, &[], true)?;
+  parser.finish()?;
+  Ok(value)
+}
+
+/// Reads an instance of `ty`, e.g. `Vector User` or `%User`, from text in
+/// the form [`print`] writes. Constructors, fields and literals are checked
+/// against the schema.
+pub fn parse_as(schema: &Schema, ty: &TLType, text: &str) -> Result<Value<'static>, TextError> {
+  let mut parser = Parser::new(schema, text)?;
+  parser.top = true;
+  let value = parser.value(ty)?;
+  parser.finish()?;
+  Ok(value)
+}
+
+struct Printer<'a> {
+  schema: &'a Schema,
+  out: String,
+}
+
+impl<'a> Printer<'a> {
+  fn value(&mut self, value: &Value, nested: bool) {
+    match value {
+      Value::Nat(nat) => self.out.push_str(&nat.to_string()),
+      Value::Bool(value) => self.out.push_str(&value.to_string()),
+      Value::Int(int) => self.out.push_str(&int.to_string()),
+      Value::Long(long) => self.out.push_str(&long.to_string()),
+      Value::Double(double) => self.out.push_str(&double_text(*double)),
+      Value::String(string) => self.out.push_str(&format!("{:?}", string)),
+      Value::Bytes(bytes) => self.out.push_str(&bytes_text(bytes)),
+      Value::Int128(int) => self.out.push_str(&hex_text(int)),
+      Value::Int256(int) => self.out.push_str(&hex_text(int)),
+      Value::Vector(items) => {
+        self.out.push('[');
+        for (index, item) in items.iter().enumerate() {
+          if index > 0 {
+            self.out.push(' ');
+          }
+          self.value(item, true);
+        }
+        self.out.push(']');
+      }
+      Value::Object(object) => {
+        let parenthesized = nested || object.constructor.is_empty();
+        if parenthesized {
+          self.out.push('(');
+        }
+        self.object(object);
+        if parenthesized {
+          self.out.push(')');
+        }
+      }
+      Value::Unknown { id, raw } => {
+        let text = format!("#{:08x} {}", id, bytes_text(raw));
+        match nested {
+          true => self.out.push_str(&format!("({})", text)),
+          false => self.out.push_str(&text),
+        }
+      }
+    }
+  }
+
+  fn object(&mut self, object: &Object) {
+    let combinator = self
+      .schema
+      .constructor(&object.constructor)
+      .or_else(|| self.schema.function(&object.constructor));
+    let computed = combinator.map_or_else(HashSet::new, |combinator| {
+      computed_fields(&combinator.fields)
+    });
+    let mut first = true;
+    if !object.constructor.is_empty() {
+      self.out.push_str(&object.constructor);
+      first = false;
+    }
+    for (key, value) in &object.fields {
+      let field = combinator.and_then(|combinator| combinator.field(key));
+      let hidden = match value {
+        Value::Nat(_) => computed.contains(key.as_str()),
+        Value::Bool(false) => field.is_some_and(Field::is_flag),
+        _ => false,
+      };
+      if hidden {
+        continue;
+      }
+      if !first {
+        self.out.push(' ');
+      }
+      first = false;
+      self.out.push_str(key);
+      self.out.push(':');
+      self.value(value, true);
+    }
+  }
+}
+
+fn double_text(double: f64) -> String {
+  if double.is_nan() {
+    "nan".to_string()
+  } else if double.is_infinite() {
+    if double > 0.0 { "inf" } else { "-inf" }.to_string()
+  } else {
+    format!("{:?}", double)
+  }
+}
+
+fn bytes_text(bytes: &[u8]) -> String {
+  let mut text = String::from("b\"");
+  for &byte in bytes {
+    match byte {
+      b'"' => text.push_str("\\\""),
+      b'\\' => text.push_str("\\\\"),
+      b'\n' => text.push_str("\\n"),
+      b'\r' => text.push_str("\\r"),
+      b'\t' => text.push_str("\\t"),
+      0x20..=0x7e => text.push(char::from(byte)),
+      byte => text.push_str(&format!("\\x{:02x}", byte)),
+    }
+  }
+  text.push('"');
+  text
+}
+
+fn hex_text(bytes: &[u8]) -> String {
+  let digits = bytes
+    .iter()
+    .map(|byte| format!("{:02x}", byte))
+    .collect::<String>();
+  format!("0x{}", digits)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  /// A token of TL declarations: a name, a punctuation mark or an `#id`.
+  Tl(TLTokenEnum, String),
+  /// A number, possibly negative, fractional or hexadecimal.
+  Number(String),
+  String(String),
+  Bytes(Vec<u8>),
+  End,
+}
+
+impl fmt::Display for Token {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Token::Tl(_, text) | Token::Number(text) => write!(f, "`{}`", text),
+      Token::String(_) => write!(f, "a string"),
+      Token::Bytes(_) => write!(f, "bytes"),
+      Token::End => write!(f, "the end of the text"),
+    }
+  }
+}
+
+/// Splits `text` into tokens with their offsets. Names and punctuation
+/// are lexed as in TL declarations; literals, which declarations do not
+/// have, are read here.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, TextError> {
+  let mut tokens = vec![];
+  let mut offset = 0;
+  loop {
+    let rest = &text[offset..];
+    let trimmed = rest.trim_start();
+    offset += rest.len() - trimmed.len();
+    let rest = trimmed;
+    let mut chars = rest.chars();
+    let (token, length) = match (chars.next(), chars.next()) {
+      (None, _) => break,
+      (Some('"'), _) => {
+        let (bytes, length) = quoted(text, offset + 1, false)?;
+        let string = String::from_utf8(bytes)
+          .map_err(|_| position(text, offset, "string is not valid UTF-8".to_string()))?;
+        (Token::String(string), length + 1)
+      }
+      (Some('b'), Some('"')) => {
+        let (bytes, length) = quoted(text, offset + 2, true)?;
+        (Token::Bytes(bytes), length + 2)
+      }
+      (Some(first), _) if first.is_ascii_digit() || first == '-' => {
+        let mut previous = first;
+        let length = rest
+          .char_indices()
+          .skip(1)
+          .find(|&(_, c)| {
+            let exponent = (c == '+' || c == '-') && (previous == 'e' || previous == 'E');
+            previous = c;
+            !(c.is_ascii_alphanumeric() || c == '.' || c == '_' || exponent)
+          })
+          .map_or(rest.len(), |(index, _)| index);
+        (Token::Number(rest[..length].to_string()), length)
+      }
+      _ => {
+        let lexer = TLTokenEnum::lexer(rest);
+        let range = lexer.range();
+        match lexer.token {
+          TLTokenEnum::END => break,
+          TLTokenEnum::ERROR => {
+            let found = rest[range.start..].chars().next().unwrap_or(' ');
+            return Err(position(
+              text,
+              offset + range.start,
+              format!("unexpected character `{}`", found),
+            ));
+          }
+          TLTokenEnum::COMMENT => {
+            offset += range.end;
+            continue;
+          }
+          token => (Token::Tl(token, lexer.slice().to_string()), range.end),
+        }
+      }
+    };
+    tokens.push((token, offset));
+    offset += length;
+  }
+  tokens.push((Token::End, text.len()));
+  Ok(tokens)
+}
+
+/// Reads a quoted string or bytes literal whose contents start at
+/// `start`, returning its bytes and its length up to the closing quote
+/// included. Escapes are those of Rust literals; `\xNN` is allowed in
+/// bytes only.
+fn quoted(text: &str, start: usize, bytes: bool) -> Result<(Vec<u8>, usize), TextError> {
+  let mut out = vec![];
+  let mut chars = text[start..].char_indices();
+  while let Some((index, c)) = chars.next() {
+    let escaped = match c {
+      '"' => return Ok((out, index + 1)),
+      '\\' => chars.next().map(|(_, c)| c),
+      c => {
+        let mut buffer = [0; 4];
+        out.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+        continue;
+      }
+    };
+    let error = || position(text, start + index, "invalid escape".to_string());
+    let c = match escaped {
+      Some('n') => '\n',
+      Some('r') => '\r',
+      Some('t') => '\t',
+      Some('0') => '\0',
+      Some(c @ '\\') | Some(c @ '"') | Some(c @ '\'') => c,
+      Some('x') if bytes => {
+        let digits = chars.by_ref().take(2).map(|(_, c)| c).collect::<String>();
+        let byte = u8::from_str_radix(&digits, 16).map_err(|_| error())?;
+        out.push(byte);
+        continue;
+      }
+      Some('u') => {
+        if chars.next().map(|(_, c)| c) != Some('{') {
+          return Err(error());
+        }
+        let digits = chars
+          .by_ref()
+          .map(|(_, c)| c)
+          .take_while(|&c| c != '}')
+          .collect::<String>();
+        u32::from_str_radix(&digits, 16)
+          .ok()
+          .and_then(std::char::from_u32)
+          .ok_or_else(error)?
+      }
+      _ => return Err(error()),
+    };
+    let mut buffer = [0; 4];
+    out.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+  }
+  Err(position(
+    text,
+    start.saturating_sub(1),
+    "unterminated string".to_string(),
+  ))
+}
+
+fn position(text: &str, offset: usize, message: String) -> TextError {
+  let before = &text[..offset.min(text.len())];
+  let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+  TextError {
+    line: before.matches('\n').count() + 1,
+    column: before[line_start..].chars().count() + 1,
+    message,
+  }
+}
+
+struct Parser<'a, 't> {
+  schema: &'a Schema,
+  codecs: Codecs,
+  text: &'t str,
+  tokens: Vec<(Token, usize)>,
+  next: usize,
+  /// Whether the value read is the whole text, so that an object needs
+  /// no parentheses around its fields.
+  top: bool,
+}
+
+impl<'a, 't> Parser<'a, 't> {
+  fn new(schema: &'a Schema, text: &'t str) -> Result<Parser<'a, 't>, TextError> {
+    Ok(Parser {
+      schema,
+      codecs: Codecs::default(),
+      text,
+      tokens: tokenize(text)?,
+      next: 0,
+      top: false,
+    })
+  }
+
+  fn finish(&self) -> Result<(), TextError> {
+    match self.peek() {
+      Token::End => Ok(()),
+      token => Err(self.error(format!("unexpected {} after the value", token))),
+    }
+  }
+
+  fn peek(&self) -> &Token {
+    &self.tokens[self.next].0
+  }
+
+  fn peek_tl(&self) -> Option<TLTokenEnum> {
+    match self.peek() {
+      Token::Tl(token, _) => Some(*token),
+      _ => None,
+    }
+  }
+
+  fn advance(&mut self) -> Token {
+    let token = self.tokens[self.next].0.clone();
+    if token != Token::End {
+      self.next += 1;
+    }
+    token
+  }
+
+  /// Error at the next token.
+  fn error(&self, message: String) -> TextError {
+    position(self.text, self.tokens[self.next].1, message)
+  }
+
+  fn expect(&mut self, expected: TLTokenEnum, what: &str) -> Result<(), TextError> {
+    if self.peek_tl() == Some(expected) {
+      self.advance();
+      return Ok(());
+    }
+    Err(self.error(format!("expected {}, found {}", what, self.peek())))
+  }
+
+  /// Takes the value being read off the top: whatever it contains is
+  /// nested.
+  fn nested(&mut self) -> bool {
+    std::mem::replace(&mut self.top, false)
+  }
+
+  fn value(&mut self, ty: &TLType) -> Result<Value<'static>, TextError> {
+    match shape(self.schema, &self.codecs, ty) {
+      Shape::Nat => {
+        self.nested();
+        Ok(Value::Nat(self.number("#")?))
+      }
+      Shape::Builtin(name) => self.literal(name),
+      Shape::Vector(params) => self.vector(params),
+      Shape::Object(expected, params) => {
+        let top = self.nested();
+        self.object(expected, params, top)
+      }
+      Shape::UnknownType(name) => Err(self.error(format!("unknown type `{}`", name))),
+      Shape::NoValues(ty) => Err(self.error(format!("values of type `{}` have no text form", ty))),
+    }
+  }
+
+  fn vector(&mut self, params: &[TLType]) -> Result<Value<'static>, TextError> {
+    self.nested();
+    let item_type = match params {
+      [item_type] => item_type,
+      _ => return Err(self.error("`Vector` needs an item type".to_string())),
+    };
+    self.expect(TLTokenEnum::OPSBR, "`[`")?;
+    let mut items = vec![];
+    while self.peek_tl() != Some(TLTokenEnum::CLSBR) {
+      if *self.peek() == Token::End {
+        return Err(self.error("expected `]`".to_string()));
+      }
+      items.push(self.value(item_type)?);
+    }
+    self.advance();
+    Ok(Value::Vector(items))
+  }
+
+  /// Reads an object built by one of the `expected` constructors. Its
+  /// fields are in parentheses unless it is the whole text; one without
+  /// fields needs none.
+  fn object(
+    &mut self,
+    expected: Expected,
+    params: &[TLType],
+    top: bool,
+  ) -> Result<Value<'static>, TextError> {
+    if self.peek_tl() == Some(TLTokenEnum::OPBR) {
+      self.advance();
+      let value = self.object_fields(expected, params, true)?;
+      self.expect(TLTokenEnum::CLBR, "`)`")?;
+      return Ok(value);
+    }
+    self.object_fields(expected, params, top)
+  }
+
+  /// Reads the constructor of an object and, if `fields`, its fields.
+  fn object_fields(
+    &mut self,
+    expected: Expected,
+    params: &[TLType],
+    fields: bool,
+  ) -> Result<Value<'static>, TextError> {
+    if let Token::Tl(TLTokenEnum::HEXNUMBER, id) = self.peek() {
+      let id = u32::from_str_radix(&id[1..], 16)
+        .map_err(|_| self.error(format!("invalid constructor ID `{}`", id)))?;
+      self.advance();
+      let raw = match self.advance() {
+        Token::Bytes(raw) => raw,
+        _ => return Err(self.error("expected the bytes of an unknown object".to_string())),
+      };
+      return Ok(Value::Unknown {
+        id,
+        raw: Cow::Owned(raw),
+      });
+    }
+
+    let at = self.tokens[self.next].1;
+    let name = self.name()?;
+    let combinator = self.combinator(&name, expected, at)?;
+    if let Token::Tl(TLTokenEnum::HEXNUMBER, id) = self.peek() {
+      if u32::from_str_radix(&id[1..], 16) != Ok(combinator.id) {
+        return Err(self.error(format!(
+          "`{}` is not the ID of `{}`, #{:08x}",
+          id, name, combinator.id
+        )));
+      }
+      self.advance();
+    }
+    let given = match fields {
+      true => {
+        let scope = Scope::for_combinator(combinator, params);
+        self.fields(&combinator.name, &combinator.fields, scope)?
+      }
+      false => vec![],
+    };
+    let fields = self
+      .complete(&combinator.name, &combinator.fields, given, at)
+      .map_err(|err| match fields {
+        true => err,
+        false => position(
+          self.text,
+          at,
+          format!("`{}` has fields: write it in parentheses", name),
+        ),
+      })?;
+    Ok(Value::Object(Object {
+      constructor: combinator.name.clone(),
+      fields,
+    }))
+  }
+
+  /// A possibly namespaced constructor or function name.
+  fn name(&mut self) -> Result<String, TextError> {
+    let mut name = match self.peek() {
+      Token::Tl(TLTokenEnum::LCIDENT, name) | Token::Tl(TLTokenEnum::UCIDENT, name) => name.clone(),
+      token => return Err(self.error(format!("expected a constructor, found {}", token))),
+    };
+    self.advance();
+    while self.peek_tl() == Some(TLTokenEnum::STOP) {
+      self.advance();
+      match self.advance() {
+        Token::Tl(TLTokenEnum::LCIDENT, part) | Token::Tl(TLTokenEnum::UCIDENT, part) => {
+          name.push('.');
+          name.push_str(&part);
+        }
+        token => return Err(self.error(format!("expected a name after `.`, found {}", token))),
+      }
+    }
+    Ok(name)
+  }
+
+  /// The combinator `name`, read at `at`, if it is one of `expected`.
+  fn combinator(
+    &self,
+    name: &str,
+    expected: Expected,
+    at: usize,
+  ) -> Result<&'a Combinator, TextError> {
+    expected
+      .combinator(self.schema, name)
+      .map_err(|mismatch| match mismatch {
+        Mismatch::Unknown => position(self.text, at, format!("unknown constructor `{}`", name)),
+        Mismatch::Unexpected(message) => position(self.text, at, message),
+      })
+  }
+
+  /// Reads `key:value` pairs of `fields` for as long as they follow.
+  fn fields(
+    &mut self,
+    name: &str,
+    fields: &[Field],
+    mut scope: Scope,
+  ) -> Result<Vec<(String, Value<'static>)>, TextError> {
+    let mut values: Vec<(String, Value<'static>)> = vec![];
+    while let Some(key) = self.key() {
+      let field = match fields.iter().find(|field| field_key(field) == key) {
+        Some(field) => field,
+        None => return Err(self.error(format!("`{}` has no field `{}`", name, key))),
+      };
+      if values.iter().any(|(name, _)| *name == key) {
+        return Err(self.error(format!("field `{}` is given twice", key)));
+      }
+      self.advance();
+      self.advance();
+      let value = match &field.ty {
+        FieldType::Type(ty) => self.value(&scope.substitute(ty))?,
+        FieldType::Repeat(_, block) => self.records(name, block, &scope)?,
+      };
+      if let Value::Nat(nat) = value {
+        scope.bind(&key, TLType::Nat(nat));
+      }
+      values.push((key, value));
+    }
+    Ok(values)
+  }
+
+  /// The next token if it is a field key followed by `:`.
+  fn key(&self) -> Option<String> {
+    match (self.peek(), &self.tokens[self.next + 1..]) {
+      (Token::Tl(TLTokenEnum::LCIDENT, key), [(Token::Tl(TLTokenEnum::COLON, _), _), ..])
+      | (Token::Number(key), [(Token::Tl(TLTokenEnum::COLON, _), _), ..]) => Some(key.clone()),
+      _ => None,
+    }
+  }
+
+  /// Reads the records of a `[ ... ]` block: values for a block of a
+  /// single unnamed field, `(key:value ...)` otherwise.
+  fn records(
+    &mut self,
+    name: &str,
+    block: &[Field],
+    scope: &Scope,
+  ) -> Result<Value<'static>, TextError> {
+    self.expect(TLTokenEnum::OPSBR, "`[`")?;
+    let mut records = vec![];
+    while self.peek_tl() != Some(TLTokenEnum::CLSBR) {
+      if *self.peek() == Token::End {
+        return Err(self.error("expected `]`".to_string()));
+      }
+      if let Some(ty) = plain_item(block) {
+        records.push(self.value(&scope.substitute(ty))?);
+        continue;
+      }
+      let at = self.tokens[self.next].1;
+      self.expect(TLTokenEnum::OPBR, "`(`")?;
+      let fields = self.fields(name, block, scope.clone())?;
+      self.expect(TLTokenEnum::CLBR, "`)`")?;
+      records.push(Value::Object(Object {
+        constructor: String::new(),
+        fields: self.complete(name, block, fields, at)?,
+      }));
+    }
+    self.advance();
+    Ok(Value::Vector(records))
+  }
+
+  /// Puts the fields given in declaration order, failing if one that is
+  /// not computed on serializing is missing from the object at `at`.
+  fn complete(
+    &self,
+    name: &str,
+    fields: &[Field],
+    mut given: Vec<(String, Value<'static>)>,
+    at: usize,
+  ) -> Result<Vec<(String, Value<'static>)>, TextError> {
+    let computed = computed_fields(fields);
+    let mut values = vec![];
+    for field in fields {
+      let key = field_key(field);
+      match given.iter().position(|(name, _)| *name == key) {
+        Some(index) => values.push(given.remove(index)),
+        None if field.condition.is_some() || computed.contains(key.as_str()) => {}
+        None => {
+          return Err(position(
+            self.text,
+            at,
+            format!("`{}` is missing field `{}`", name, key),
+          ))
+        }
+      }
+    }
+    Ok(values)
+  }
+
+  fn number<T: std::str::FromStr>(&mut self, ty: &str) -> Result<T, TextError> {
+    let parsed = match self.peek() {
+      Token::Number(text) => text.parse().ok(),
+      _ => None,
+    };
+    match parsed {
+      Some(number) => {
+        self.advance();
+        Ok(number)
+      }
+      None => Err(self.error(format!("expected {}, found {}", ty, self.peek()))),
+    }
+  }
+
+  /// Reads a value of the builtin type `name`.
+  fn literal(&mut self, name: &str) -> Result<Value<'static>, TextError> {
+    self.nested();
+    let value = match name {
+      "int" => Value::Int(self.number("an int")?),
+      "long" => Value::Long(self.number("a long")?),
+      "double" => match self.peek() {
+        Token::Tl(TLTokenEnum::LCIDENT, word) if word == "inf" || word == "nan" => {
+          let double = word.parse().unwrap_or(f64::NAN);
+          self.advance();
+          Value::Double(double)
+        }
+        _ => Value::Double(self.number("a double")?),
+      },
+      "string" => match self.peek() {
+        Token::String(string) => Value::String(Cow::Owned(string.clone())),
+        token => return Err(self.error(format!("expected a string, found {}", token))),
+      },
+      "bytes" => match self.peek() {
+        Token::Bytes(bytes) => Value::Bytes(Cow::Owned(bytes.clone())),
+        Token::String(string) => Value::Bytes(Cow::Owned(string.clone().into_bytes())),
+        token => return Err(self.error(format!("expected bytes, found {}", token))),
+      },
+      "int128" => Value::Int128(self.hex("an int128")?),
+      "int256" => Value::Int256(self.hex("an int256")?),
+      "Bool" | "true" => match self.peek() {
+        Token::Tl(_, word) if word == "true" || word == "boolTrue" => Value::Bool(true),
+        Token::Tl(_, word) if word == "false" || word == "boolFalse" => Value::Bool(false),
+        token => return Err(self.error(format!("expected `true` or `false`, found {}", token))),
+      },
+      name => {
+        return Err(self.error(format!(
+          "values of the builtin type `{}` have no text form",
+          name
+        )))
+      }
+    };
+    if let Value::String(_) | Value::Bytes(_) | Value::Bool(_) = value {
+      self.advance();
+    }
+    Ok(value)
+  }
+
+  /// Reads `0x` and the bytes of an `int128` or `int256` in wire order.
+  fn hex<A: Default + AsMut<[u8]>>(&mut self, what: &str) -> Result<A, TextError> {
+    let mut array = A::default();
+    let bytes = match self.peek() {
+      Token::Number(text)
+        if text.starts_with("0x") && text.len() == 2 + 2 * array.as_mut().len() =>
+      {
+        (2..text.len())
+          .step_by(2)
+          .map(|index| u8::from_str_radix(&text[index..index + 2], 16))
+          .collect::<Result<Vec<_>, _>>()
+          .ok()
+      }
+      _ => None,
+    };
+    match bytes {
+      Some(bytes) => {
+        array.as_mut().copy_from_slice(&bytes);
+        self.advance();
+        Ok(array)
+      }
+      None => Err(self.error(format!(
+        "expected {} as `0x` and {} hex digits, found {}",
+        what,
+        2 * array.as_mut().len(),
+        self.peek()
+      ))),
+    }
+  }
+}
@@ -0,0 +1,540 @@
+use super::codec::Codecs;
+use super::ser::EncodeError;
+use super::value::field_key;
+use super::*;
+use crate::schema::{Combinator, Field, FieldType, Schema};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// What is wrong with a part of a value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+  UnknownConstructor(String),
+  UnknownFunction(String),
+  UnknownType(String),
+  /// The constructor does not build the expected type.
+  WrongConstructor {
+    expected: String,
+    found: String,
+  },
+  /// A primitive or vector value where another kind was expected.
+  WrongValue {
+    expected: String,
+    found: String,
+  },
+  MissingField(String),
+  UnknownField(String),
+  /// A conditional field given while the bit it depends on is clear in
+  /// flags of an enclosing combinator.
+  UnexpectedField {
+    field: String,
+    flags: String,
+    bit: u32,
+  },
+  /// An explicit flags value disagrees with the conditional fields given.
+  InconsistentFlags {
+    field: String,
+    value: u32,
+    expected: u32,
+  },
+  /// The count of a `[ ... ]` block does not evaluate to a number.
+  UnknownCount(String),
+  /// A `[ ... ]` block with a different number of records than its count.
+  WrongCount {
+    field: String,
+    expected: u32,
+    found: usize,
+  },
+  /// A value whose type differs from the one an implicit `{t:Type}`
+  /// parameter took from an earlier value.
+  InconsistentParam {
+    param: String,
+    expected: TLType,
+    found: TLType,
+  },
+  /// A string or bytes value longer than TL can encode.
+  TooLong(usize),
+  Unsupported(String),
+}
+
+impl fmt::Display for Problem {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Problem::UnknownConstructor(name) => write!(f, "unknown constructor `{}`", name),
+      Problem::UnknownFunction(name) => write!(f, "unknown function `{}`", name),
+      Problem::UnknownType(name) => write!(f, "unknown type `{}`", name),
+      Problem::WrongConstructor { expected, found } => write!(
+        f,
+        "constructor `{}` does not build type `{}`",
+        found, expected
+      ),
+      Problem::WrongValue { expected, found } => write!(
+        f,
+        "expected a value of type `{}`, found {}",
+        expected, found
+      ),
+      Problem::MissingField(field) => write!(f, "missing field `{}`", field),
+      Problem::UnknownField(field) => write!(f, "unknown field `{}`", field),
+      Problem::UnexpectedField { field, flags, bit } => write!(
+        f,
+        "field `{}` is given but bit {} of `{}` is clear",
+        field, bit, flags
+      ),
+      Problem::InconsistentFlags {
+        field,
+        value,
+        expected,
+      } => write!(
+        f,
+        "`{}` is {:#x} but the fields present need {:#x}",
+        field, value, expected
+      ),
+      Problem::UnknownCount(field) => {
+        write!(f, "the number of records in `{}` is unknown", field)
+      }
+      Problem::WrongCount {
+        field,
+        expected,
+        found,
+      } => write!(
+        f,
+        "`{}` has {} record(s) but its count is {}",
+        field, found, expected
+      ),
+      Problem::InconsistentParam {
+        param,
+        expected,
+        found,
+      } => write!(
+        f,
+        "`{}` is `{}` elsewhere but this value is `{}`",
+        param, expected, found
+      ),
+      Problem::TooLong(length) => write!(f, "{} bytes is too long for a TL string", length),
+      Problem::Unsupported(what) => write!(f, "{} is not supported", what),
+    }
+  }
+}
+
+/// A problem with the part of a value at `path`, such as
+/// `$.chats[3].photo`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+  pub path: String,
+  pub problem: Problem,
+}
+
+impl fmt::Display for ValidationError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}: {}", self.path, self.problem)
+  }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Checks dynamic values against a schema, finding every problem rather
+/// than stopping at the first. Only builtin values are encoded, with their
+/// codecs into a scratch buffer.
+pub struct Validator<'a> {
+  schema: &'a Schema,
+  codecs: Cow<'a, Codecs>,
+  errors: Vec<ValidationError>,
+}
+
+impl<'a> Validator<'a> {
+  pub fn new(schema: &'a Schema) -> Validator<'a> {
+    Validator {
+      schema,
+      codecs: Cow::Owned(Codecs::default()),
+      errors: vec![],
+    }
+  }
+
+  /// Validator checking builtin values with `codecs`.
+  pub fn with_codecs(schema: &'a Schema, codecs: &'a Codecs) -> Validator<'a> {
+    Validator {
+      schema,
+      codecs: Cow::Borrowed(codecs),
+      errors: vec![],
+    }
+  }
+
+  /// Problems with `value` as a boxed object or function call, as
+  /// `Serializer::write_object` takes it.
+  pub fn validate_object(&mut self, value: &Value) -> Vec<ValidationError> {
+    self.object(Expected::Any, &[], value, "$");
+    std::mem::take(&mut self.errors)
+  }
+
+  /// Problems with `value` as an instance of `ty`.
+  pub fn validate(&mut self, ty: &TLType, value: &Value) -> Vec<ValidationError> {
+    self.value(ty, value, "$");
+    std::mem::take(&mut self.errors)
+  }
+
+  fn report(&mut self, path: &str, problem: Problem) {
+    self.errors.push(ValidationError {
+      path: path.to_string(),
+      problem,
+    });
+  }
+
+  fn wrong_value(&mut self, path: &str, expected: &str, value: &Value) {
+    self.report(
+      path,
+      Problem::WrongValue {
+        expected: expected.to_string(),
+        found: value.kind(),
+      },
+    );
+  }
+
+  fn value(&mut self, ty: &TLType, value: &Value, path: &str) {
+    match shape(self.schema, &self.codecs, ty) {
+      Shape::Nat => {
+        if nat_value(value).is_none() {
+          self.wrong_value(path, "#", value);
+        }
+      }
+      Shape::Builtin(name) => self.builtin(name, value, path),
+      Shape::Vector(params) => self.vector(params, value, path),
+      Shape::Object(expected, params) => self.object(expected, params, value, path),
+      Shape::UnknownType(name) => self.report(path, Problem::UnknownType(name.to_string())),
+      Shape::NoValues(ty) => self.report(
+        path,
+        Problem::Unsupported(format!("a value of type `{}`", ty)),
+      ),
+    }
+  }
+
+  /// Checks an object built by one of the `expected` constructors.
+  fn object(&mut self, expected: Expected, params: &[TLType], value: &Value, path: &str) {
+    let object = match value {
+      Value::Object(object) => object,
+      Value::Unknown { .. } => return,
+      value => return self.wrong_value(path, &expected.to_string(), value),
+    };
+    let found = object.constructor.clone();
+    let problem = match expected.combinator(self.schema, &object.constructor) {
+      Ok(combinator) => return self.fields(combinator, params, object, path),
+      Err(_) if expected == Expected::Function => Problem::UnknownFunction(found),
+      Err(Mismatch::Unknown) => Problem::UnknownConstructor(found),
+      Err(Mismatch::Unexpected(_)) => Problem::WrongConstructor {
+        expected: expected.to_string(),
+        found,
+      },
+    };
+    self.report(path, problem)
+  }
+
+  fn fields(&mut self, combinator: &Combinator, params: &[TLType], object: &Object, path: &str) {
+    let mut scope = Scope::for_combinator(combinator, params);
+    // implicit parameters that neither the result type nor the value
+    // determines take the type of the first value given for them
+    let mut inferred = Params::default();
+    for param in &combinator.params {
+      let name = TLType::Named(param.name.clone(), vec![]);
+      if scope.substitute(&name) == name {
+        inferred.free.insert(param.name.clone());
+      }
+    }
+    self.block(&combinator.fields, object, &mut scope, &mut inferred, path)
+  }
+
+  /// Checks the fields of a combinator or of one record of one of its
+  /// `[ ... ]` blocks.
+  fn block(
+    &mut self,
+    fields: &[Field],
+    object: &Object,
+    scope: &mut Scope,
+    inferred: &mut Params,
+    path: &str,
+  ) {
+    for (key, _) in &object.fields {
+      if !fields.iter().any(|field| &field_key(field) == key) {
+        self.report(path, Problem::UnknownField(key.clone()));
+      }
+    }
+
+    let flags = computed_flags(fields, object);
+    let counts = computed_counts(fields, object);
+    let mut unknown_flags: Vec<&str> = vec![];
+    for field in fields {
+      let key = field_key(field);
+      let field_path = format!("{}.{}", path, key);
+      let value = object.get(&key);
+      if let Some(condition) = &field.condition {
+        let given = match value {
+          Some(Value::Bool(set)) if field.is_flag() => *set,
+          value => value.is_some(),
+        };
+        // without its flags a field is checked if it is there
+        let set = match scope.nat(&condition.field) {
+          Some(bits) => bits & (1 << condition.bit) != 0,
+          None => {
+            if !unknown_flags.contains(&condition.field.as_str()) {
+              unknown_flags.push(&condition.field);
+              self.report(path, Problem::MissingField(condition.field.clone()));
+            }
+            given
+          }
+        };
+        if given && !set {
+          self.report(
+            &field_path,
+            Problem::UnexpectedField {
+              field: key,
+              flags: condition.field.clone(),
+              bit: condition.bit,
+            },
+          );
+          continue;
+        }
+        if !set {
+          continue;
+        }
+        if field.is_flag() {
+          if let Some(value) = value {
+            self.value(
+              &TLType::Named("true".to_string(), vec![]),
+              value,
+              &field_path,
+            );
+          }
+          continue;
+        }
+      }
+      if let Some(&(mask, bits)) = flags.get(&key) {
+        let nat = match value {
+          Some(value) => match nat_value(value) {
+            Some(nat) => nat,
+            None => {
+              self.wrong_value(&field_path, "#", value);
+              bits
+            }
+          },
+          None => bits,
+        };
+        if nat & mask != bits {
+          self.report(
+            &field_path,
+            Problem::InconsistentFlags {
+              field: key.clone(),
+              value: nat,
+              expected: bits,
+            },
+          );
+        }
+        // go on as the fields present have it rather than report them too
+        scope.bind(&key, TLType::Nat(nat & !mask | bits));
+        continue;
+      }
+      let value = match (value, counts.get(&key)) {
+        (Some(value), _) => value,
+        (None, Some(&count)) => {
+          scope.bind(&key, TLType::Nat(count));
+          continue;
+        }
+        (None, None) => {
+          self.report(path, Problem::MissingField(key));
+          continue;
+        }
+      };
+      match &field.ty {
+        FieldType::Type(ty) => {
+          if !self.infer(ty, value, scope, inferred, &field_path) {
+            continue;
+          }
+          let ty = scope.substitute(ty);
+          self.value(&ty, value, &field_path);
+          if ty == TLType::NatType {
+            if let Some(nat) = nat_value(value) {
+              scope.bind(&key, TLType::Nat(nat));
+            }
+          }
+        }
+        FieldType::Repeat(count, block) => {
+          let items = match value {
+            Value::Vector(items) => items,
+            value => {
+              self.wrong_value(&field_path, "vector", value);
+              continue;
+            }
+          };
+          if let Count::Expr(TLType::Named(name, params)) = count {
+            if params.is_empty() && inferred.free.remove(name) {
+              scope.bind(name, TLType::Nat(items.len() as u32));
+            }
+          }
+          let count = match scope.count(count) {
+            Some(count) => count,
+            None => {
+              self.report(&field_path, Problem::UnknownCount(key));
+              continue;
+            }
+          };
+          if items.len() != count as usize {
+            self.report(
+              &field_path,
+              Problem::WrongCount {
+                field: key,
+                expected: count,
+                found: items.len(),
+              },
+            );
+            continue;
+          }
+          for (index, item) in items.iter().enumerate() {
+            let item_path = format!("{}[{}]", field_path, index);
+            match (plain_item(block), item) {
+              (Some(ty), item) => {
+                if self.infer(ty, item, scope, inferred, &item_path) {
+                  self.value(&scope.substitute(ty), item, &item_path);
+                }
+              }
+              (None, Value::Object(record)) => {
+                let mut scope = scope.clone();
+                self.block(block, record, &mut scope, inferred, &item_path)
+              }
+              (None, item) => self.wrong_value(&item_path, "record", item),
+            }
+          }
+        }
+      }
+    }
+  }
+
+  /// Binds the free parameters `ty` is made of from the type of `value`,
+  /// reporting a value whose type differs from what a parameter is bound
+  /// to. Returns whether `value` is worth checking against `ty`.
+  fn infer(
+    &mut self,
+    ty: &TLType,
+    value: &Value,
+    scope: &mut Scope,
+    inferred: &mut Params,
+    path: &str,
+  ) -> bool {
+    match (ty, value) {
+      (TLType::Named(name, params), value) if params.is_empty() && inferred.has(name) => {
+        let found = match self.type_of(value) {
+          Some(found) => found,
+          None => return !inferred.free.contains(name),
+        };
+        if inferred.free.remove(name) {
+          inferred.bound.insert(name.clone(), found.clone());
+        }
+        let expected = inferred.bound[name].clone();
+        // records have scopes of their own
+        scope.bind(name, expected.clone());
+        if expected != found {
+          let problem = Problem::InconsistentParam {
+            param: name.clone(),
+            expected,
+            found,
+          };
+          self.report(path, problem);
+          return false;
+        }
+        true
+      }
+      (TLType::Named(name, params), Value::Vector(items))
+        if name == "Vector" || name == "vector" =>
+      {
+        match params.as_slice() {
+          [item_type] => items.iter().enumerate().all(|(index, item)| {
+            let path = format!("{}[{}]", path, index);
+            self.infer(item_type, item, scope, inferred, &path)
+          }),
+          _ => true,
+        }
+      }
+      (TLType::Bare(inner), value) => self.infer(inner, value, scope, inferred, path),
+      _ => true,
+    }
+  }
+
+  /// Type of a value whose type is evident from the value alone.
+  fn type_of(&self, value: &Value) -> Option<TLType> {
+    let name = match value {
+      Value::Nat(_) => return Some(TLType::NatType),
+      Value::Bool(_) => "Bool",
+      Value::Int(_) => "int",
+      Value::Long(_) => "long",
+      Value::Double(_) => "double",
+      Value::String(_) => "string",
+      Value::Bytes(_) => "bytes",
+      Value::Int128(_) => "int128",
+      Value::Int256(_) => "int256",
+      Value::Object(object) => match self.schema.constructor(&object.constructor) {
+        Some(constructor) if constructor.result_type.params().is_empty() => constructor.type_name(),
+        _ => return None,
+      },
+      Value::Vector(_) | Value::Unknown { .. } => return None,
+    };
+    Some(TLType::Named(name.to_string(), vec![]))
+  }
+
+  fn vector(&mut self, params: &[TLType], value: &Value, path: &str) {
+    let items = match value {
+      Value::Vector(items) => items,
+      value => return self.wrong_value(path, "vector", value),
+    };
+    let item_type = match params {
+      [item_type] => item_type,
+      _ => return self.report(path, Problem::UnknownType("Vector".to_string())),
+    };
+    for (index, item) in items.iter().enumerate() {
+      self.value(item_type, item, &format!("{}[{}]", path, index));
+    }
+  }
+
+  /// Checks a builtin value by encoding it with its codec.
+  fn builtin(&mut self, name: &str, value: &Value, path: &str) {
+    let codec = match self.codecs.get(name) {
+      Some(codec) => codec,
+      None => {
+        return self.report(
+          path,
+          Problem::Unsupported(format!("the builtin type `{}` without a codec", name)),
+        )
+      }
+    };
+    let problem = match codec.encode(value, &mut vec![]) {
+      Ok(()) => return,
+      Err(EncodeError::WrongValue { expected, found }) => Problem::WrongValue { expected, found },
+      Err(EncodeError::TooLong(length)) => Problem::TooLong(length),
+      Err(err) => Problem::Unsupported(err.to_string()),
+    };
+    self.report(path, problem)
+  }
+}
+
+/// Implicit parameters of a combinator taken from the values of its
+/// fields.
+#[derive(Default)]
+struct Params {
+  /// Not bound yet.
+  free: HashSet<String>,
+  /// Bound to the type of a value.
+  bound: HashMap<String, TLType>,
+}
+
+impl Params {
+  fn has(&self, name: &str) -> bool {
+    self.free.contains(name) || self.bound.contains_key(name)
+  }
+}
+
+/// Problems with a boxed object or function call.
+pub fn validate(schema: &Schema, value: &Value) -> Vec<ValidationError> {
+  Validator::new(schema).validate_object(value)
+}
+
+/// Problems with `value` as an instance of `ty`, e.g. `Vector User` or
+/// `%User`.
+pub fn validate_as(schema: &Schema, ty: &TLType, value: &Value) -> Vec<ValidationError> {
+  Validator::new(schema).validate(ty, value)
+}
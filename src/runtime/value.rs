@@ -0,0 +1,133 @@
+use crate::schema::Field;
+use std::borrow::Cow;
+
+/// Dynamically typed TL value. Strings and bytes decoded from a buffer
+/// borrow from it for `'d`; `into_owned` detaches the value from the
+/// buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'d> {
+  /// `#`
+  Nat(u32),
+  Bool(bool),
+  Int(i32),
+  Long(i64),
+  Double(f64),
+  String(Cow<'d, str>),
+  Bytes(Cow<'d, [u8]>),
+  Int128([u8; 16]),
+  Int256([u8; 32]),
+  Vector(Vec<Value<'d>>),
+  Object(Object<'d>),
+  /// An object whose constructor ID the schema does not know, kept as the
+  /// bytes that followed the ID so that it is written back unchanged.
+  Unknown {
+    id: u32,
+    raw: Cow<'d, [u8]>,
+  },
+}
+
+impl<'d> Value<'d> {
+  /// Short description of the value used in error messages.
+  pub fn kind(&self) -> String {
+    match self {
+      Value::Nat(_) => "#".to_string(),
+      Value::Bool(_) => "Bool".to_string(),
+      Value::Int(_) => "int".to_string(),
+      Value::Long(_) => "long".to_string(),
+      Value::Double(_) => "double".to_string(),
+      Value::String(_) => "string".to_string(),
+      Value::Bytes(_) => "bytes".to_string(),
+      Value::Int128(_) => "int128".to_string(),
+      Value::Int256(_) => "int256".to_string(),
+      Value::Vector(_) => "vector".to_string(),
+      Value::Object(object) => object.constructor.clone(),
+      Value::Unknown { id, .. } => format!("unknown #{:08x}", id),
+    }
+  }
+
+  /// Copies every borrowed string and bytes value.
+  pub fn into_owned(self) -> Value<'static> {
+    match self {
+      Value::Nat(nat) => Value::Nat(nat),
+      Value::Bool(value) => Value::Bool(value),
+      Value::Int(int) => Value::Int(int),
+      Value::Long(long) => Value::Long(long),
+      Value::Double(double) => Value::Double(double),
+      Value::String(string) => Value::String(Cow::Owned(string.into_owned())),
+      Value::Bytes(bytes) => Value::Bytes(Cow::Owned(bytes.into_owned())),
+      Value::Int128(int) => Value::Int128(int),
+      Value::Int256(int) => Value::Int256(int),
+      Value::Vector(items) => Value::Vector(items.into_iter().map(Value::into_owned).collect()),
+      Value::Object(object) => Value::Object(object.into_owned()),
+      Value::Unknown { id, raw } => Value::Unknown {
+        id,
+        raw: Cow::Owned(raw.into_owned()),
+      },
+    }
+  }
+}
+
+/// An object built by a constructor or a function call, with its fields in
+/// declaration order. The records of `[ ... ]` blocks are objects with an
+/// empty constructor name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object<'d> {
+  pub constructor: String,
+  pub fields: Vec<(String, Value<'d>)>,
+}
+
+impl<'d> Object<'d> {
+  pub fn new(constructor: &str) -> Object<'d> {
+    Object {
+      constructor: constructor.to_string(),
+      fields: vec![],
+    }
+  }
+
+  pub fn with(mut self, field: &str, value: Value<'d>) -> Object<'d> {
+    self.set(field, value);
+    self
+  }
+
+  pub fn get(&self, field: &str) -> Option<&Value<'d>> {
+    self
+      .fields
+      .iter()
+      .find(|(name, _)| name == field)
+      .map(|(_, value)| value)
+  }
+
+  /// Replaces the value of `field` or appends it.
+  pub fn set(&mut self, field: &str, value: Value<'d>) {
+    match self.fields.iter_mut().find(|(name, _)| name == field) {
+      Some(entry) => entry.1 = value,
+      None => self.fields.push((field.to_string(), value)),
+    }
+  }
+
+  pub fn into_owned(self) -> Object<'static> {
+    Object {
+      constructor: self.constructor,
+      fields: self
+        .fields
+        .into_iter()
+        .map(|(name, value)| (name, value.into_owned()))
+        .collect(),
+    }
+  }
+}
+
+impl<'d> From<Object<'d>> for Value<'d> {
+  fn from(object: Object<'d>) -> Value<'d> {
+    Value::Object(object)
+  }
+}
+
+/// Key of a field in `Object::fields`: its name, or its position for
+/// unnamed fields.
+pub fn field_key(field: &Field) -> String {
+  match &field.name {
+    Some(name) => name.clone(),
+    None => field.position.to_string(),
+  }
+}
@@ -0,0 +1,419 @@
+use super::ast::*;
+use super::checks::args::uncounted;
+use super::checks::{check, resolve_combinators, BlockKind, Diagnostic, ImplicitParam};
+use super::types::{Bindings, TLType};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// `flags.N?` in front of a field type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+  /// Name of the `#` field holding the flags.
+  pub field: String,
+  pub bit: Nat,
+}
+
+/// Number of repetitions of a `[ ... ]` block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Count {
+  /// An expression over implicit parameters and named `#` fields.
+  Expr(TLType),
+  /// The value of the unnamed `#` field at this position, as in
+  /// `vector {t:Type} # [ t ] = Vector t`.
+  Field(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldType {
+  Type(TLType),
+  Repeat(Count, Vec<Field>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+  pub name: Option<String>,
+  pub ty: FieldType,
+  pub condition: Option<Condition>,
+  /// Index of the field among the explicit arguments of its combinator or
+  /// of its `[ ... ]` block.
+  pub position: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Combinator {
+  pub name: String,
+  pub id: Nat,
+  pub kind: BlockKind,
+  pub builtin: bool,
+  pub params: Vec<ImplicitParam>,
+  pub fields: Vec<Field>,
+  pub result_type: TLType,
+}
+
+impl Combinator {
+  /// Name of the boxed type the combinator belongs to, or of the type a
+  /// function returns.
+  pub fn type_name(&self) -> &str {
+    self.result_type.name().unwrap_or("")
+  }
+
+  pub fn field(&self, name: &str) -> Option<&Field> {
+    self
+      .fields
+      .iter()
+      .find(|field| field.name.as_deref() == Some(name))
+  }
+
+  /// Binds the implicit parameters and `#` fields the result type names so
+  /// that it becomes `ty`, e.g. `t` to `User` for `vector {t:Type} # [ t ]
+  /// = Vector t` and `Vector User`. None if the combinator does not build
+  /// `ty`.
+  pub fn bind_result(&self, ty: &TLType) -> Option<Bindings> {
+    let mut variables = self
+      .params
+      .iter()
+      .map(|param| param.name.as_str())
+      .collect::<HashSet<_>>();
+    variables.extend(self.fields.iter().filter_map(|field| field.name.as_deref()));
+    let mut bindings = Bindings::new();
+    match self.result_type.unify(ty, &variables, &mut bindings) {
+      true => Some(bindings),
+      false => None,
+    }
+  }
+
+  /// The combinator with `bindings` substituted through its fields and
+  /// result type. The implicit parameters bound are removed.
+  pub fn instantiate(&self, bindings: &Bindings) -> Combinator {
+    Combinator {
+      name: self.name.clone(),
+      id: self.id,
+      kind: self.kind,
+      builtin: self.builtin,
+      params: self
+        .params
+        .iter()
+        .filter(|param| !bindings.contains_key(&param.name))
+        .cloned()
+        .collect(),
+      fields: self
+        .fields
+        .iter()
+        .map(|field| field.substitute(bindings))
+        .collect(),
+      result_type: self.result_type.substitute(bindings),
+    }
+  }
+}
+
+impl Field {
+  /// Whether the field is a `flags.N?true` bit, set or cleared as a
+  /// boolean.
+  pub fn is_flag(&self) -> bool {
+    match &self.ty {
+      FieldType::Type(TLType::Named(name, params)) => {
+        self.condition.is_some() && name == "true" && params.is_empty()
+      }
+      _ => false,
+    }
+  }
+}
+
+impl Field {
+  /// The field with `bindings` substituted through its type, or through
+  /// the count and fields of a `[ ... ]` block.
+  pub fn substitute(&self, bindings: &Bindings) -> Field {
+    let ty = match &self.ty {
+      FieldType::Type(ty) => FieldType::Type(ty.substitute(bindings)),
+      FieldType::Repeat(count, block) => {
+        let count = match count {
+          Count::Expr(expr) => Count::Expr(expr.substitute(bindings)),
+          Count::Field(position) => Count::Field(*position),
+        };
+        let block = block
+          .iter()
+          .map(|field| field.substitute(bindings))
+          .collect();
+        FieldType::Repeat(count, block)
+      }
+    };
+    Field { ty, ..self.clone() }
+  }
+}
+
+/// Why a polymorphic type could not be instantiated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstantiateError {
+  UnknownType(String),
+  /// A type applied to a different number of parameters than it takes.
+  WrongArity {
+    ty: String,
+    expected: usize,
+    found: usize,
+  },
+  /// A type where a natural is expected or the other way around, as in
+  /// `Tuple int User`.
+  WrongKind {
+    ty: String,
+    param: String,
+  },
+  /// A type none of whose constructors builds the instance asked for.
+  NoConstructor(String),
+}
+
+impl fmt::Display for InstantiateError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      InstantiateError::UnknownType(name) => write!(f, "unknown type `{}`", name),
+      InstantiateError::WrongArity {
+        ty,
+        expected,
+        found,
+      } => write!(
+        f,
+        "`{}` takes {} parameter(s) but is given {}",
+        ty, expected, found
+      ),
+      InstantiateError::WrongKind { ty, param } => {
+        write!(f, "`{}` gives `{}` a value of the wrong kind", ty, param)
+      }
+      InstantiateError::NoConstructor(ty) => write!(f, "no constructor builds `{}`", ty),
+    }
+  }
+}
+
+impl std::error::Error for InstantiateError {}
+
+fn var_name(name: &Option<TLVarName>) -> Option<String> {
+  name.as_ref().map(|TLVarName::Name(name)| name.clone())
+}
+
+/// Fields of a combinator, or None if it has a `[ ... ]` block with
+/// nothing to count it.
+fn resolve_fields(args: &[TLArg]) -> Option<Vec<Field>> {
+  let mut fields: Vec<Field> = vec![];
+  // `[ ... ]` without `n*` repeats as many times as the closest `#` before it
+  let mut last_nat = None;
+  for arg in args {
+    let position = fields.len();
+    let field = match arg {
+      TLArg::OptArg(TLVarName::Name(name), expr) => {
+        if let TLType::NatType = TLType::from_expression(expr) {
+          last_nat = Some(Count::Expr(TLType::Named(name.clone(), vec![])));
+        }
+        continue;
+      }
+      TLArg::Arg(name, expr) => {
+        let ty = TLType::from_expression(expr);
+        if let TLType::NatType = ty {
+          last_nat = Some(match var_name(name) {
+            Some(name) => Count::Expr(TLType::Named(name, vec![])),
+            None => Count::Field(position),
+          });
+        }
+        Field {
+          name: var_name(name),
+          ty: FieldType::Type(ty),
+          condition: None,
+          position,
+        }
+      }
+      TLArg::ConditionalArg(name, TLCondition::Condition(TLVarName::Name(var), bit), expr) => {
+        Field {
+          name: var_name(name),
+          ty: FieldType::Type(TLType::from_expression(expr)),
+          condition: Some(Condition {
+            field: var.clone(),
+            bit: *bit,
+          }),
+          position,
+        }
+      }
+      TLArg::MultiplicityArg(name, count, args) => {
+        let count = match count {
+          Some(count) => Count::Expr(TLType::from_expression(count)),
+          None => last_nat.clone()?,
+        };
+        Field {
+          name: var_name(name),
+          ty: FieldType::Repeat(count, resolve_fields(args)?),
+          condition: None,
+          position,
+        }
+      }
+    };
+    fields.push(field);
+  }
+  Some(fields)
+}
+
+/// Schema resolved from one or more checked `TLProgram`s, with
+/// combinators indexed by name, ID and type.
+#[derive(Debug, Clone)]
+pub struct Schema {
+  combinators: Vec<Combinator>,
+  constructors_by_name: HashMap<String, usize>,
+  constructors_by_id: HashMap<Nat, usize>,
+  constructors_by_type: HashMap<String, Vec<usize>>,
+  functions_by_name: HashMap<String, usize>,
+  functions_by_id: HashMap<Nat, usize>,
+}
+
+impl Schema {
+  pub fn from_program(program: &TLProgram) -> Result<Schema, Vec<Diagnostic>> {
+    Schema::from_files(&[("", program)])
+  }
+
+  /// Checks `files` and resolves them into one schema. Fails with every
+  /// diagnostic the checks produced.
+  pub fn from_files(files: &[(&str, &TLProgram)]) -> Result<Schema, Vec<Diagnostic>> {
+    let diagnostics = check(files);
+    if !diagnostics.is_empty() {
+      return Err(diagnostics);
+    }
+
+    let mut schema = Schema {
+      combinators: vec![],
+      constructors_by_name: HashMap::new(),
+      constructors_by_id: HashMap::new(),
+      constructors_by_type: HashMap::new(),
+      functions_by_name: HashMap::new(),
+      functions_by_id: HashMap::new(),
+    };
+    for resolved in resolve_combinators(files) {
+      let (name, id) = match (resolved.name(), resolved.id()) {
+        (Some(name), Some(id)) => (name, id),
+        _ => continue,
+      };
+      let fields = match resolve_fields(&resolved.combinator.args) {
+        Some(fields) => fields,
+        None => return Err(vec![uncounted(&resolved)]),
+      };
+      let combinator = Combinator {
+        name: name.clone(),
+        id,
+        kind: resolved.kind,
+        builtin: resolved.builtin,
+        params: resolved.implicit_params(),
+        fields,
+        result_type: resolved.result_type,
+      };
+      let index = schema.combinators.len();
+      match combinator.kind {
+        BlockKind::Types => {
+          schema.constructors_by_name.insert(name, index);
+          schema.constructors_by_id.insert(id, index);
+          schema
+            .constructors_by_type
+            .entry(combinator.type_name().to_string())
+            .or_default()
+            .push(index);
+        }
+        BlockKind::Functions => {
+          schema.functions_by_name.insert(name, index);
+          schema.functions_by_id.insert(id, index);
+        }
+      }
+      schema.combinators.push(combinator);
+    }
+    Ok(schema)
+  }
+
+  /// Constructors and functions in declaration order.
+  pub fn combinators(&self) -> &[Combinator] {
+    &self.combinators
+  }
+
+  pub fn constructor(&self, name: &str) -> Option<&Combinator> {
+    self.get(self.constructors_by_name.get(name))
+  }
+
+  pub fn constructor_by_id(&self, id: Nat) -> Option<&Combinator> {
+    self.get(self.constructors_by_id.get(&id))
+  }
+
+  /// Constructors of the boxed type `name`, e.g. `boolFalse` and `boolTrue`
+  /// for `Bool`.
+  pub fn constructors_of(&self, name: &str) -> Vec<&Combinator> {
+    match self.constructors_by_type.get(name) {
+      Some(indexes) => indexes
+        .iter()
+        .map(|&index| &self.combinators[index])
+        .collect(),
+      None => vec![],
+    }
+  }
+
+  /// Whether `name` is a boxed type with at least one constructor.
+  pub fn has_type(&self, name: &str) -> bool {
+    self.constructors_by_type.contains_key(name)
+  }
+
+  /// Constructors of the boxed type `ty` applied to its parameters, e.g.
+  /// `Vector<User>`, with the parameters substituted so that their fields
+  /// have concrete types. Constructors that build other instances of the
+  /// type only are left out. Parsed expressions are converted with
+  /// `TLType::from_expression`.
+  pub fn instantiate(&self, ty: &TLType) -> Result<Vec<Combinator>, InstantiateError> {
+    let (name, params) = match ty {
+      TLType::Named(name, params) => (name, params),
+      TLType::Bare(inner) => return self.instantiate(inner),
+      ty => return Err(InstantiateError::UnknownType(ty.to_string())),
+    };
+    let constructors = self.constructors_of(name);
+    let arity = match constructors.first() {
+      Some(constructor) => constructor.result_type.params().len(),
+      None => return Err(InstantiateError::UnknownType(name.clone())),
+    };
+    if params.len() != arity {
+      return Err(InstantiateError::WrongArity {
+        ty: name.clone(),
+        expected: arity,
+        found: params.len(),
+      });
+    }
+
+    let mut instances = vec![];
+    for constructor in constructors {
+      let bindings = match constructor.bind_result(ty) {
+        Some(bindings) => bindings,
+        None => continue,
+      };
+      for param in &constructor.params {
+        let wrong_kind = match bindings.get(&param.name) {
+          Some(TLType::Nat(_)) | Some(TLType::Plus(_, _)) => param.ty != TLType::NatType,
+          // names the schema does not know are variables of the context
+          // the type is used in, of either kind
+          Some(TLType::Named(name, params)) if params.is_empty() => {
+            param.ty == TLType::NatType && (self.has_type(name) || self.constructor(name).is_some())
+          }
+          Some(_) => param.ty == TLType::NatType,
+          None => false,
+        };
+        if wrong_kind {
+          return Err(InstantiateError::WrongKind {
+            ty: ty.to_string(),
+            param: param.name.clone(),
+          });
+        }
+      }
+      instances.push(constructor.instantiate(&bindings));
+    }
+    match instances.is_empty() {
+      true => Err(InstantiateError::NoConstructor(ty.to_string())),
+      false => Ok(instances),
+    }
+  }
+
+  pub fn function(&self, name: &str) -> Option<&Combinator> {
+    self.get(self.functions_by_name.get(name))
+  }
+
+  pub fn function_by_id(&self, id: Nat) -> Option<&Combinator> {
+    self.get(self.functions_by_id.get(&id))
+  }
+
+  fn get(&self, index: Option<&usize>) -> Option<&Combinator> {
+    index.map(|&index| &self.combinators[index])
+  }
+}
@@ -0,0 +1,286 @@
+use super::ast::*;
+use super::parser::parse_tl;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Types and naturals that variables of a type expression stand for, by
+/// variable name.
+pub type Bindings = HashMap<String, TLType>;
+
+/// Type expression with the parser's nesting flattened away.
+///
+/// `Vector<User>`, `(Vector User)` and the result type `Vector User` all
+/// become `Named("Vector", [Named("User", [])])`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TLType {
+  Nat(Nat),
+  /// The natural number type `#`.
+  NatType,
+  /// A type, constructor or variable name applied to its parameters.
+  Named(String, Vec<TLType>),
+  /// `%T`
+  Bare(Box<TLType>),
+  /// `!X`
+  Bang(Box<TLType>),
+  /// `n + k`
+  Plus(Box<TLType>, Nat),
+}
+
+fn ident_name(ident: &TLTypeIdent) -> String {
+  match ident {
+    TLTypeIdent::Lower(name) => name.to_string(),
+    TLTypeIdent::Upper(name) => name.to_string(),
+  }
+}
+
+fn add(base: TLType, nat: Nat) -> TLType {
+  match base {
+    TLType::Nat(n) => TLType::Nat(n + nat),
+    TLType::Plus(base, n) => TLType::Plus(base, n + nat),
+    base => TLType::Plus(Box::new(base), nat),
+  }
+}
+
+fn apply(mut terms: Vec<TLType>) -> TLType {
+  if terms.is_empty() {
+    return TLType::Named(String::new(), vec![]);
+  }
+  let rest = terms.split_off(1);
+  match terms.remove(0) {
+    TLType::Named(name, mut params) => {
+      params.extend(rest);
+      TLType::Named(name, params)
+    }
+    TLType::Bare(head) if !rest.is_empty() => TLType::Bare(Box::new(apply(
+      std::iter::once(*head).chain(rest).collect(),
+    ))),
+    head => head,
+  }
+}
+
+/// `+ k` suffix as produced by `parse_expression_hp`.
+fn with_suffix(base: TLType, suffix: &TLExpression) -> TLType {
+  if let TLExpression::Operator(TLOperator::Plus, expr) = suffix {
+    if let TLExpression::Expression(parts) = &**expr {
+      if let [TLExpression::Nat(nat), rest] = parts.as_slice() {
+        return with_suffix(add(base, *nat), rest);
+      }
+    }
+  }
+  base
+}
+
+fn sequence(items: &[TLExpression]) -> TLType {
+  let mut terms: Vec<TLType> = vec![];
+  for item in items {
+    match item {
+      TLExpression::Empty => {}
+      TLExpression::Operator(TLOperator::Plus, expr) => match &**expr {
+        TLExpression::Expression(parts) => match parts.as_slice() {
+          [TLExpression::Nat(_), _] => {
+            if let Some(last) = terms.pop() {
+              terms.push(with_suffix(last, item));
+            }
+          }
+          [TLExpression::Nat(nat), expr, rest] => {
+            terms.push(with_suffix(add(TLType::from_expression(expr), *nat), rest))
+          }
+          _ => terms.push(TLType::from_expression(expr)),
+        },
+        expr => terms.push(TLType::from_expression(expr)),
+      },
+      item => terms.push(TLType::from_expression(item)),
+    }
+  }
+  if terms.len() == 1 {
+    terms.remove(0)
+  } else {
+    apply(terms)
+  }
+}
+
+impl TLType {
+  /// Normalizes an argument type, a multiplicity or a combinator result
+  /// type.
+  pub fn from_expression(expr: &TLExpression) -> TLType {
+    match expr {
+      TLExpression::Nat(nat) => TLType::Nat(*nat),
+      TLExpression::Hash => TLType::NatType,
+      TLExpression::Ident(ident) => TLType::Named(ident_name(ident), vec![]),
+      TLExpression::Operator(TLOperator::Bang, expr) => {
+        TLType::Bang(Box::new(TLType::from_expression(expr)))
+      }
+      TLExpression::Operator(TLOperator::Bare, expr) => {
+        TLType::Bare(Box::new(TLType::from_expression(expr)))
+      }
+      TLExpression::Operator(TLOperator::Plus, _) => sequence(std::slice::from_ref(expr)),
+      TLExpression::Expression(items) => match items.as_slice() {
+        // `ident<expr, ...>` from `parse_term_brackets`
+        [TLExpression::Ident(ident), TLExpression::Expression(list)]
+          if !list.is_empty()
+            && list
+              .iter()
+              .all(|expr| matches!(expr, TLExpression::Expression(_))) =>
+        {
+          TLType::Named(
+            ident_name(ident),
+            list.iter().map(TLType::from_expression).collect(),
+          )
+        }
+        items => sequence(items),
+      },
+      TLExpression::Empty => TLType::Named(String::new(), vec![]),
+    }
+  }
+
+  /// Parses a type written as in a declaration, e.g. `Vector<User>`,
+  /// `%Int` or `Tuple int 2`.
+  pub fn parse(text: &str) -> Result<TLType, String> {
+    let not_a_type = || format!("`{}` is not a type", text);
+    let program = parse_tl(&format!("x value:({}) = X;", text)).map_err(|_| not_a_type())?;
+    let declarations = match program.blocks.first() {
+      Some(TLDeclarationBlock::Types(declarations)) => declarations,
+      _ => return Err(not_a_type()),
+    };
+    let ty = match declarations.first() {
+      Some(TLDeclaration::Combinator(TLCombinator { args, .. })) => match args.as_slice() {
+        [TLArg::Arg(_, expr)] => TLType::from_expression(expr),
+        _ => return Err(not_a_type()),
+      },
+      _ => return Err(not_a_type()),
+    };
+    match &ty {
+      TLType::Named(name, _) if name.is_empty() => Err(not_a_type()),
+      _ => Ok(ty),
+    }
+  }
+
+  /// Name at the head of the expression, looking through `%` and `!`.
+  pub fn name(&self) -> Option<&str> {
+    match self {
+      TLType::Named(name, _) => Some(name),
+      TLType::Bare(inner) | TLType::Bang(inner) => inner.name(),
+      _ => None,
+    }
+  }
+
+  pub fn params(&self) -> &[TLType] {
+    match self {
+      TLType::Named(_, params) => params,
+      TLType::Bare(inner) | TLType::Bang(inner) => inner.params(),
+      _ => &[],
+    }
+  }
+
+  /// Whether `name` occurs anywhere in the expression.
+  pub fn mentions(&self, name: &str) -> bool {
+    let mut found = false;
+    self.walk(&mut |ty| {
+      if let TLType::Named(other, _) = ty {
+        found = found || other == name;
+      }
+    });
+    found
+  }
+
+  /// Calls `f` for this expression and every expression nested in it.
+  pub fn walk<F: FnMut(&TLType)>(&self, f: &mut F) {
+    f(self);
+    match self {
+      TLType::Named(_, params) => {
+        for param in params {
+          param.walk(f);
+        }
+      }
+      TLType::Bare(inner) | TLType::Bang(inner) | TLType::Plus(inner, _) => inner.walk(f),
+      TLType::Nat(_) | TLType::NatType => {}
+    }
+  }
+
+  /// Replaces the variables bound in `bindings` and folds `n+k` once `n`
+  /// is a number.
+  pub fn substitute(&self, bindings: &Bindings) -> TLType {
+    match self {
+      TLType::Named(name, params) if params.is_empty() => match bindings.get(name) {
+        Some(bound) => bound.clone(),
+        None => self.clone(),
+      },
+      TLType::Named(name, params) => TLType::Named(
+        name.clone(),
+        params
+          .iter()
+          .map(|param| param.substitute(bindings))
+          .collect(),
+      ),
+      TLType::Bare(inner) => TLType::Bare(Box::new(inner.substitute(bindings))),
+      TLType::Bang(inner) => TLType::Bang(Box::new(inner.substitute(bindings))),
+      TLType::Plus(base, nat) => match base.substitute(bindings) {
+        TLType::Nat(base) => TLType::Nat(base.wrapping_add(*nat)),
+        base => TLType::Plus(Box::new(base), *nat),
+      },
+      TLType::Nat(_) | TLType::NatType => self.clone(),
+    }
+  }
+
+  /// Binds the `variables` of this expression so that it becomes `ty`,
+  /// e.g. `t` to `User` for `Vector t` and `Vector User`. Fails if no
+  /// binding does, leaving `bindings` partly filled.
+  pub fn unify(&self, ty: &TLType, variables: &HashSet<&str>, bindings: &mut Bindings) -> bool {
+    match (self, ty) {
+      (TLType::Named(name, params), ty)
+        if params.is_empty() && variables.contains(name.as_str()) =>
+      {
+        match bindings.get(name) {
+          Some(bound) => bound == ty,
+          None => {
+            bindings.insert(name.clone(), ty.clone());
+            true
+          }
+        }
+      }
+      (TLType::Named(name, params), TLType::Named(other, others)) => {
+        name == other
+          && params.len() == others.len()
+          && params
+            .iter()
+            .zip(others)
+            .all(|(param, other)| param.unify(other, variables, bindings))
+      }
+      (TLType::Bare(inner), TLType::Bare(other)) | (TLType::Bang(inner), TLType::Bang(other)) => {
+        inner.unify(other, variables, bindings)
+      }
+      (TLType::Plus(base, nat), TLType::Nat(other)) if other >= nat => {
+        base.unify(&TLType::Nat(other - nat), variables, bindings)
+      }
+      (pattern, ty) => pattern == ty,
+    }
+  }
+}
+
+impl fmt::Display for TLType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      TLType::Nat(nat) => write!(f, "{}", nat),
+      TLType::NatType => write!(f, "#"),
+      TLType::Named(name, params) => {
+        write!(f, "{}", name)?;
+        for param in params {
+          match param {
+            TLType::Named(_, inner) if !inner.is_empty() => write!(f, " ({})", param)?,
+            TLType::Plus(_, _) => write!(f, " ({})", param)?,
+            _ => write!(f, " {}", param)?,
+          }
+        }
+        Ok(())
+      }
+      TLType::Bare(inner) | TLType::Bang(inner) => {
+        let operator = if let TLType::Bare(_) = self { "%" } else { "!" };
+        match &**inner {
+          TLType::Named(_, params) if !params.is_empty() => write!(f, "{}({})", operator, inner),
+          _ => write!(f, "{}{}", operator, inner),
+        }
+      }
+      TLType::Plus(base, nat) => write!(f, "{}+{}", base, nat),
+    }
+  }
+}
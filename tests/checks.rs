@@ -0,0 +1,362 @@
+use tl_steam::checks::{check, BlockKind, DiagnosticKind, Recovery};
+use tl_steam::parser::parse_tl;
+use tl_steam::schema::{Combinator, Schema};
+
+fn diagnostics(source: &str) -> Vec<DiagnosticKind> {
+  let program = parse_tl(source).unwrap();
+  match Schema::from_program(&program) {
+    Ok(_) => vec![],
+    Err(diagnostics) => diagnostics
+      .iter()
+      .map(|diagnostic| diagnostic.kind)
+      .collect(),
+  }
+}
+
+/// Kinds of the diagnostics `check` reports over `files`, each with the
+/// locations it points at.
+fn check_files(files: &[(&str, &str)]) -> Vec<(DiagnosticKind, Vec<String>)> {
+  let programs = files
+    .iter()
+    .map(|(path, source)| (*path, parse_tl(source).unwrap()))
+    .collect::<Vec<_>>();
+  let files = programs
+    .iter()
+    .map(|(path, program)| (*path, program))
+    .collect::<Vec<_>>();
+  check(&files)
+    .into_iter()
+    .map(|diagnostic| {
+      let locations = diagnostic
+        .locations
+        .iter()
+        .map(|location| location.to_string())
+        .collect();
+      (diagnostic.kind, locations)
+    })
+    .collect()
+}
+
+#[test]
+fn constructor_of_final_type() {
+  assert_eq!(
+    check_files(&[
+      ("a.tl", "user id:# = User;\nFinal User;"),
+      ("b.tl", "userEmpty = User;"),
+    ]),
+    vec![(
+      DiagnosticKind::ConstructorOfFinalType,
+      vec![
+        "b.tl: block 1, declaration 1".to_string(),
+        "a.tl: block 1, declaration 2".to_string(),
+      ]
+    )]
+  );
+  assert_eq!(check_files(&[("a.tl", "Final User;\n")]), vec![]);
+}
+
+#[test]
+fn constructor_of_empty_type() {
+  assert_eq!(
+    check_files(&[("a.tl", "Empty Nothing;\nnothing = Nothing;")]),
+    vec![(
+      DiagnosticKind::ConstructorOfEmptyType,
+      vec![
+        "a.tl: block 1, declaration 2".to_string(),
+        "a.tl: block 1, declaration 1".to_string(),
+      ]
+    )]
+  );
+  assert_eq!(
+    check_files(&[("a.tl", "nothing = Nothing;"), ("b.tl", "Empty Nothing;")]),
+    vec![(
+      DiagnosticKind::ConstructorOfEmptyType,
+      vec![
+        "b.tl: block 1, declaration 1".to_string(),
+        "a.tl: block 1, declaration 1".to_string(),
+      ]
+    )]
+  );
+}
+
+#[test]
+fn new_type_redefined() {
+  assert_eq!(
+    check_files(&[("a.tl", "user id:# = User;"), ("b.tl", "New User;")]),
+    vec![(
+      DiagnosticKind::NewTypeRedefined,
+      vec![
+        "b.tl: block 1, declaration 1".to_string(),
+        "a.tl: block 1, declaration 1".to_string(),
+      ]
+    )]
+  );
+  assert_eq!(
+    check_files(&[("a.tl", "New User;\nNew User;")]),
+    vec![(
+      DiagnosticKind::NewTypeRedefined,
+      vec![
+        "a.tl: block 1, declaration 2".to_string(),
+        "a.tl: block 1, declaration 1".to_string(),
+      ]
+    )]
+  );
+  assert_eq!(
+    check_files(&[("a.tl", "New User;\nuser id:# = User;")]),
+    vec![]
+  );
+}
+
+#[test]
+fn duplicate_name() {
+  assert_eq!(
+    check_files(&[
+      ("a.tl", "user#11111111 id:# = User;"),
+      ("b.tl", "user#22222222 id:# name:# = User;"),
+    ]),
+    vec![(
+      DiagnosticKind::DuplicateName,
+      vec![
+        "b.tl: block 1, declaration 1".to_string(),
+        "a.tl: block 1, declaration 1".to_string(),
+      ]
+    )]
+  );
+  assert_eq!(
+    check_files(&[(
+      "a.tl",
+      "pong = Pong;\n---functions---\nping#11111111 = Pong;\nping#22222222 id:# = Pong;"
+    )]),
+    vec![(
+      DiagnosticKind::DuplicateName,
+      vec![
+        "a.tl: block 2, declaration 2".to_string(),
+        "a.tl: block 2, declaration 1".to_string(),
+      ]
+    )]
+  );
+}
+
+#[test]
+fn duplicate_id() {
+  assert_eq!(
+    check_files(&[("a.tl", "user id:# = User;"), ("b.tl", "user id:# = User;")]),
+    vec![(
+      DiagnosticKind::DuplicateId,
+      vec![
+        "b.tl: block 1, declaration 1".to_string(),
+        "a.tl: block 1, declaration 1".to_string(),
+      ]
+    )]
+  );
+}
+
+#[test]
+fn id_collision() {
+  assert_eq!(
+    check_files(&[(
+      "a.tl",
+      "user#11111111 id:# = User;\nchat#11111111 id:# = Chat;"
+    )]),
+    vec![(
+      DiagnosticKind::IdCollision,
+      vec![
+        "a.tl: block 1, declaration 2".to_string(),
+        "a.tl: block 1, declaration 1".to_string(),
+      ]
+    )]
+  );
+  // constructor and function IDs share one space
+  assert_eq!(
+    check_files(&[(
+      "a.tl",
+      "user#11111111 id:# = User;\n---functions---\ngetUser#11111111 id:# = User;"
+    )]),
+    vec![(
+      DiagnosticKind::IdCollision,
+      vec![
+        "a.tl: block 2, declaration 1".to_string(),
+        "a.tl: block 1, declaration 1".to_string(),
+      ]
+    )]
+  );
+}
+
+#[test]
+fn unknown_result_type() {
+  let at = || vec!["a.tl: block 2, declaration 1".to_string()];
+  assert_eq!(
+    check_files(&[(
+      "a.tl",
+      "user id:# = User;\n---functions---\ngetChat id:# = Chat;"
+    )]),
+    vec![(DiagnosticKind::UnknownResultType, at())]
+  );
+  assert_eq!(
+    check_files(&[(
+      "a.tl",
+      "user id:# = User;\n---functions---\ninvoke {X:Type} query:!X = X User;"
+    )]),
+    vec![(DiagnosticKind::UnknownResultType, at())]
+  );
+  assert_eq!(
+    check_files(&[(
+      "a.tl",
+      "user id:# = User;\n---functions---\ngetUser id:# = User;\ninvoke {X:Type} query:!X = X;"
+    )]),
+    vec![]
+  );
+}
+
+#[test]
+fn bang_outside_function() {
+  assert_eq!(
+    check_files(&[("a.tl", "wrap {X:Type} query:!X = Wrap X;")]),
+    vec![(
+      DiagnosticKind::BangOutsideFunction,
+      vec!["a.tl: block 1, declaration 1".to_string()]
+    )]
+  );
+}
+
+#[test]
+fn bang_on_non_type_variable() {
+  assert_eq!(
+    check_files(&[(
+      "a.tl",
+      "user id:# = User;\n---functions---\ninvoke query:!User = User;"
+    )]),
+    vec![(
+      DiagnosticKind::BangOnNonTypeVariable,
+      vec!["a.tl: block 2, declaration 1".to_string()]
+    )]
+  );
+}
+
+#[test]
+fn function_name_clash() {
+  assert_eq!(
+    check_files(&[
+      ("a.tl", "user id:# = User;"),
+      (
+        "b.tl",
+        "chat id:# = Chat;\n---functions---\nuser id:# flags:# = User;"
+      ),
+    ]),
+    vec![(
+      DiagnosticKind::FunctionNameClash,
+      vec![
+        "b.tl: block 2, declaration 1".to_string(),
+        "a.tl: block 1, declaration 1".to_string(),
+      ]
+    )]
+  );
+}
+
+#[test]
+fn block_kind() {
+  let program = parse_tl("user id:# = User;\n---functions---\ngetUser id:# = User;").unwrap();
+  let schema = Schema::from_program(&program).unwrap();
+  assert_eq!(schema.constructor("user").unwrap().kind, BlockKind::Types);
+  assert_eq!(
+    schema.function("getUser").unwrap().kind,
+    BlockKind::Functions
+  );
+}
+
+#[test]
+fn unused_implicit_param() {
+  assert_eq!(
+    check_files(&[("a.tl", "box {t:Type} {u:Type} value:t = Box t;")]),
+    vec![(
+      DiagnosticKind::UnusedImplicitParam,
+      vec!["a.tl: block 1, declaration 1".to_string()]
+    )]
+  );
+  // used only by the count of a block or by a condition
+  assert_eq!(
+    check_files(&[("a.tl", "list {n:#} xs:n*[ x:# ] = List;")]),
+    vec![]
+  );
+}
+
+#[test]
+fn unbound_result_param() {
+  assert_eq!(
+    check_files(&[("a.tl", "box {t:Type} value:t = Box t u;")]),
+    vec![(
+      DiagnosticKind::UnboundResultParam,
+      vec!["a.tl: block 1, declaration 1".to_string()]
+    )]
+  );
+  // declared types are not variables
+  assert_eq!(
+    check_files(&[("a.tl", "user id:# = User;\nusers = Box User;")]),
+    vec![]
+  );
+}
+
+#[test]
+fn recovery() {
+  let source = "vector#1cb5c415 {t:Type} # [ t ] = Vector t;
+same {t:Type} a:t b:t = Same;
+---functions---
+invoke {X:Type} query:!X = X;";
+  let schema = Schema::from_program(&parse_tl(source).unwrap()).unwrap();
+  let recovery = |combinator: Option<&Combinator>| {
+    combinator
+      .unwrap()
+      .params
+      .iter()
+      .map(|param| param.recovery.clone())
+      .collect::<Vec<_>>()
+  };
+  assert_eq!(
+    recovery(schema.constructor("vector")),
+    vec![Recovery::ResultType(0)]
+  );
+  assert_eq!(
+    recovery(schema.function("invoke")),
+    vec![Recovery::BangArg("query".to_string())]
+  );
+  assert_eq!(recovery(schema.constructor("same")), vec![Recovery::None]);
+}
+
+#[test]
+fn block_without_count() {
+  assert_eq!(
+    diagnostics("bad [ int ] = Bad;"),
+    vec![DiagnosticKind::UncountedBlock]
+  );
+  assert_eq!(
+    diagnostics("bad xs:[ int ] n:# = Bad;"),
+    vec![DiagnosticKind::UncountedBlock]
+  );
+  assert_eq!(
+    diagnostics("bad n:# xs:n*[ [ int ] ] = Bad;"),
+    vec![DiagnosticKind::UncountedBlock]
+  );
+}
+
+#[test]
+fn block_counted_by_a_nat() {
+  assert!(diagnostics("ok n:# [ int ] = Ok;").is_empty());
+  assert!(diagnostics("ok n:# xs:n*[ m:# [ int ] ] = Ok;").is_empty());
+  assert!(diagnostics("tuple {t:Type} {n:#} [ t ] = Tuple t n;").is_empty());
+}
+
+#[test]
+fn flag_bit_out_of_range() {
+  assert_eq!(
+    diagnostics("int ? = Int;\nok flags:# a:flags.31?int = Ok;"),
+    vec![]
+  );
+  assert_eq!(
+    diagnostics("int ? = Int;\nbad flags:# a:flags.32?int = Bad;"),
+    vec![DiagnosticKind::FlagBitOutOfRange]
+  );
+  assert_eq!(
+    diagnostics("int ? = Int;\nbad flags:# n:# xs:n*[ a:flags.40?int ] = Bad;"),
+    vec![DiagnosticKind::FlagBitOutOfRange]
+  );
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tl_steam::runtime::codec::{Codec, Codecs};
+use tl_steam::runtime::de::Deserializer;
+use tl_steam::runtime::generate::{GenerateError, Generator};
+use tl_steam::runtime::ser::{EncodeError, Serializer};
+use tl_steam::runtime::validate::Validator;
+use tl_steam::runtime::{Object, Value};
+
+const SOURCE: &str = "point ? = Point;
+withPoint#11223344 p:point = WithPoint;
+";
+
+#[test]
+fn codec_with_runtime_name_and_state() {
+  let schema = common::schema(SOURCE);
+
+  let encoded = Arc::new(AtomicUsize::new(0));
+  let counter = encoded.clone();
+  let name = ["po", "int"].concat();
+  let mut codecs = Codecs::default();
+  codecs.register(Codec::new(
+    name,
+    move |value: &Value, out: &mut Vec<u8>| {
+      counter.fetch_add(1, Ordering::SeqCst);
+      match value {
+        Value::Int(x) => {
+          out.extend_from_slice(&x.to_le_bytes());
+          Ok(())
+        }
+        _ => Err(EncodeError::WrongValue {
+          expected: "int".to_string(),
+          found: value.kind(),
+        }),
+      }
+    },
+    |reader| Ok(Value::Int(reader.read_u32()? as i32)),
+  ));
+
+  let value = Value::Object(Object::new("withPoint").with("p", Value::Int(7)));
+  let mut serializer = Serializer::with_codecs(&schema, &codecs);
+  serializer.write_object(&value).unwrap();
+  let bytes = serializer.finish();
+  assert_eq!(bytes, [0x44, 0x33, 0x22, 0x11, 7, 0, 0, 0]);
+  assert_eq!(encoded.load(Ordering::SeqCst), 1);
+
+  let mut deserializer = Deserializer::with_codecs(&schema, &codecs, &bytes);
+  assert_eq!(deserializer.read(&common::ty("WithPoint")).unwrap(), value);
+}
+
+fn point_codec() -> Codec {
+  Codec::new(
+    "point",
+    |value: &Value, out: &mut Vec<u8>| match value {
+      Value::Int(x) => {
+        out.extend_from_slice(&x.to_le_bytes());
+        Ok(())
+      }
+      _ => Err(EncodeError::WrongValue {
+        expected: "int".to_string(),
+        found: value.kind(),
+      }),
+    },
+    |reader| Ok(Value::Int(reader.read_u32()? as i32)),
+  )
+}
+
+#[test]
+fn generator_uses_the_codecs() {
+  let schema = common::schema(SOURCE);
+  let ty = common::ty("WithPoint");
+
+  let mut codecs = Codecs::default();
+  codecs.register(point_codec());
+  let mut generator = Generator::with_codecs(&schema, &codecs, 1);
+  assert_eq!(
+    generator.generate(&ty),
+    Err(GenerateError::Unsupported(
+      "values of the builtin type `point`".to_string()
+    ))
+  );
+
+  codecs.register(point_codec().with_generate(|rng, _| Value::Int(rng.below(10) as i32)));
+  let mut generator = Generator::with_codecs(&schema, &codecs, 1);
+  let value = generator.generate(&ty).unwrap();
+  assert!(Validator::with_codecs(&schema, &codecs)
+    .validate_object(&value)
+    .is_empty());
+}
@@ -0,0 +1,36 @@
+//! Schemas and values shared by the integration tests.
+#![allow(dead_code)]
+
+use tl_steam::parser::parse_tl;
+use tl_steam::runtime::Value;
+use tl_steam::schema::Schema;
+use tl_steam::types::TLType;
+
+/// Builtin types, `Bool`, `true` and `vector`, as most schemas declare
+/// them.
+pub const PRELUDE: &str = "int ? = Int;
+long ? = Long;
+double ? = Double;
+string ? = String;
+bytes ? = Bytes;
+int128 4*[ int ] = Int128;
+int256 8*[ int ] = Int256;
+boolFalse#bc799737 = Bool;
+boolTrue#997275b5 = Bool;
+true#3fedd339 = True;
+vector#1cb5c415 {t:Type} # [ t ] = Vector t;
+";
+
+/// The schema of [`PRELUDE`] followed by `source`.
+pub fn schema(source: &str) -> Schema {
+  let source = [PRELUDE, source].concat();
+  Schema::from_program(&parse_tl(&source).unwrap()).unwrap()
+}
+
+pub fn ty(source: &str) -> TLType {
+  TLType::parse(source).unwrap()
+}
+
+pub fn string(text: &str) -> Value<'static> {
+  Value::String(text.to_string().into())
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use tl_steam::runtime::de::{DecodeError, Deserializer, Limits, UnknownConstructors};
+use tl_steam::runtime::generate::Rng;
+use tl_steam::runtime::ser::serialize;
+use tl_steam::runtime::{Object, Value};
+use tl_steam::schema::Schema;
+
+const SOURCE: &str = "node#0a0b0c0d children:(Vector Node) = Node;
+leaf#01010101 value:int = Node;
+blob#02020202 data:bytes = Blob;
+ints#04040404 xs:(Vector int) = Ints;
+list#03030303 n:# xs:n*[ x:int y:string ] = List;
+user#abcdef12 flags:# id:long name:flags.0?string bot:flags.1?true = User;
+";
+
+fn decode(schema: &Schema, limits: Limits, data: &[u8]) -> Result<Value<'static>, DecodeError> {
+  let mut deserializer = Deserializer::new(schema, data);
+  deserializer.set_limits(limits);
+  let value = deserializer.read_object()?;
+  deserializer.finish()?;
+  Ok(value.into_owned())
+}
+
+#[test]
+fn random_input_does_not_panic() {
+  let schema = common::schema(SOURCE);
+  let ids: Vec<u32> = schema
+    .combinators()
+    .iter()
+    .map(|combinator| combinator.id)
+    .collect();
+  let mut rng = Rng::new(0);
+  for round in 0..10_000 {
+    let length = rng.below(96);
+    let mut data = vec![];
+    if round % 2 == 1 {
+      let id = ids[rng.below(ids.len())];
+      data.extend_from_slice(&id.to_le_bytes());
+    }
+    data.extend((0..length).map(|_| rng.next_u64() as u8));
+    for unknown in &[
+      UnknownConstructors::Fail,
+      UnknownConstructors::Preserve,
+      UnknownConstructors::Scan,
+    ] {
+      let mut deserializer = Deserializer::new(&schema, &data);
+      deserializer.set_limits(Limits::default());
+      deserializer.set_unknown_constructors(*unknown);
+      if deserializer.read_object().is_ok() {
+        let _: Result<(), DecodeError> = deserializer.finish();
+      }
+    }
+  }
+}
+
+#[test]
+fn depth() {
+  let schema = common::schema(SOURCE);
+  let mut node = Value::Object(Object::new("leaf").with("value", Value::Int(0)));
+  for _ in 0..10 {
+    node = Value::Object(Object::new("node").with("children", Value::Vector(vec![node])));
+  }
+  let data = serialize(&schema, &node).unwrap();
+  assert_eq!(decode(&schema, Limits::none(), &data), Ok(node));
+
+  let limits = Limits {
+    max_depth: 8,
+    ..Limits::default()
+  };
+  match decode(&schema, limits, &data) {
+    Err(DecodeError::TooDeep { limit: 8, .. }) => {}
+    other => panic!("expected too deep, got {:?}", other),
+  }
+}
+
+#[test]
+fn vector_length() {
+  let schema = common::schema(SOURCE);
+  // A vector claiming 2^31 items with none following.
+  let mut data = vec![0x04, 0x04, 0x04, 0x04, 0x15, 0xc4, 0xb5, 0x1c];
+  data.extend(&0x8000_0000u32.to_le_bytes());
+  match decode(&schema, Limits::default(), &data) {
+    Err(DecodeError::VectorTooLong {
+      offset: 8,
+      length: 0x8000_0000,
+      ..
+    }) => {}
+    other => panic!("expected a vector too long, got {:?}", other),
+  }
+
+  let ints = (0..20).map(Value::Int).collect();
+  let value = Value::Object(Object::new("ints").with("xs", Value::Vector(ints)));
+  let data = serialize(&schema, &value).unwrap();
+  let limits = Limits {
+    max_vector_length: 10,
+    ..Limits::default()
+  };
+  match decode(&schema, limits, &data) {
+    Err(DecodeError::VectorTooLong {
+      length: 20,
+      limit: 10,
+      ..
+    }) => {}
+    other => panic!("expected a vector too long, got {:?}", other),
+  }
+}
+
+#[test]
+fn bytes_length() {
+  let schema = common::schema(SOURCE);
+  // `0xfe` and a three byte length of 2^24 - 1.
+  let data = [0x02, 0x02, 0x02, 0x02, 0xfe, 0xff, 0xff, 0xff];
+  let limits = Limits {
+    max_bytes_length: 1 << 20,
+    ..Limits::default()
+  };
+  match decode(&schema, limits, &data) {
+    Err(DecodeError::BytesTooLong {
+      offset: 4,
+      length: 0xff_ffff,
+      ..
+    }) => {}
+    other => panic!("expected bytes too long, got {:?}", other),
+  }
+}
+
+#[test]
+fn allocation() {
+  let schema = common::schema(SOURCE);
+  let ints = (0..1000).map(Value::Int).collect();
+  let value = Value::Object(Object::new("ints").with("xs", Value::Vector(ints)));
+  let data = serialize(&schema, &value).unwrap();
+  assert_eq!(decode(&schema, Limits::default(), &data), Ok(value));
+
+  let limits = Limits {
+    max_allocation: 1024,
+    ..Limits::default()
+  };
+  match decode(&schema, limits, &data) {
+    Err(DecodeError::AllocationLimit { limit: 1024, .. }) => {}
+    other => panic!("expected the allocation limit, got {:?}", other),
+  }
+}
+
+#[test]
+fn records() {
+  let schema = common::schema(SOURCE);
+  let mut data = vec![0x03, 0x03, 0x03, 0x03];
+  data.extend(&0x4000_0000u32.to_le_bytes());
+  match decode(&schema, Limits::default(), &data) {
+    Err(DecodeError::TooManyRecords { .. }) => {}
+    other => panic!("expected too many records, got {:?}", other),
+  }
+}
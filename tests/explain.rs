@@ -0,0 +1,82 @@
+mod common;
+
+use tl_steam::runtime::explain::{explain, explain_object};
+
+const SOURCE: &str = "message#5c8f1a0e id:int text:string = Message;
+chat#0a0b0c0d title:string messages:(Vector Message) = Chat;
+";
+
+/// A chat titled "hi" with one message, 7 "abc".
+const CHAT: [u8; 28] = [
+  0x0d, 0x0c, 0x0b, 0x0a, 2, b'h', b'i', 0, 0x15, 0xc4, 0xb5, 0x1c, 1, 0, 0, 0, 0x0e, 0x1a, 0x8f,
+  0x5c, 7, 0, 0, 0, 3, b'a', b'b', b'c',
+];
+
+fn lines(lines: &[&str]) -> String {
+  lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+#[test]
+fn nested_object() {
+  let schema = common::schema(SOURCE);
+  assert_eq!(
+    explain_object(&schema, &CHAT),
+    lines(&[
+      "   000000  0d 0c 0b 0a              chat#0a0b0c0d",
+      "   000004  02 68 69 00                title: \"hi\"",
+      "   000008  15 c4 b5 1c                messages: vector#1cb5c415",
+      "   00000c  01 00 00 00                1 item",
+      "   000010  0e 1a 8f 5c                  [0]: message#5c8f1a0e",
+      "   000014  07 00 00 00                    id: 7",
+      "   000018  03 61 62 63                    text: \"abc\"",
+    ])
+  );
+}
+
+#[test]
+fn bare_type() {
+  let schema = common::schema(SOURCE);
+  assert_eq!(
+    explain(&schema, &common::ty("%Message"), &CHAT[20..]),
+    lines(&[
+      "   000000                           message",
+      "   000000  07 00 00 00                id: 7",
+      "   000004  03 61 62 63                text: \"abc\"",
+    ])
+  );
+}
+
+#[test]
+fn failure_marked_where_decoding_stopped() {
+  let schema = common::schema(SOURCE);
+  // the marker goes on the field cut short, with the bytes left of it
+  assert_eq!(
+    explain_object(&schema, &CHAT[..22]),
+    lines(&[
+      "   000000  0d 0c 0b 0a              chat#0a0b0c0d",
+      "   000004  02 68 69 00                title: \"hi\"",
+      "   000008  15 c4 b5 1c                messages: vector#1cb5c415",
+      "   00000c  01 00 00 00                1 item",
+      "   000010  0e 1a 8f 5c                  [0]: message#5c8f1a0e",
+      "!! 000014  07 00                          id: input truncated at offset 20: 2 more byte(s) needed",
+    ])
+  );
+
+  // unknown constructors fail on their ID, at the top level
+  assert_eq!(
+    explain_object(&schema, &[1, 2, 3, 4, 5]),
+    lines(&["!! 000000  01 02 03 04 05           unknown constructor ID #04030201 at offset 0"])
+  );
+}
+
+#[test]
+fn trailing_bytes_marked_after_the_value() {
+  let schema = common::schema(SOURCE);
+  let mut data = CHAT.to_vec();
+  data.extend_from_slice(&[9, 9, 9, 9]);
+  let text = explain_object(&schema, &data);
+  assert!(text.starts_with(&explain_object(&schema, &CHAT)));
+  assert!(text.ends_with(&lines(&[
+    "!! 00001c  09 09 09 09              unexpected bytes after the value at offset 28"
+  ])));
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use serde::{Deserialize, Serialize};
+use tl_steam::parser::parse_tl;
+use tl_steam::runtime::format::{
+  from_bytes, from_bytes_with_schema, to_bytes, to_bytes_with_schema, BareVector, Error,
+};
+use tl_steam::schema::Schema;
+
+const SOURCE: &str =
+  "user#abcdef12 flags:# id:int first_name:flags.0?string bot:flags.2?true = User;
+";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "point#11223344")]
+struct Point {
+  x: i32,
+  y: i32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "user")]
+struct User {
+  flags: u32,
+  id: i32,
+  first_name: Option<String>,
+  bot: bool,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Shape {
+  #[serde(rename = "square#00000001")]
+  Square { side: i32 },
+  #[serde(rename = "empty#00000002")]
+  Empty,
+}
+
+#[test]
+fn id_spelled_out_in_the_name() {
+  let point = Point { x: 1, y: -1 };
+  let bytes = to_bytes(&point).unwrap();
+  assert_eq!(bytes[..4], [0x44, 0x33, 0x22, 0x11]);
+  assert_eq!(bytes.len(), 12);
+  assert_eq!(from_bytes::<Point>(&bytes).unwrap(), point);
+
+  let square = Shape::Square { side: 3 };
+  let bytes = to_bytes(&square).unwrap();
+  assert_eq!(bytes, [1, 0, 0, 0, 3, 0, 0, 0]);
+  assert_eq!(from_bytes::<Shape>(&bytes).unwrap(), square);
+  assert_eq!(
+    from_bytes::<Shape>(&to_bytes(&Shape::Empty).unwrap()).unwrap(),
+    Shape::Empty
+  );
+}
+
+#[test]
+fn id_looked_up_in_the_schema() {
+  let schema = common::schema(SOURCE);
+  let user = User {
+    flags: 0b101,
+    id: 42,
+    first_name: Some("Ann".to_string()),
+    bot: true,
+  };
+  let bytes = to_bytes_with_schema(&schema, &user).unwrap();
+  assert_eq!(bytes[..4], [0x12, 0xef, 0xcd, 0xab]);
+  // The `bot` bit is only in the flags.
+  assert_eq!(bytes.len(), 4 + 4 + 4 + 4);
+  assert_eq!(
+    from_bytes_with_schema::<User>(&schema, &bytes).unwrap(),
+    user
+  );
+
+  assert_eq!(to_bytes(&user), Err(Error::UnknownName("user".to_string())));
+}
+
+#[test]
+fn absent_options() {
+  let schema = common::schema(SOURCE);
+  let user = User {
+    flags: 0,
+    id: 7,
+    first_name: None,
+    bot: false,
+  };
+  let bytes = to_bytes_with_schema(&schema, &user).unwrap();
+  assert_eq!(bytes.len(), 4 + 4 + 4);
+  assert_eq!(
+    from_bytes_with_schema::<User>(&schema, &bytes).unwrap(),
+    user
+  );
+}
+
+#[test]
+fn options_require_a_schema() {
+  #[derive(Debug, Serialize, Deserialize)]
+  #[serde(rename = "maybe#00000003")]
+  struct Maybe {
+    value: Option<i32>,
+  }
+
+  let bytes = to_bytes(&Maybe { value: Some(1) }).unwrap();
+  match from_bytes::<Maybe>(&bytes) {
+    Err(Error::Unsupported(what)) => assert!(what.contains("`Option`"), "{}", what),
+    other => panic!("expected an unsupported `Option`, got {:?}", other),
+  }
+}
+
+#[test]
+fn wrong_constructor() {
+  let bytes = to_bytes(&Point { x: 0, y: 0 }).unwrap();
+  match from_bytes::<Shape>(&bytes) {
+    Err(Error::WrongConstructor { found, offset, .. }) => {
+      assert_eq!((found, offset), (0x1122_3344, 0));
+    }
+    other => panic!("expected a wrong constructor, got {:?}", other),
+  }
+}
+
+#[test]
+fn id_computed_from_a_declaration() {
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  #[serde(rename = "pair a:int b:int = Pair")]
+  struct Pair {
+    a: i32,
+    b: i32,
+  }
+
+  let program = parse_tl("int ? = Int;\npair a:int b:int = Pair;").unwrap();
+  let schema = Schema::from_program(&program).unwrap();
+  let id = schema.constructor("pair").unwrap().id;
+  let pair = Pair { a: 1, b: 2 };
+  let bytes = to_bytes(&pair).unwrap();
+  assert_eq!(bytes[..4], id.to_le_bytes());
+  assert_eq!(from_bytes::<Pair>(&bytes).unwrap(), pair);
+}
+
+#[test]
+fn flags_computed_from_the_fields_present() {
+  let schema = common::schema(SOURCE);
+  let user = User {
+    flags: 0b1000,
+    id: 42,
+    first_name: None,
+    bot: true,
+  };
+  let bytes = to_bytes_with_schema(&schema, &user).unwrap();
+  // the `bot` bit set, the unused bit 3 kept
+  assert_eq!(
+    bytes,
+    [0x12, 0xef, 0xcd, 0xab, 0b1100, 0, 0, 0, 42, 0, 0, 0]
+  );
+  assert_eq!(
+    from_bytes_with_schema::<User>(&schema, &bytes).unwrap(),
+    User {
+      flags: 0b1100,
+      ..user
+    }
+  );
+
+  let user = User {
+    flags: 0b111,
+    id: 42,
+    first_name: None,
+    bot: false,
+  };
+  let bytes = to_bytes_with_schema(&schema, &user).unwrap();
+  assert_eq!(bytes[4..8], [0b010, 0, 0, 0]);
+
+  // without a schema the flags are written as given
+  #[derive(Serialize)]
+  #[serde(rename = "user#abcdef12")]
+  struct Given {
+    flags: u32,
+    id: i32,
+  }
+  let bytes = to_bytes(&Given { flags: 1, id: 42 }).unwrap();
+  assert_eq!(bytes[4..8], [1, 0, 0, 0]);
+}
+
+#[test]
+fn bare_vectors() {
+  #[derive(Debug, PartialEq, Serialize, Deserialize)]
+  #[serde(rename = "lists#00000004")]
+  struct Lists {
+    boxed: Vec<i32>,
+    bare: BareVector<i32>,
+  }
+
+  let lists = Lists {
+    boxed: vec![1],
+    bare: BareVector(vec![2, 3]),
+  };
+  let bytes = to_bytes(&lists).unwrap();
+  assert_eq!(
+    bytes,
+    [
+      4, 0, 0, 0, // lists
+      0x15, 0xc4, 0xb5, 0x1c, 1, 0, 0, 0, 1, 0, 0, 0, // boxed
+      2, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, // bare
+    ]
+  );
+  assert_eq!(from_bytes::<Lists>(&bytes).unwrap(), lists);
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use tl_steam::runtime::generate::{self_test, Budget, Generator, Rng};
+use tl_steam::runtime::{Object, Value};
+
+const SOURCE: &str = "tuple#9770768a {t:Type} {n:#} [ t ] = Tuple t n;
+nothing#2d3a4b5c {t:Type} = Maybe t;
+just#3e4b5c6d {t:Type} value:t = Maybe t;
+photoEmpty#4f11bae1 = Photo;
+photo#1a2b3c4d id:long sizes:(Vector int) = Photo;
+user#abcdef12 flags:# id:int name:flags.0?string photo:flags.1?Photo bot:flags.2?true flags2:# score:flags2.5?double = User;
+points#5a6b7c8d n:# xs:n*[ x:int y:int ] = Points;
+grid#6b7c8d9e rows:# cols:# cells:rows*[ cols*[ bytes ] ] = Grid;
+leaf#01010101 value:int = Tree;
+node#02020202 left:Tree right:Tree tags:(Vector string) = Tree;
+pair#03030303 a:(Maybe User) b:(Tuple Tree 2) = Pair;
+---functions---
+getUser#11111111 id:int = User;
+getTree#22222222 depth:# = Tree;
+";
+
+#[test]
+fn round_trips() {
+  let schema = common::schema(SOURCE);
+  let report = self_test(&schema, 42, 20, Budget::default());
+  let failures = report
+    .failures
+    .iter()
+    .map(|failure| failure.to_string())
+    .collect::<Vec<_>>();
+  assert_eq!(failures, Vec::<String>::new());
+  for builtin in &["int", "long", "double", "string", "bytes"] {
+    assert!(report.skipped.contains(&builtin.to_string()));
+  }
+  // Polymorphic constructors are instantiated rather than skipped.
+  for polymorphic in &["vector", "tuple", "nothing", "just"] {
+    assert!(
+      !report.skipped.contains(&polymorphic.to_string()),
+      "{} skipped",
+      polymorphic
+    );
+  }
+  let combinators = schema.combinators().len();
+  assert_eq!(report.passed, (combinators - report.skipped.len()) * 20);
+}
+
+#[test]
+fn same_seed_same_values() {
+  let schema = common::schema(SOURCE);
+  let types = ["User", "Pair", "Tree", "Grid", "Vector Photo"]
+    .iter()
+    .map(|ty| common::ty(ty))
+    .collect::<Vec<_>>();
+  let values = |seed| {
+    let mut generator = Generator::new(&schema, seed);
+    types
+      .iter()
+      .map(|ty| generator.generate(ty).unwrap())
+      .collect::<Vec<_>>()
+  };
+  assert_eq!(values(7), values(7));
+  assert_ne!(values(7), values(8));
+
+  // splitmix64 then xorshift64*, so that a seed gives the same values on
+  // every version
+  let mut rng = Rng::new(7);
+  assert_eq!(
+    (0..3).map(|_| rng.next_u64()).collect::<Vec<_>>(),
+    [
+      0x14ea_a7d1_f828_843a,
+      0x421d_9d8f_ff2d_1844,
+      0x5aa5_48bb_d8c6_01d5
+    ]
+  );
+}
+
+/// Checks that `value`, nested `depth` deep, stays within `budget`.
+fn within(value: &Value, depth: usize, budget: &Budget) {
+  match value {
+    Value::Vector(items) => {
+      assert!(items.len() <= budget.max_items, "{:?}", value);
+      if depth >= budget.max_depth {
+        assert!(items.is_empty(), "{:?} past the depth budget", value);
+      }
+      for item in items {
+        within(item, depth + 1, budget);
+      }
+    }
+    Value::Object(Object { fields, .. }) => {
+      for (_, field) in fields {
+        within(field, depth + 1, budget);
+      }
+    }
+    Value::String(string) => assert!(string.len() <= budget.max_length),
+    Value::Bytes(bytes) => assert!(bytes.len() <= budget.max_length),
+    _ => {}
+  }
+}
+
+#[test]
+fn budget_respected() {
+  let schema = common::schema(SOURCE);
+  let budget = Budget {
+    max_depth: 3,
+    max_items: 2,
+    max_length: 5,
+  };
+  let mut generator = Generator::new(&schema, 1);
+  generator.set_budget(budget);
+  // `Tuple Tree 2` and the cells of `Grid` have counts fixed above them,
+  // so they are left out
+  for ty in &["User", "Tree", "Points", "Vector Photo", "Vector string"] {
+    for _ in 0..50 {
+      within(&generator.generate(&common::ty(ty)).unwrap(), 0, &budget);
+    }
+  }
+}
+
+#[test]
+fn flags_match_the_fields_present() {
+  let schema = common::schema(SOURCE);
+  let mut generator = Generator::new(&schema, 3);
+  let mut seen = [false; 2];
+  for _ in 0..50 {
+    let user = match generator.generate(&common::ty("User")).unwrap() {
+      Value::Object(user) => user,
+      other => panic!("expected a user, got {:?}", other),
+    };
+    let flags = match (user.get("flags"), user.get("flags2")) {
+      (Some(Value::Nat(flags)), Some(Value::Nat(flags2))) => (*flags, *flags2),
+      other => panic!("expected flags, got {:?}", other),
+    };
+    assert_eq!(flags.0 & 1 != 0, user.get("name").is_some());
+    assert_eq!(flags.0 & 2 != 0, user.get("photo").is_some());
+    assert_eq!(
+      flags.0 & 4 != 0,
+      user.get("bot") == Some(&Value::Bool(true))
+    );
+    assert_eq!(flags.1 & 32 != 0, user.get("score").is_some());
+    seen[(flags.0 & 1) as usize] = true;
+  }
+  // both with and without a name
+  assert_eq!(seen, [true, true]);
+}
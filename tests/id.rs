@@ -0,0 +1,117 @@
+use tl_steam::ast::*;
+use tl_steam::checks::ids::verify_ids;
+use tl_steam::checks::DiagnosticKind;
+use tl_steam::id::{computed_id, crc32, fix_ids, normalized_text};
+use tl_steam::parser::parse_tl;
+
+/// Calls `f` with the single combinator `source` declares and whether it
+/// is a builtin.
+fn with_combinator<T>(source: &str, f: impl FnOnce(&TLCombinator, bool) -> T) -> T {
+  let program = parse_tl(source).unwrap();
+  match program.blocks.first() {
+    Some(TLDeclarationBlock::Types(declarations)) => match declarations.first() {
+      Some(TLDeclaration::Combinator(combinator)) => f(combinator, false),
+      Some(TLDeclaration::BuiltIn(combinator)) => f(combinator, true),
+      declaration => panic!("unexpected declaration {:?}", declaration),
+    },
+    block => panic!("unexpected block {:?}", block),
+  }
+}
+
+fn id(source: &str) -> u32 {
+  with_combinator(source, |combinator, builtin| {
+    computed_id(combinator, builtin).unwrap()
+  })
+}
+
+#[test]
+fn checksum() {
+  assert_eq!(crc32(b""), 0);
+  assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+}
+
+#[test]
+fn known_ids() {
+  assert_eq!(id("vector {t:Type} # [ t ] = Vector t;"), 0x1cb5_c415);
+  assert_eq!(id("boolFalse = Bool;"), 0xbc79_9737);
+  assert_eq!(id("boolTrue = Bool;"), 0x9972_75b5);
+  assert_eq!(id("true = True;"), 0x3fed_d339);
+  assert_eq!(id("int ? = Int;"), 0xa850_9bda);
+  assert_eq!(id("long ? = Long;"), 0x2207_6cba);
+  assert_eq!(id("string ? = String;"), 0xb528_6e24);
+  assert_eq!(id("req_pq nonce:int128 = ResPQ;"), 0x6046_9778);
+  assert_eq!(
+    id("resPQ nonce:int128 server_nonce:int128 pq:string server_public_key_fingerprints:Vector<long> = ResPQ;"),
+    0x0516_2463
+  );
+  assert_eq!(id("msgs_ack msg_ids:Vector<long> = MsgsAck;"), 0x62d6_b459);
+  assert_eq!(
+    id("rpc_result req_msg_id:long result:Object = RpcResult;"),
+    0xf35c_6d01
+  );
+}
+
+#[test]
+fn explicit_id_is_ignored() {
+  assert_eq!(id("boolTrue#00000000 = Bool;"), 0x9972_75b5);
+}
+
+#[test]
+fn normalization() {
+  let text = |source| {
+    with_combinator(source, |combinator, builtin| {
+      normalized_text(combinator, builtin).unwrap()
+    })
+  };
+  assert_eq!(
+    text("msgs_ack#62d6b459 msg_ids:Vector<long> = MsgsAck;"),
+    "msgs_ack msg_ids:Vector long = MsgsAck"
+  );
+  assert_eq!(
+    text("tuple {t:Type} {n:#} [ t ] = Tuple t n;"),
+    "tuple t:Type n:# [ t ] = Tuple t n"
+  );
+  assert_eq!(
+    text("blob data:bytes = Blob;"),
+    text("blob data:string = Blob;")
+  );
+  assert_eq!(
+    text("user flags:# id:int bot:flags.0?true name:flags.1?string = User;"),
+    "user flags:# id:int name:flags.1?string = User"
+  );
+}
+
+#[test]
+fn verify_reports_mismatches() {
+  let program =
+    parse_tl("boolFalse#bc799737 = Bool;\nboolTrue#12345678 = Bool;\ntrue = True;").unwrap();
+  let diagnostics = verify_ids(&[("a.tl", &program)]);
+  assert_eq!(diagnostics.len(), 1);
+  assert_eq!(diagnostics[0].kind, DiagnosticKind::IdMismatch);
+  assert_eq!(
+    diagnostics[0].message,
+    "`boolTrue` is declared with ID #12345678 but its declaration hashes to #997275b5"
+  );
+  assert_eq!(
+    diagnostics[0].locations[0].to_string(),
+    "a.tl: block 1, declaration 2"
+  );
+}
+
+#[test]
+fn fix_rewrites_only_wrong_ids() {
+  let source = "// base types
+boolFalse#bc799737 = Bool;
+boolTrue#12345678   = Bool;
+
+true#3fedd339 = True;
+";
+  let fixed = fix_ids(source).unwrap();
+  assert_eq!(fixed, source.replace("12345678", "997275b5"));
+  assert_eq!(fix_ids(&fixed).unwrap(), fixed);
+}
+
+#[test]
+fn fix_needs_a_valid_schema() {
+  assert!(fix_ids("boolTrue#997275b5 = ;").is_err());
+}
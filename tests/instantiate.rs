@@ -0,0 +1,121 @@
+mod common;
+
+use tl_steam::runtime::Scope;
+use tl_steam::schema::{Count, Field, FieldType, InstantiateError, Schema};
+use tl_steam::types::TLType;
+
+const SOURCE: &str = "tuple#9770768a {t:Type} {n:#} [ t ] = Tuple t n;
+user#abcdef12 id:int name:string = User;
+box#1a2b3c4d {t:Type} value:t = Box t int;
+";
+
+/// Types of the fields of the single constructor of `instance`, with
+/// `[ ... ]` blocks as the count and the type of their one field.
+fn field_types(schema: &Schema, instance: &str) -> Vec<(Option<Count>, TLType)> {
+  let instances = schema.instantiate(&common::ty(instance)).unwrap();
+  assert_eq!(instances.len(), 1);
+  assert!(instances[0].params.is_empty());
+  assert_eq!(instances[0].result_type, common::ty(instance));
+  instances[0]
+    .fields
+    .iter()
+    .map(|field| match &field.ty {
+      FieldType::Type(ty) => (None, ty.clone()),
+      FieldType::Repeat(count, block) => match &block[..] {
+        [Field {
+          ty: FieldType::Type(ty),
+          ..
+        }] => (Some(count.clone()), ty.clone()),
+        block => panic!("unexpected block {:?}", block),
+      },
+    })
+    .collect()
+}
+
+#[test]
+fn vector_of_user() {
+  let schema = common::schema(SOURCE);
+  assert_eq!(
+    field_types(&schema, "Vector User"),
+    vec![
+      (None, TLType::NatType),
+      (Some(Count::Field(0)), common::ty("User")),
+    ]
+  );
+}
+
+#[test]
+fn tuple_of_three() {
+  let schema = common::schema(SOURCE);
+  assert_eq!(
+    field_types(&schema, "Tuple int 3"),
+    vec![(Some(Count::Expr(TLType::Nat(3))), common::ty("int"))]
+  );
+}
+
+#[test]
+fn wrong_arity() {
+  let schema = common::schema(SOURCE);
+  assert_eq!(
+    schema.instantiate(&common::ty("Vector")).unwrap_err(),
+    InstantiateError::WrongArity {
+      ty: "Vector".to_string(),
+      expected: 1,
+      found: 0,
+    }
+  );
+  assert_eq!(
+    schema
+      .instantiate(&common::ty("Tuple int 3 4"))
+      .unwrap_err(),
+    InstantiateError::WrongArity {
+      ty: "Tuple".to_string(),
+      expected: 2,
+      found: 3,
+    }
+  );
+}
+
+#[test]
+fn wrong_kind() {
+  let schema = common::schema(SOURCE);
+  assert_eq!(
+    schema
+      .instantiate(&common::ty("Tuple int User"))
+      .unwrap_err(),
+    InstantiateError::WrongKind {
+      ty: "Tuple int User".to_string(),
+      param: "n".to_string(),
+    }
+  );
+  assert_eq!(
+    schema.instantiate(&common::ty("Tuple 3 3")).unwrap_err(),
+    InstantiateError::WrongKind {
+      ty: "Tuple 3 3".to_string(),
+      param: "t".to_string(),
+    }
+  );
+}
+
+#[test]
+fn scope_with_some_params() {
+  let schema = common::schema(SOURCE);
+  let tuple = schema.constructor("tuple").unwrap();
+  let scope = Scope::for_combinator(tuple, &[common::ty("User")]);
+  assert_eq!(scope.substitute(&common::ty("t")), common::ty("User"));
+  assert_eq!(scope.nat("n"), None);
+
+  let scope = Scope::for_combinator(tuple, &[common::ty("User"), TLType::Nat(3)]);
+  assert_eq!(scope.substitute(&common::ty("t")), common::ty("User"));
+  assert_eq!(scope.nat("n"), Some(3));
+}
+
+#[test]
+fn scope_with_a_mismatched_param() {
+  let schema = common::schema(SOURCE);
+  let boxed = schema.constructor("box").unwrap();
+  // `string` does not match the `int` of `Box t int`, but `t` is still
+  // bound.
+  let scope = Scope::for_combinator(boxed, &[common::ty("User"), common::ty("string")]);
+  assert_eq!(scope.substitute(&common::ty("t")), common::ty("User"));
+}
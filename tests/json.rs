@@ -0,0 +1,136 @@
+mod common;
+
+use serde_json::json;
+use tl_steam::runtime::json::{
+  binary_to_json, from_json, from_json_as, json_to_binary, to_json, JsonError,
+};
+use tl_steam::runtime::ser::serialize;
+use tl_steam::runtime::{Object, Value};
+use tl_steam::types::TLType;
+
+const SOURCE: &str = "chatPhotoEmpty#37c1011c = ChatPhoto;
+chatPhoto#1c6e1c11 small:bytes = ChatPhoto;
+chat#6e9c9bc7 id:long title:string photo:ChatPhoto = Chat;
+chats#7e4d3a2b chats:(Vector Chat) = Chats;
+points#8a1b2c3d n:# xs:n*[ x:int y:int ] = Points;
+---functions---
+getChat#5a7e1f0c id:long = Chat;
+";
+
+fn chat(id: i64, photo: serde_json::Value) -> serde_json::Value {
+  json!({"@type": "chat", "id": id.to_string(), "title": "t", "photo": photo})
+}
+
+fn invalid(path: &str, message: &str) -> JsonError {
+  JsonError::Invalid {
+    path: path.to_string(),
+    message: message.to_string(),
+  }
+}
+
+#[test]
+fn long_as_a_string() {
+  let schema = common::schema(SOURCE);
+  let id = (1i64 << 60) + 1;
+  let value = Value::Object(
+    Object::new("chat")
+      .with("id", Value::Long(id))
+      .with("title", Value::String("t".into()))
+      .with("photo", Value::Object(Object::new("chatPhotoEmpty"))),
+  );
+  let json = to_json(&schema, &value).unwrap();
+  assert_eq!(json["id"], json!("1152921504606846977"));
+  assert_eq!(from_json(&schema, &json), Ok(value.clone()));
+
+  let bytes = serialize(&schema, &value).unwrap();
+  let text = binary_to_json(&schema, &bytes).unwrap();
+  assert_eq!(json_to_binary(&schema, &text), Ok(bytes));
+
+  let mut number = json.clone();
+  number["id"] = json!(12);
+  match from_json(&schema, &number) {
+    Ok(Value::Object(object)) => assert_eq!(object.get("id"), Some(&Value::Long(12))),
+    other => panic!("expected a chat, got {:?}", other),
+  }
+}
+
+#[test]
+fn bytes_as_base64() {
+  let schema = common::schema(SOURCE);
+  let photo = json!({"@type": "chatPhoto", "small": "AAEC/w=="});
+  let ty = TLType::parse("ChatPhoto").unwrap();
+  let value = from_json_as(&schema, &ty, &photo).unwrap();
+  match &value {
+    Value::Object(object) => {
+      assert_eq!(
+        object.get("small"),
+        Some(&Value::Bytes(vec![0, 1, 2, 255].into()))
+      )
+    }
+    other => panic!("expected a photo, got {:?}", other),
+  }
+  assert_eq!(to_json(&schema, &value), Ok(photo));
+
+  let bad = json!({"@type": "chatPhoto", "small": "not base64!"});
+  match from_json_as(&schema, &ty, &bad) {
+    Err(JsonError::Invalid { path, message }) => {
+      assert_eq!(path, "$.small");
+      assert!(message.starts_with("invalid base64"), "{}", message);
+    }
+    other => panic!("expected invalid base64, got {:?}", other),
+  }
+}
+
+#[test]
+fn extra_is_ignored() {
+  let schema = common::schema(SOURCE);
+  let plain = json!({"@type": "getChat", "id": "5"});
+  let extra = json!({"@type": "getChat", "id": "5", "@extra": {"request": 17}});
+  assert_eq!(from_json(&schema, &extra), from_json(&schema, &plain));
+  assert!(from_json(&schema, &plain).is_ok());
+}
+
+#[test]
+fn records_without_type() {
+  let schema = common::schema(SOURCE);
+  let points = json!({"@type": "points", "xs": [{"x": 1, "y": 2}, {"x": 3, "y": 4}]});
+  let value = from_json(&schema, &points).unwrap();
+  assert_eq!(to_json(&schema, &value), Ok(points));
+}
+
+#[test]
+fn error_paths() {
+  let schema = common::schema(SOURCE);
+  let empty = json!({"@type": "chatPhotoEmpty"});
+  let mut chats = vec![
+    chat(1, empty.clone()),
+    chat(2, empty.clone()),
+    chat(3, empty),
+  ];
+  chats.push(chat(4, json!({"@type": "nope"})));
+  let json = json!({"@type": "chats", "chats": chats});
+  let err = from_json(&schema, &json).unwrap_err();
+  assert_eq!(err, invalid("$.chats[3].photo", "unknown @type `nope`"));
+  assert_eq!(err.to_string(), "$.chats[3].photo: unknown @type `nope`");
+
+  let json = json!({"@type": "chats", "chats": [chat(1, json!({"@type": "chat"}))]});
+  assert_eq!(
+    from_json(&schema, &json),
+    Err(invalid(
+      "$.chats[0].photo",
+      "`chat` does not build type `ChatPhoto`"
+    ))
+  );
+
+  let json = json!({"@type": "chats", "chats": [{"id": "1"}]});
+  assert_eq!(
+    from_json(&schema, &json),
+    Err(invalid("$.chats[0]", "missing @type"))
+  );
+
+  let json = chat(1, json!({"@type": "chatPhoto", "small": 5}));
+  match from_json(&schema, &json) {
+    Err(JsonError::Invalid { path, .. }) => assert_eq!(path, "$.photo.small"),
+    other => panic!("expected an invalid value, got {:?}", other),
+  }
+}
@@ -0,0 +1,161 @@
+use tl_steam::lint::{lint, LintConfig, Severity};
+use tl_steam::parser::parse_tl;
+
+/// Rule and message of each lint `config` reports for `source`.
+fn lints_with(source: &str, config: &LintConfig) -> Vec<(&'static str, String)> {
+  let program = parse_tl(source).unwrap();
+  lint(&[("a.tl", source, &program)], config)
+    .into_iter()
+    .map(|lint| (lint.rule, lint.message))
+    .collect()
+}
+
+fn lints(source: &str) -> Vec<(&'static str, String)> {
+  lints_with(source, &LintConfig::default())
+}
+
+fn lint_of(rule: &'static str, message: &str) -> (&'static str, String) {
+  (rule, message.to_string())
+}
+
+#[test]
+fn clean_schema() {
+  let source = "user flags:# id:# first_name:flags.0?# last_name:flags.1?# = User;
+auth.sentCode phone_code_hash:# = auth.SentCode;
+---functions---
+users.getUsers id:# = User;";
+  assert_eq!(lints(source), vec![]);
+}
+
+#[test]
+fn constructor_case() {
+  assert_eq!(
+    lints("user_full id:# = UserFull;\n---functions---\nget_user id:# = UserFull;"),
+    vec![
+      lint_of("constructor_case", "`user_full` is not in lowerCamelCase"),
+      lint_of("constructor_case", "`get_user` is not in lowerCamelCase"),
+    ]
+  );
+}
+
+#[test]
+fn type_case() {
+  assert_eq!(
+    lints("userFull id:# = User_full;"),
+    vec![lint_of(
+      "type_case",
+      "type `User_full` is not in UpperCamelCase"
+    )]
+  );
+}
+
+#[test]
+fn field_case() {
+  assert_eq!(
+    lints("user firstName:# n:# Xs:n*[ X:# ] = User;"),
+    vec![
+      lint_of("field_case", "field `firstName` contains uppercase letters"),
+      lint_of("field_case", "field `Xs` contains uppercase letters"),
+      lint_of("field_case", "field `X` contains uppercase letters"),
+    ]
+  );
+}
+
+#[test]
+fn flag_bits_skipped() {
+  assert_eq!(
+    lints("user flags:# a:flags.0?# d:flags.3?# = User;"),
+    vec![lint_of(
+      "flag_bits_skipped",
+      "`user` skips bit(s) 1, 2 of `flags`"
+    )]
+  );
+}
+
+#[test]
+fn flags_field_name() {
+  assert_eq!(
+    lints("user options:# flags2:# a:options.0?# b:flags2.0?# = User;"),
+    vec![lint_of(
+      "flags_field_name",
+      "flags field `options` is not named `flags`"
+    )]
+  );
+}
+
+#[test]
+fn mixed_namespaces() {
+  assert_eq!(
+    lints("auth.sentCode id:# = SentCode;\nsentCode id:# = auth.SentCode;"),
+    vec![
+      lint_of(
+        "mixed_namespaces",
+        "constructor `auth.sentCode` is not in the namespace of its type `SentCode`"
+      ),
+      lint_of(
+        "mixed_namespaces",
+        "constructor `sentCode` is not in the namespace of its type `auth.SentCode`"
+      ),
+    ]
+  );
+}
+
+#[test]
+fn config_sets_severities() {
+  let config = LintConfig::parse(
+    "# style
+constructor_case = allow
+
+type_case=error
+",
+  )
+  .unwrap();
+  let source = "user_full id:# = User_full;";
+  assert_eq!(
+    lints_with(source, &config),
+    vec![lint_of(
+      "type_case",
+      "type `User_full` is not in UpperCamelCase"
+    )]
+  );
+  let program = parse_tl(source).unwrap();
+  let found = lint(&[("a.tl", source, &program)], &config);
+  assert_eq!(found[0].severity, Severity::Error);
+  assert_eq!(
+    found[0].to_string(),
+    "error[type_case]: type `User_full` is not in UpperCamelCase\n  --> a.tl: block 1, declaration 1"
+  );
+}
+
+#[test]
+fn config_errors() {
+  assert_eq!(
+    LintConfig::parse("type_case = warn\nconstructor_case").unwrap_err(),
+    "line 2: expected `rule = level`"
+  );
+  assert_eq!(
+    LintConfig::parse("camel_case = warn").unwrap_err(),
+    "line 1: unknown lint rule `camel_case`"
+  );
+  assert_eq!(
+    LintConfig::parse("type_case = loud").unwrap_err(),
+    "line 1: unknown lint level `loud`"
+  );
+}
+
+#[test]
+fn suppression() {
+  let source = "// lint: allow(constructor_case, field_case)
+user_full firstName:# = UserFull;
+user_empty id:# = UserFull;
+---functions---
+// lint: allow(type_case)
+get_user id:# = UserFull;";
+  assert_eq!(
+    lints(source),
+    vec![
+      lint_of("constructor_case", "`user_empty` is not in lowerCamelCase"),
+      lint_of("constructor_case", "`get_user` is not in lowerCamelCase"),
+    ]
+  );
+}
@@ -0,0 +1,56 @@
+use tl_steam::ast::*;
+use tl_steam::parser::parse_tl;
+use tl_steam::types::TLType;
+
+fn first_arg(source: &str) -> TLExpression {
+  let program = parse_tl(source).unwrap();
+  match program.blocks.first() {
+    Some(TLDeclarationBlock::Types(declarations)) => match declarations.first() {
+      Some(TLDeclaration::Combinator(combinator)) => match combinator.args.first() {
+        Some(TLArg::Arg(_, expr)) => expr.clone(),
+        arg => panic!("unexpected argument {:?}", arg),
+      },
+      declaration => panic!("unexpected declaration {:?}", declaration),
+    },
+    block => panic!("unexpected block {:?}", block),
+  }
+}
+
+fn find_operator(expr: &TLExpression) -> Option<&TLOperator> {
+  match expr {
+    TLExpression::Operator(operator, _) => Some(operator),
+    TLExpression::Expression(items) => items.iter().find_map(find_operator),
+    _ => None,
+  }
+}
+
+#[test]
+fn percent_parses_as_bare() {
+  let expr = first_arg("wrapper value:%Int = Wrapper;");
+  assert!(matches!(find_operator(&expr), Some(TLOperator::Bare)));
+  assert_eq!(
+    TLType::from_expression(&expr),
+    TLType::Bare(Box::new(TLType::Named("Int".to_string(), vec![])))
+  );
+}
+
+#[test]
+fn bang_parses_as_bang() {
+  let source = "unit = Unit;\n---functions---\ninvoke {X:Type} query:!X = X;";
+  let program = parse_tl(source).unwrap();
+  let args = match program.blocks.get(1) {
+    Some(TLDeclarationBlock::Functions(declarations)) => match declarations.first() {
+      Some(TLDeclaration::Combinator(combinator)) => &combinator.args,
+      declaration => panic!("unexpected declaration {:?}", declaration),
+    },
+    block => panic!("unexpected block {:?}", block),
+  };
+  let query = args
+    .iter()
+    .find_map(|arg| match arg {
+      TLArg::Arg(_, expr) => Some(expr),
+      _ => None,
+    })
+    .unwrap();
+  assert!(matches!(find_operator(query), Some(TLOperator::Bang)));
+}
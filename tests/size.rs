@@ -0,0 +1,162 @@
+mod common;
+
+use tl_steam::runtime::codec::{Codec, Codecs};
+use tl_steam::runtime::ser::{serialize, EncodeError, Serializer};
+use tl_steam::runtime::size::{
+  combinator_sizes, encoded_len, encoded_len_as, encoded_len_as_with_codecs,
+  encoded_len_with_codecs, type_size, type_size_with_codecs, Size, Sizes,
+};
+use tl_steam::runtime::{Object, Value};
+use tl_steam::types::TLType;
+
+const SOURCE: &str = "point ? = Point;
+message#5c8f1a0e id:int text:string = Message;
+withPoint#11223344 p:point = WithPoint;
+pair#01010101 a:int b:long = Pair;
+maybe#02020202 flags:# x:flags.0?int = Maybe;
+named#03030303 name:string = Named;
+listNil#04040404 = List;
+listCons#05050505 head:int tail:List = List;
+nil {t:Type} = Foo t;
+cons {t:Type} x:t rest:(Foo (Foo t)) = Foo t;
+";
+
+#[test]
+fn length_of_serialized_bytes() {
+  let schema = common::schema(SOURCE);
+  let message = Value::Object(
+    Object::new("message")
+      .with("id", Value::Int(1))
+      .with("text", Value::String("x".repeat(300).into())),
+  );
+  let length = serialize(&schema, &message).unwrap().len();
+  assert_eq!(encoded_len(&schema, &message), Ok(length));
+  let ty = TLType::Named("Message".to_string(), vec![]);
+  assert_eq!(encoded_len_as(&schema, &ty, &message), Ok(length));
+}
+
+#[test]
+fn length_with_codecs() {
+  let schema = common::schema(SOURCE);
+  let mut codecs = Codecs::default();
+  codecs.register(Codec::new(
+    "point",
+    |value: &Value, out: &mut Vec<u8>| match value {
+      Value::Vector(xy) if xy.len() == 2 => {
+        out.extend_from_slice(&[0; 8]);
+        Ok(())
+      }
+      _ => Err(EncodeError::WrongValue {
+        expected: "point".to_string(),
+        found: value.kind(),
+      }),
+    },
+    |reader| {
+      reader.take(8)?;
+      Ok(Value::Vector(vec![Value::Int(0), Value::Int(0)]))
+    },
+  ));
+  let value = Value::Object(
+    Object::new("withPoint").with("p", Value::Vector(vec![Value::Int(0), Value::Int(0)])),
+  );
+
+  let mut serializer = Serializer::with_codecs(&schema, &codecs);
+  serializer.write_object(&value).unwrap();
+  assert_eq!(serializer.finish().len(), 12);
+  assert_eq!(encoded_len_with_codecs(&schema, &codecs, &value), Ok(12));
+  let ty = TLType::Named("WithPoint".to_string(), vec![]);
+  assert_eq!(
+    encoded_len_as_with_codecs(&schema, &codecs, &ty, &value),
+    Ok(12)
+  );
+  assert!(encoded_len(&schema, &value).is_err());
+}
+
+fn bounds(min: usize, max: Option<usize>) -> Size {
+  Size { min, max }
+}
+
+#[test]
+fn type_sizes() {
+  let schema = common::schema(SOURCE);
+  let size = |ty| type_size(&schema, &common::ty(ty));
+  assert_eq!(size("Pair"), Size::fixed(16));
+  assert_eq!(size("%Pair"), Size::fixed(12));
+  assert_eq!(size("Maybe"), bounds(8, Some(12)));
+  assert_eq!(size("Named"), bounds(8, None));
+  assert_eq!(size("List"), bounds(4, None));
+  assert_eq!(size("Vector int"), bounds(8, None));
+
+  assert_eq!(Size::fixed(16).to_string(), "16 bytes");
+  assert_eq!(bounds(8, Some(12)).to_string(), "8 to 12 bytes");
+  assert_eq!(bounds(8, None).to_string(), "at least 8 bytes");
+}
+
+#[test]
+fn sizes_of_combinators() {
+  let schema = common::schema(SOURCE);
+  let sizes = combinator_sizes(&schema);
+  let size = |name: &str| {
+    sizes
+      .iter()
+      .find(|(combinator, _)| combinator.name == name)
+      .map(|(_, size)| *size)
+      .unwrap()
+  };
+  assert_eq!(size("int"), Size::fixed(8));
+  assert_eq!(size("pair"), Size::fixed(16));
+  assert_eq!(size("maybe"), bounds(8, Some(12)));
+  assert_eq!(size("listNil"), Size::fixed(4));
+  assert_eq!(size("listCons"), bounds(12, None));
+
+  let mut sizes = Sizes::new(&schema);
+  assert_eq!(sizes.type_size(&common::ty("Pair")), Size::fixed(16));
+  assert_eq!(sizes.type_size(&common::ty("List")), bounds(4, None));
+}
+
+#[test]
+fn polymorphic_recursion_is_unbounded() {
+  let schema = common::schema(SOURCE);
+  let sizes = combinator_sizes(&schema);
+  let size = |name: &str| {
+    sizes
+      .iter()
+      .find(|(combinator, _)| combinator.name == name)
+      .map(|(_, size)| *size)
+      .unwrap()
+  };
+  assert_eq!(size("nil"), Size::fixed(4));
+  assert_eq!(size("cons"), bounds(8, None));
+  assert_eq!(type_size(&schema, &common::ty("Foo int")), bounds(4, None));
+}
+
+#[test]
+fn builtin_sizes_come_from_codecs() {
+  let schema = common::schema(SOURCE);
+  let ty = common::ty("WithPoint");
+  assert_eq!(type_size(&schema, &ty), bounds(4, None));
+
+  let mut codecs = Codecs::default();
+  codecs.register(
+    Codec::new(
+      "point",
+      |_: &Value, out: &mut Vec<u8>| {
+        out.extend_from_slice(&[0; 8]);
+        Ok(())
+      },
+      |reader| {
+        reader.take(8)?;
+        Ok(Value::Vector(vec![]))
+      },
+    )
+    .with_size(Size::fixed(8)),
+  );
+  assert_eq!(
+    type_size_with_codecs(&schema, &codecs, &ty),
+    Size::fixed(12)
+  );
+  assert_eq!(
+    Sizes::with_codecs(&schema, &codecs).type_size(&ty),
+    Size::fixed(12)
+  );
+}
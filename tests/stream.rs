@@ -0,0 +1,91 @@
+mod common;
+
+use tl_steam::runtime::de::DecodeError;
+use tl_steam::runtime::ser::serialize;
+use tl_steam::runtime::stream::{Decoded, StreamDecoder};
+use tl_steam::runtime::{Object, Value};
+
+const SOURCE: &str = "message#5c8f1a0e id:int text:string = Message;
+";
+
+fn message(id: i32, text: &str) -> Value<'static> {
+  Value::Object(
+    Object::new("message")
+      .with("id", Value::Int(id))
+      .with("text", Value::String(text.to_string().into())),
+  )
+}
+
+#[test]
+fn values_split_across_feeds() {
+  let schema = common::schema(SOURCE);
+  let mut input = serialize(&schema, &message(1, "hello")).unwrap();
+  input.extend(serialize(&schema, &message(2, "world")).unwrap());
+
+  let mut decoder = StreamDecoder::new(&schema);
+  let mut decoded = vec![];
+  for byte in &input {
+    decoder.feed(&[*byte]);
+    while let Decoded::Object(value) = decoder.next_object().unwrap() {
+      decoded.push(value);
+    }
+  }
+  assert_eq!(decoded, vec![message(1, "hello"), message(2, "world")]);
+  assert_eq!(decoder.buffered(), 0);
+}
+
+#[test]
+fn several_values_in_one_feed() {
+  let schema = common::schema(SOURCE);
+  let mut input = serialize(&schema, &message(1, "a")).unwrap();
+  input.extend(serialize(&schema, &message(2, "b")).unwrap());
+  input.extend(&[0x0e, 0x1a]);
+
+  let mut decoder = StreamDecoder::new(&schema);
+  decoder.feed(&input);
+  assert_eq!(decoder.next_object(), Ok(Decoded::Object(message(1, "a"))));
+  assert_eq!(decoder.next_object(), Ok(Decoded::Object(message(2, "b"))));
+  assert_eq!(decoder.next_object(), Ok(Decoded::NeedMoreData));
+  assert_eq!(decoder.buffered(), 2);
+  decoder.feed(&serialize(&schema, &message(3, "c")).unwrap()[2..]);
+  assert_eq!(decoder.next_object(), Ok(Decoded::Object(message(3, "c"))));
+}
+
+#[test]
+fn error_poisons_until_skipped() {
+  let schema = common::schema(SOURCE);
+  let mut decoder = StreamDecoder::new(&schema);
+  decoder.feed(&[0xef, 0xbe, 0xad, 0xde]);
+  decoder.feed(&serialize(&schema, &message(1, "ok")).unwrap());
+
+  let err = decoder.next_object().unwrap_err();
+  assert!(matches!(
+    err,
+    DecodeError::UnknownConstructor {
+      id: 0xdead_beef,
+      ..
+    }
+  ));
+  decoder.feed(&[]);
+  assert_eq!(decoder.next_object(), Err(err));
+
+  decoder.skip(4);
+  assert_eq!(decoder.next_object(), Ok(Decoded::Object(message(1, "ok"))));
+  assert_eq!(decoder.next_object(), Ok(Decoded::NeedMoreData));
+}
+
+#[test]
+fn reset_drops_everything() {
+  let schema = common::schema(SOURCE);
+  let mut decoder = StreamDecoder::new(&schema);
+  decoder.feed(&[0xef, 0xbe, 0xad, 0xde, 1, 2, 3]);
+  assert!(decoder.next_object().is_err());
+  decoder.reset();
+  assert_eq!(decoder.buffered(), 0);
+  assert_eq!(decoder.next_object(), Ok(Decoded::NeedMoreData));
+  decoder.feed(&serialize(&schema, &message(7, "again")).unwrap());
+  assert_eq!(
+    decoder.next_object(),
+    Ok(Decoded::Object(message(7, "again")))
+  );
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use tl_steam::runtime::de::{deserialize, deserialize_as};
+use tl_steam::runtime::ser::{serialize, serialize_as};
+use tl_steam::runtime::text::{parse, parse_as, print, TextError};
+use tl_steam::runtime::Value;
+use tl_steam::schema::Schema;
+use tl_steam::types::TLType;
+
+const SOURCE: &str = "userPhotoEmpty#4f11bae1 = UserPhoto;
+userPhoto#1a2b3c4d id:long = UserPhoto;
+user#abcdef12 flags:# id:int first_name:flags.0?string photo:flags.1?UserPhoto bot:flags.2?true = User;
+userEmpty#d3bc4b7a id:int = User;
+inputList#5a6b7c8d n:# data:n*[ x:int y:string ] = InputList;
+pair#6a3f9c01 a:User b:int c:long = Pair;
+keys#7b8c9d0e small:int128 big:int256 = Keys;
+blob#0e0f1011 data:bytes ratio:double = Blob;
+";
+
+/// Reads `text`, writes and reads it back through binary, and checks that
+/// it prints as `text` again.
+fn round_trip(schema: &Schema, ty: Option<&str>, text: &str) -> Value<'static> {
+  let ty = ty.map(|ty| TLType::parse(ty).unwrap());
+  let value = match &ty {
+    Some(ty) => parse_as(schema, ty, text),
+    None => parse(schema, text),
+  }
+  .unwrap_or_else(|err| panic!("{}: {}", text, err));
+  let bytes = match &ty {
+    Some(ty) => serialize_as(schema, ty, &value),
+    None => serialize(schema, &value),
+  }
+  .unwrap();
+  let decoded = match &ty {
+    Some(ty) => deserialize_as(schema, ty, &bytes),
+    None => deserialize(schema, &bytes),
+  }
+  .unwrap();
+  assert_eq!(print(schema, &decoded), text);
+  value
+}
+
+fn error(schema: &Schema, text: &str) -> TextError {
+  match parse(schema, text) {
+    Ok(value) => panic!("{}: parsed as {:?}", text, value),
+    Err(err) => err,
+  }
+}
+
+#[test]
+fn objects() {
+  let schema = common::schema(SOURCE);
+  round_trip(&schema, None, "userEmpty id:-7");
+  round_trip(
+    &schema,
+    None,
+    r#"user id:42 first_name:"Ann \"A\"\n" photo:(userPhoto id:-5)"#,
+  );
+  round_trip(
+    &schema,
+    Some("Vector User"),
+    r#"[(userEmpty id:1) (user id:2 first_name:"x")]"#,
+  );
+  round_trip(&schema, Some("%Pair"), "pair a:(userEmpty id:3) b:4 c:5");
+  round_trip(&schema, Some("Bool"), "true");
+  assert_eq!(
+    parse_as(&schema, &TLType::parse("Bool").unwrap(), "boolTrue"),
+    Ok(Value::Bool(true))
+  );
+}
+
+#[test]
+fn flags() {
+  let schema = common::schema(SOURCE);
+  let value = round_trip(&schema, None, "user id:1 photo:(userPhotoEmpty) bot:true");
+  let bytes = serialize(&schema, &value).unwrap();
+  // `flags` is computed from the fields present: bits 1 and 2.
+  assert_eq!(bytes[4..8], [0b110, 0, 0, 0]);
+  round_trip(&schema, None, "user id:1");
+}
+
+#[test]
+fn records() {
+  let schema = common::schema(SOURCE);
+  let value = round_trip(&schema, None, r#"inputList data:[(x:1 y:"a") (x:2 y:"b")]"#);
+  let bytes = serialize(&schema, &value).unwrap();
+  // `n` is the number of records.
+  assert_eq!(bytes[4..8], [2, 0, 0, 0]);
+  round_trip(&schema, None, "inputList data:[]");
+}
+
+#[test]
+fn bytes_escapes() {
+  let schema = common::schema(SOURCE);
+  let value = round_trip(&schema, None, r#"blob data:b"\xff\x00ab\\c\"" ratio:1.5"#);
+  let bytes = serialize(&schema, &value).unwrap();
+  assert_eq!(bytes[4..12], [7, 0xff, 0, b'a', b'b', b'\\', b'c', b'"']);
+}
+
+#[test]
+fn wide_integers() {
+  let schema = common::schema(SOURCE);
+  let small = "0x00112233445566778899aabbccddeeff";
+  let big = "0xffeeddccbbaa99887766554433221100ffeeddccbbaa99887766554433221100";
+  let value = round_trip(&schema, None, &format!("keys small:{} big:{}", small, big));
+  let bytes = serialize(&schema, &value).unwrap();
+  // Wire order: the bytes as written.
+  assert_eq!(bytes[4..8], [0x00, 0x11, 0x22, 0x33]);
+  assert_eq!(bytes[20..24], [0xff, 0xee, 0xdd, 0xcc]);
+  assert_eq!(
+    error(&schema, &format!("keys small:0x0011 big:{}", big)).column,
+    12
+  );
+}
+
+#[test]
+fn unknown_objects() {
+  let schema = common::schema(SOURCE);
+  let value = parse(
+    &schema,
+    r#"pair a:(#11223344 b"\x01\x00\x00\x00") b:1 c:-9223372036854775808"#,
+  )
+  .unwrap();
+  let expected = Value::Unknown {
+    id: 0x1122_3344,
+    raw: vec![1, 0, 0, 0].into(),
+  };
+  match &value {
+    Value::Object(object) => assert_eq!(object.get("a"), Some(&expected)),
+    other => panic!("expected a pair, got {:?}", other),
+  }
+  assert_eq!(
+    print(&schema, &value),
+    r#"pair a:(#11223344 b"\x01\x00\x00\x00") b:1 c:-9223372036854775808"#
+  );
+}
+
+#[test]
+fn error_positions() {
+  let schema = common::schema(SOURCE);
+  let cases = [
+    ("user id:\"x\"", 1, 9),
+    ("user id:1\n  nope:2", 2, 3),
+    ("user id:1\n  first_name:\"abc", 2, 14),
+    ("userEmpty id:1 )", 1, 16),
+    ("nobody", 1, 1),
+  ];
+  for (text, line, column) in cases.iter() {
+    let err = error(&schema, text);
+    assert_eq!(
+      (err.line, err.column),
+      (*line, *column),
+      "{}: {}",
+      text,
+      err
+    );
+  }
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use std::borrow::Cow;
+use tl_steam::runtime::de::{DecodeError, Deserializer, UnknownConstructors};
+use tl_steam::runtime::ser::{serialize, serialize_as};
+use tl_steam::runtime::{Object, Value};
+use tl_steam::schema::Schema;
+use tl_steam::types::TLType;
+
+const SOURCE: &str = "userEmpty#d3bc4b7a id:int = User;
+userName#5e1f0b3c id:int name:string = User;
+pair#6a3f9c01 a:User b:int c:long = Pair;
+named#7b4e8d12 a:User name:string = Named;
+";
+
+/// An object of a constructor the schema does not have, three ints long.
+const UNKNOWN: [u8; 16] = [0x11, 0x22, 0x33, 0x44, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0];
+
+fn unknown() -> Value<'static> {
+  Value::Unknown {
+    id: 0x4433_2211,
+    raw: Cow::Owned(UNKNOWN[4..].to_vec()),
+  }
+}
+
+fn user(id: i32) -> Value<'static> {
+  Value::Object(Object::new("userEmpty").with("id", Value::Int(id)))
+}
+
+fn read<'d>(
+  schema: &Schema,
+  unknown: UnknownConstructors,
+  ty: Option<&TLType>,
+  data: &'d [u8],
+) -> Result<Value<'d>, DecodeError> {
+  let mut deserializer = Deserializer::new(schema, data);
+  deserializer.set_unknown_constructors(unknown);
+  let value = match ty {
+    Some(ty) => deserializer.read(ty)?,
+    None => deserializer.read_object()?,
+  };
+  deserializer.finish()?;
+  Ok(value)
+}
+
+/// A boxed `Vector User` of `items`, each already encoded.
+fn users(items: &[&[u8]]) -> Vec<u8> {
+  let mut data = vec![0x15, 0xc4, 0xb5, 0x1c];
+  data.extend(&(items.len() as u32).to_le_bytes());
+  for item in items {
+    data.extend(*item);
+  }
+  data
+}
+
+#[test]
+fn preserve_round_trips_unknown_objects() {
+  let schema = common::schema(SOURCE);
+  let mut data = schema
+    .constructor("pair")
+    .unwrap()
+    .id
+    .to_le_bytes()
+    .to_vec();
+  data.extend(&UNKNOWN);
+  data.extend(&[9, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8]);
+
+  match read(&schema, UnknownConstructors::Fail, None, &data) {
+    Err(DecodeError::UnknownConstructor {
+      id: 0x4433_2211,
+      offset: 4,
+    }) => {}
+    other => panic!("expected an unknown constructor, got {:?}", other),
+  }
+
+  let value = read(&schema, UnknownConstructors::Preserve, None, &data).unwrap();
+  let expected = Object::new("pair")
+    .with("a", unknown())
+    .with("b", Value::Int(9))
+    .with("c", Value::Long(0x0807_0605_0403_0201));
+  assert_eq!(value, Value::Object(expected));
+  assert_eq!(serialize(&schema, &value).unwrap(), data);
+}
+
+#[test]
+fn preserve_keeps_a_trailing_unknown_object() {
+  let schema = common::schema(SOURCE);
+  let ty = TLType::parse("Vector User").unwrap();
+  let known = serialize(&schema, &user(7)).unwrap();
+
+  let last = users(&[&known, &UNKNOWN]);
+  let value = read(&schema, UnknownConstructors::Preserve, Some(&ty), &last).unwrap();
+  assert_eq!(value, Value::Vector(vec![user(7), unknown()]));
+  assert_eq!(serialize_as(&schema, &ty, &value).unwrap(), last);
+}
+
+#[test]
+fn scan_recovers_known_objects() {
+  let schema = common::schema(SOURCE);
+  let ty = TLType::parse("Vector User").unwrap();
+  let named = Value::Object(
+    Object::new("userName")
+      .with("id", Value::Int(8))
+      .with("name", Value::String("Ann".into())),
+  );
+  let known = serialize(&schema, &named).unwrap();
+
+  // A string follows, so where the unknown object ends is not known.
+  let data = users(&[&UNKNOWN, &known]);
+  match read(&schema, UnknownConstructors::Preserve, Some(&ty), &data) {
+    Err(DecodeError::UnknownConstructor { offset: 8, .. }) => {}
+    other => panic!("expected an unknown constructor, got {:?}", other),
+  }
+  let value = read(&schema, UnknownConstructors::Scan, Some(&ty), &data).unwrap();
+  assert_eq!(value, Value::Vector(vec![unknown(), named]));
+  assert_eq!(serialize_as(&schema, &ty, &value).unwrap(), data);
+}
+
+#[test]
+fn preserve_fails_where_an_unknown_object_ends_nowhere_known() {
+  let schema = common::schema(SOURCE);
+  let mut data = schema
+    .constructor("named")
+    .unwrap()
+    .id
+    .to_le_bytes()
+    .to_vec();
+  data.extend(&UNKNOWN);
+  data.extend(&[3, b'A', b'n', b'n']);
+
+  // the string after `a` is not taken for part of the unknown object
+  match read(&schema, UnknownConstructors::Preserve, None, &data) {
+    Err(DecodeError::UnknownConstructor {
+      id: 0x4433_2211,
+      offset: 4,
+    }) => {}
+    other => panic!("expected an unknown constructor, got {:?}", other),
+  }
+}
+
+#[test]
+fn preserve_reads_the_whole_input() {
+  let schema = common::schema(SOURCE);
+  let mut data = serialize(&schema, &user(7)).unwrap();
+  data.extend(&UNKNOWN);
+
+  // the input is one value, so bytes after it are an error rather than
+  // the start of the next value
+  let mut deserializer = Deserializer::new(&schema, &data);
+  deserializer.set_unknown_constructors(UnknownConstructors::Preserve);
+  assert_eq!(
+    deserializer.read_object(),
+    Err(DecodeError::TrailingBytes { offset: 8 })
+  );
+}
@@ -0,0 +1,287 @@
+mod common;
+
+use tl_steam::runtime::validate::{validate, validate_as, Problem, ValidationError};
+use tl_steam::runtime::{Object, Value};
+use tl_steam::types::TLType;
+
+const SOURCE: &str = "point ? = Point;
+user#abcdef12 flags:# id:int name:flags.0?string bot:flags.1?true = User;
+photo#1a2b3c4d id:int = Photo;
+list#03030303 n:# xs:n*[ x:int ] = List;
+rows#04040404 flags:# n:# xs:n*[ note:flags.0?string ] = Rows;
+same#05050505 {t:Type} a:t b:t = Same;
+located#06060606 at:point = Located;
+blob#07070707 data:bytes = Blob;
+foo#08080808 flags:# a:flags.0?int b:int c:string = Foo;
+---functions---
+getUser#11111111 id:int = User;
+invoke#22222222 {X:Type} query:!X = X;
+";
+
+fn error(path: &str, problem: Problem) -> ValidationError {
+  ValidationError {
+    path: path.to_string(),
+    problem,
+  }
+}
+
+#[test]
+fn valid() {
+  let schema = common::schema(SOURCE);
+  let user = Object::new("user")
+    .with("id", Value::Int(1))
+    .with("name", common::string("ann"))
+    .with("bot", Value::Bool(true));
+  assert_eq!(validate(&schema, &Value::Object(user)), vec![]);
+  let call = Object::new("invoke").with(
+    "query",
+    Value::Object(Object::new("getUser").with("id", Value::Int(1))),
+  );
+  assert_eq!(validate(&schema, &Value::Object(call)), vec![]);
+}
+
+#[test]
+fn unknown_names() {
+  let schema = common::schema(SOURCE);
+  let value = Value::Object(Object::new("nope"));
+  assert_eq!(
+    validate(&schema, &value),
+    vec![error("$", Problem::UnknownConstructor("nope".to_string()))]
+  );
+
+  let call = Object::new("invoke").with("query", Value::Object(Object::new("user")));
+  assert_eq!(
+    validate(&schema, &Value::Object(call)),
+    vec![error(
+      "$.query",
+      Problem::UnknownFunction("user".to_string())
+    )]
+  );
+
+  assert_eq!(
+    validate_as(&schema, &common::ty("Missing"), &value),
+    vec![error("$", Problem::UnknownType("Missing".to_string()))]
+  );
+}
+
+#[test]
+fn wrong_constructor_and_value() {
+  let schema = common::schema(SOURCE);
+  let photo = Value::Object(Object::new("photo").with("id", Value::Int(1)));
+  assert_eq!(
+    validate_as(&schema, &common::ty("User"), &photo),
+    vec![error(
+      "$",
+      Problem::WrongConstructor {
+        expected: "User".to_string(),
+        found: "photo".to_string(),
+      }
+    )]
+  );
+
+  let photos = Value::Vector(vec![photo, Value::Int(2)]);
+  assert_eq!(
+    validate_as(&schema, &common::ty("Vector Photo"), &photos),
+    vec![error(
+      "$[1]",
+      Problem::WrongValue {
+        expected: "Photo".to_string(),
+        found: Value::Int(2).kind(),
+      }
+    )]
+  );
+}
+
+#[test]
+fn missing_and_unknown_fields() {
+  let schema = common::schema(SOURCE);
+  let value = Value::Object(Object::new("photo").with("size", Value::Int(1)));
+  assert_eq!(
+    validate(&schema, &value),
+    vec![
+      error("$", Problem::UnknownField("size".to_string())),
+      error("$", Problem::MissingField("id".to_string())),
+    ]
+  );
+}
+
+#[test]
+fn flags() {
+  let schema = common::schema(SOURCE);
+  let user = Object::new("user")
+    .with("flags", Value::Nat(0))
+    .with("id", Value::Int(1))
+    .with("name", common::string("ann"));
+  assert_eq!(
+    validate(&schema, &Value::Object(user)),
+    vec![error(
+      "$.flags",
+      Problem::InconsistentFlags {
+        field: "flags".to_string(),
+        value: 0,
+        expected: 1,
+      }
+    )]
+  );
+
+  // `flags` belongs to `rows`, so the record cannot set its bit.
+  let record = Object::new("").with("note", common::string("hi"));
+  let rows = Object::new("rows")
+    .with("flags", Value::Nat(0))
+    .with("xs", Value::Vector(vec![Value::Object(record)]));
+  assert_eq!(
+    validate(&schema, &Value::Object(rows)),
+    vec![error(
+      "$.xs[0].note",
+      Problem::UnexpectedField {
+        field: "note".to_string(),
+        flags: "flags".to_string(),
+        bit: 0,
+      }
+    )]
+  );
+}
+
+#[test]
+fn every_problem_after_wrong_flags() {
+  let schema = common::schema(SOURCE);
+  let flags = common::string("x");
+  let foo = Object::new("foo")
+    .with("flags", flags.clone())
+    .with("c", Value::Int(1));
+  assert_eq!(
+    validate(&schema, &Value::Object(foo)),
+    vec![
+      error(
+        "$.flags",
+        Problem::WrongValue {
+          expected: "#".to_string(),
+          found: flags.kind(),
+        }
+      ),
+      error("$", Problem::MissingField("b".to_string())),
+      error(
+        "$.c",
+        Problem::WrongValue {
+          expected: "string".to_string(),
+          found: Value::Int(1).kind(),
+        }
+      ),
+    ]
+  );
+
+  // the fields present decide the flags in place of a wrong value, so
+  // `a` is still checked
+  let foo = Object::new("foo")
+    .with("flags", flags.clone())
+    .with("a", common::string("one"))
+    .with("b", Value::Int(2))
+    .with("c", common::string("three"));
+  assert_eq!(
+    validate(&schema, &Value::Object(foo)),
+    vec![
+      error(
+        "$.flags",
+        Problem::WrongValue {
+          expected: "#".to_string(),
+          found: flags.kind(),
+        }
+      ),
+      error(
+        "$.a",
+        Problem::WrongValue {
+          expected: "int".to_string(),
+          found: common::string("one").kind(),
+        }
+      ),
+    ]
+  );
+}
+
+#[test]
+fn counts() {
+  let schema = common::schema(SOURCE);
+  let record = || Value::Object(Object::new("").with("x", Value::Int(1)));
+  let list = Object::new("list")
+    .with("n", Value::Nat(3))
+    .with("xs", Value::Vector(vec![record(), record()]));
+  assert_eq!(
+    validate(&schema, &Value::Object(list)),
+    vec![error(
+      "$.xs",
+      Problem::WrongCount {
+        field: "xs".to_string(),
+        expected: 3,
+        found: 2,
+      }
+    )]
+  );
+
+  let list = Object::new("list")
+    .with("n", common::string("three"))
+    .with("xs", Value::Vector(vec![record()]));
+  assert_eq!(
+    validate(&schema, &Value::Object(list)),
+    vec![
+      error(
+        "$.n",
+        Problem::WrongValue {
+          expected: "#".to_string(),
+          found: common::string("three").kind(),
+        }
+      ),
+      error("$.xs", Problem::UnknownCount("xs".to_string())),
+    ]
+  );
+}
+
+#[test]
+fn inconsistent_param() {
+  let schema = common::schema(SOURCE);
+  let same = Object::new("same")
+    .with("a", Value::Int(1))
+    .with("b", common::string("one"));
+  assert_eq!(
+    validate(&schema, &Value::Object(same)),
+    vec![error(
+      "$.b",
+      Problem::InconsistentParam {
+        param: "t".to_string(),
+        expected: common::ty("int"),
+        found: common::ty("string"),
+      }
+    )]
+  );
+}
+
+#[test]
+fn too_long() {
+  let schema = common::schema(SOURCE);
+  let data = vec![0; 1 << 24];
+  let blob = Object::new("blob").with("data", Value::Bytes(data.into()));
+  assert_eq!(
+    validate(&schema, &Value::Object(blob)),
+    vec![error("$.data", Problem::TooLong(1 << 24))]
+  );
+}
+
+#[test]
+fn unsupported() {
+  let schema = common::schema(SOURCE);
+  let located = Object::new("located").with("at", Value::Int(1));
+  assert_eq!(
+    validate(&schema, &Value::Object(located)),
+    vec![error(
+      "$.at",
+      Problem::Unsupported("the builtin type `point` without a codec".to_string())
+    )]
+  );
+
+  assert_eq!(
+    validate_as(&schema, &TLType::Nat(3), &Value::Nat(3)),
+    vec![error(
+      "$",
+      Problem::Unsupported("a value of type `3`".to_string())
+    )]
+  );
+}
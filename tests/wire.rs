@@ -0,0 +1,326 @@
+mod common;
+
+use std::borrow::Cow;
+use tl_steam::runtime::de::{deserialize, deserialize_as, DecodeError};
+use tl_steam::runtime::ser::{serialize, serialize_as, EncodeError};
+use tl_steam::runtime::{Object, Value};
+use tl_steam::schema::Schema;
+
+const SOURCE: &str = "message#5c8f1a0e id:int text:string = Message;
+ping#7abe77ec ok:true id:int = Ping;
+user#abcdef12 flags:# id:int name:flags.0?string bot:flags.1?true age:flags.3?int = User;
+points#5a6b7c8d n:# xs:n*[ x:int y:string ] = Points;
+ints#6b7c8d9e count:# [ int ] = Ints;
+";
+
+/// Checks that `value` of type `ty` is written as `bytes` and that
+/// `bytes` read back as `value`.
+fn assert_wire(schema: &Schema, ty: &str, value: &Value, bytes: &[u8]) {
+  let ty = common::ty(ty);
+  assert_eq!(serialize_as(schema, &ty, value).unwrap(), bytes, "{}", ty);
+  assert_eq!(
+    &deserialize_as(schema, &ty, bytes).unwrap(),
+    value,
+    "{}",
+    ty
+  );
+}
+
+fn decode_error(schema: &Schema, ty: &str, bytes: &[u8]) -> DecodeError {
+  deserialize_as(schema, &common::ty(ty), bytes).unwrap_err()
+}
+
+fn bytes_value(length: usize) -> Value<'static> {
+  Value::Bytes(Cow::Owned(vec![0xab; length]))
+}
+
+#[test]
+fn short_lengths() {
+  let schema = common::schema(SOURCE);
+  assert_wire(&schema, "bytes", &bytes_value(0), &[0, 0, 0, 0]);
+  assert_wire(&schema, "bytes", &bytes_value(1), &[1, 0xab, 0, 0]);
+  assert_wire(&schema, "bytes", &bytes_value(3), &[3, 0xab, 0xab, 0xab]);
+  assert_wire(&schema, "string", &common::string("abc"), b"\x03abc");
+
+  // 1 + 253 bytes, padded to 256
+  let mut expected = vec![253];
+  expected.extend(vec![0xab; 253]);
+  expected.extend(&[0, 0]);
+  assert_wire(&schema, "bytes", &bytes_value(253), &expected);
+}
+
+#[test]
+fn long_lengths() {
+  let schema = common::schema(SOURCE);
+  // `0xfe`, a three byte length and 254 bytes, padded to 260
+  let mut expected = vec![0xfe, 254, 0, 0];
+  expected.extend(vec![0xab; 254]);
+  expected.extend(&[0, 0]);
+  assert_wire(&schema, "bytes", &bytes_value(254), &expected);
+
+  let mut expected = vec![0xfe, 0x00, 0x01, 0x00];
+  expected.extend(vec![0xab; 256]);
+  assert_wire(&schema, "bytes", &bytes_value(256), &expected);
+}
+
+#[test]
+fn numbers() {
+  let schema = common::schema(SOURCE);
+  assert_wire(&schema, "int", &Value::Int(-2), &[0xfe, 0xff, 0xff, 0xff]);
+  assert_wire(
+    &schema,
+    "long",
+    &Value::Long(0x0102_0304_0506_0708),
+    &[8, 7, 6, 5, 4, 3, 2, 1],
+  );
+  assert_wire(
+    &schema,
+    "double",
+    &Value::Double(1.0),
+    &[0, 0, 0, 0, 0, 0, 0xf0, 0x3f],
+  );
+  assert_wire(&schema, "#", &Value::Nat(7), &[7, 0, 0, 0]);
+}
+
+#[test]
+fn objects_and_vectors() {
+  let schema = common::schema(SOURCE);
+  let message = Value::Object(
+    Object::new("message")
+      .with("id", Value::Int(1))
+      .with("text", common::string("hi")),
+  );
+  let boxed = [
+    0x0e, 0x1a, 0x8f, 0x5c, // message
+    1, 0, 0, 0, // id
+    2, b'h', b'i', 0, // text
+  ];
+  assert_eq!(serialize(&schema, &message).unwrap(), boxed);
+  assert_eq!(deserialize(&schema, &boxed).unwrap(), message);
+  assert_wire(&schema, "Message", &message, &boxed);
+  assert_wire(&schema, "%Message", &message, &boxed[4..]);
+
+  let ints = Value::Vector(vec![Value::Int(1), Value::Int(2)]);
+  assert_wire(
+    &schema,
+    "Vector int",
+    &ints,
+    &[0x15, 0xc4, 0xb5, 0x1c, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0],
+  );
+  assert_wire(
+    &schema,
+    "%Vector int",
+    &ints,
+    &[2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0],
+  );
+}
+
+#[test]
+fn malformed_strings() {
+  let schema = common::schema(SOURCE);
+  assert_eq!(
+    decode_error(&schema, "bytes", &[1, 0xab, 0, 1]),
+    DecodeError::BadPadding { offset: 2 }
+  );
+  assert_eq!(
+    decode_error(&schema, "bytes", &[0xff, 0, 0, 0]),
+    DecodeError::BadLength { offset: 0 }
+  );
+  assert_eq!(
+    decode_error(&schema, "bytes", &[3, 0xab, 0xab]),
+    DecodeError::Truncated {
+      offset: 1,
+      needed: 1
+    }
+  );
+  assert_eq!(
+    decode_error(&schema, "string", &[2, 0xc3, 0x28, 0]),
+    DecodeError::InvalidString { offset: 0 }
+  );
+  assert_eq!(
+    decode_error(&schema, "int", &[1, 0, 0, 0, 0]),
+    DecodeError::TrailingBytes { offset: 4 }
+  );
+}
+
+#[test]
+fn bools() {
+  let schema = common::schema(SOURCE);
+  assert_wire(
+    &schema,
+    "Bool",
+    &Value::Bool(true),
+    &[0xb5, 0x75, 0x72, 0x99],
+  );
+  assert_wire(
+    &schema,
+    "Bool",
+    &Value::Bool(false),
+    &[0x37, 0x97, 0x79, 0xbc],
+  );
+  assert_eq!(
+    serialize_as(
+      &schema,
+      &common::ty("Bool"),
+      &Value::Object(Object::new("boolFalse"))
+    )
+    .unwrap(),
+    [0x37, 0x97, 0x79, 0xbc]
+  );
+  assert_eq!(
+    decode_error(&schema, "Bool", &[0x3f, 0xed, 0xd3, 0x39]),
+    DecodeError::UnknownConstructor {
+      id: 0x39d3_ed3f,
+      offset: 0
+    }
+  );
+}
+
+#[test]
+fn true_takes_no_space() {
+  let schema = common::schema(SOURCE);
+  assert_wire(&schema, "true", &Value::Bool(true), &[]);
+  let ping = Value::Object(
+    Object::new("ping")
+      .with("ok", Value::Bool(true))
+      .with("id", Value::Int(5)),
+  );
+  assert_wire(&schema, "%Ping", &ping, &[5, 0, 0, 0]);
+}
+
+#[test]
+fn computed_flags() {
+  let schema = common::schema(SOURCE);
+  let user = Object::new("user")
+    .with("id", Value::Int(1))
+    .with("name", common::string("al"))
+    .with("bot", Value::Bool(true));
+  let bytes = [
+    0x12, 0xef, 0xcd, 0xab, // user
+    0x03, 0, 0, 0, // flags: name and bot
+    1, 0, 0, 0, // id
+    2, b'a', b'l', 0, // name
+  ];
+  assert_eq!(
+    serialize(&schema, &Value::Object(user.clone())).unwrap(),
+    bytes
+  );
+  let decoded = Object::new("user")
+    .with("flags", Value::Nat(3))
+    .with("id", Value::Int(1))
+    .with("name", common::string("al"))
+    .with("bot", Value::Bool(true));
+  assert_eq!(
+    deserialize(&schema, &bytes).unwrap(),
+    Value::Object(decoded)
+  );
+
+  // `false` and absent fields leave their bits clear
+  let user = Object::new("user")
+    .with("id", Value::Int(1))
+    .with("bot", Value::Bool(false))
+    .with("age", Value::Int(30));
+  assert_eq!(
+    serialize(&schema, &Value::Object(user)).unwrap(),
+    [0x12, 0xef, 0xcd, 0xab, 0x08, 0, 0, 0, 1, 0, 0, 0, 30, 0, 0, 0]
+  );
+}
+
+#[test]
+fn explicit_flags() {
+  let schema = common::schema(SOURCE);
+  // bits no condition tests are kept
+  let user = Object::new("user")
+    .with("flags", Value::Nat(0x10 | 0x01))
+    .with("id", Value::Int(1))
+    .with("name", common::string("al"));
+  assert_eq!(
+    serialize(&schema, &Value::Object(user)).unwrap()[4..8],
+    [0x11, 0, 0, 0]
+  );
+
+  let user = Object::new("user")
+    .with("flags", Value::Nat(0))
+    .with("id", Value::Int(1))
+    .with("name", common::string("al"));
+  assert_eq!(
+    serialize(&schema, &Value::Object(user)).unwrap_err(),
+    EncodeError::InconsistentFlags {
+      constructor: "user".to_string(),
+      field: "flags".to_string(),
+      value: 0,
+      expected: 1,
+    }
+  );
+}
+
+fn record(x: i32, y: &str) -> Value<'static> {
+  Value::Object(
+    Object::new("")
+      .with("x", Value::Int(x))
+      .with("y", common::string(y)),
+  )
+}
+
+#[test]
+fn block_counted_by_an_earlier_field() {
+  let schema = common::schema(SOURCE);
+  let points =
+    Object::new("points").with("xs", Value::Vector(vec![record(1, "a"), record(2, "b")]));
+  let bytes = [
+    0x8d, 0x7c, 0x6b, 0x5a, // points
+    2, 0, 0, 0, // n
+    1, 0, 0, 0, 1, b'a', 0, 0, // x, y
+    2, 0, 0, 0, 1, b'b', 0, 0, // x, y
+  ];
+  assert_eq!(
+    serialize(&schema, &Value::Object(points.clone())).unwrap(),
+    bytes
+  );
+  let decoded = Object::new("points")
+    .with("n", Value::Nat(2))
+    .with("xs", points.get("xs").unwrap().clone());
+  assert_eq!(
+    deserialize(&schema, &bytes).unwrap(),
+    Value::Object(decoded)
+  );
+
+  let points = Object::new("points")
+    .with("n", Value::Nat(3))
+    .with("xs", Value::Vector(vec![record(1, "a")]));
+  assert_eq!(
+    serialize(&schema, &Value::Object(points)).unwrap_err(),
+    EncodeError::WrongCount {
+      constructor: "points".to_string(),
+      field: "xs".to_string(),
+      expected: 3,
+      found: 1,
+    }
+  );
+}
+
+#[test]
+fn block_without_a_count() {
+  let schema = common::schema(SOURCE);
+  // an unnamed block is keyed by its position and counted by `count`
+  let ints = Object::new("ints").with("1", Value::Vector(vec![Value::Int(7), Value::Int(8)]));
+  let bytes = [0x9e, 0x8d, 0x7c, 0x6b, 2, 0, 0, 0, 7, 0, 0, 0, 8, 0, 0, 0];
+  assert_eq!(
+    serialize(&schema, &Value::Object(ints.clone())).unwrap(),
+    bytes
+  );
+  let decoded = Object::new("ints")
+    .with("count", Value::Nat(2))
+    .with("1", ints.get("1").unwrap().clone());
+  assert_eq!(
+    deserialize(&schema, &bytes).unwrap(),
+    Value::Object(decoded)
+  );
+  assert_eq!(
+    decode_error(&schema, "Ints", &bytes[..12]),
+    DecodeError::Truncated {
+      offset: 12,
+      needed: 4
+    }
+  );
+}